@@ -0,0 +1,224 @@
+use bevy::input::mouse::MouseWheel;
+use bevy::prelude::*;
+use bevy::transform::TransformSystem;
+
+use crate::common::*;
+use crate::explosion::{ExplosionEvent, ExplosionKind};
+use crate::player::{CanonSight, Life, Player};
+use crate::spectator::Spectator;
+
+// Factor by which the zoom changes each second a key is held, and at each notch of the wheel.
+const ZOOM_SPEED: f32 = 2.;
+const WHEEL_ZOOM_STEP: f32 = 1.1;
+// How fast the shown zoom catches up with the one asked for.
+const ZOOM_EASE: f32 = 4.;
+// Space kept around the target of the canon when zooming out to show it.
+const SIGHT_MARGIN: f32 = 40.;
+// Distance beyond which explosions do not shake the screen.
+const SHAKE_RANGE: f32 = 400.;
+// Trauma for each point of life lost by the boat of the player.
+const HULL_TRAUMA: f32 = 0.02;
+// Trauma lost each second.
+const TRAUMA_DECAY: f32 = 0.8;
+const SHAKE_MAX_OFFSET: f32 = 12.;
+const SHAKE_MAX_ANGLE: f32 = 0.05;
+const SHAKE_FREQUENCY: f32 = 15.;
+
+//
+// Misc functions
+//
+
+/// Trauma of an explosion right under the camera.
+fn explosion_trauma(kind: ExplosionKind) -> f32 {
+    match kind {
+        ExplosionKind::Splash => 0.,
+        ExplosionKind::HullHit => 0.2,
+        ExplosionKind::Torpedo | ExplosionKind::Mine => 0.5,
+        ExplosionKind::Magazine => 0.8,
+    }
+}
+
+/// Smooth noise, between -1 and 1, a different one for each channel.
+fn shake_noise(t: f32, channel: f32) -> f32 {
+    let t = SHAKE_FREQUENCY * t + 10. * channel;
+    0.6 * t.sin() + 0.4 * (2.3 * t + channel).sin()
+}
+
+//
+// Resources
+//
+
+/// Options of the camera, set on the command line.
+pub struct CameraSettings {
+    pub min_zoom: f32,
+    pub max_zoom: f32,
+    // Zoom out to keep the target of the canon on screen.
+    pub auto_zoom: bool,
+    // Strength of the screen shake, 0 to disable it.
+    pub shake: f32,
+}
+
+impl Default for CameraSettings {
+    fn default() -> Self {
+        let args: Vec<String> = std::env::args().collect();
+        // Accessibility: no camera moving on its own.
+        let reduced_motion = args.iter().any(|arg| arg == "--reduced-motion");
+        let shake = match args.iter().position(|arg| arg == "--shake") {
+            Some(i) => args
+                .get(i + 1)
+                .and_then(|shake| shake.parse().ok())
+                .unwrap_or(1.),
+            None => 1.,
+        };
+        CameraSettings {
+            min_zoom: 0.25,
+            max_zoom: 2.,
+            auto_zoom: !reduced_motion,
+            shake: if reduced_motion { 0. } else { shake },
+        }
+    }
+}
+
+/// Where the camera looks and how far, before the screen shake.
+pub struct CameraRig {
+    pub centre: Vec2,
+    // Zoom asked for, and the one shown, eased toward what is needed.
+    pub zoom: f32,
+    scale: f32,
+    trauma: f32,
+    player_life: Option<u32>,
+}
+
+impl Default for CameraRig {
+    fn default() -> Self {
+        CameraRig {
+            centre: Vec2::ZERO,
+            zoom: 1.,
+            scale: 1.,
+            trauma: 0.,
+            player_life: None,
+        }
+    }
+}
+
+//
+// Systems
+//
+
+fn camera_zoom(
+    time: Res<Time>,
+    settings: Res<CameraSettings>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut mouse_wheel: EventReader<MouseWheel>,
+    mut rig: ResMut<CameraRig>,
+) {
+    // Page up zooms in, page down zooms out, as well as the wheel.
+    let wheel: f32 = mouse_wheel.iter().map(|event| event.y).sum();
+    let mut zoom = WHEEL_ZOOM_STEP.powf(-wheel);
+    if keyboard_input.pressed(KeyCode::PageUp) {
+        zoom /= ZOOM_SPEED.powf(time.delta_seconds());
+    }
+    if keyboard_input.pressed(KeyCode::PageDown) {
+        zoom *= ZOOM_SPEED.powf(time.delta_seconds());
+    }
+    if zoom != 1. {
+        rig.zoom = (rig.zoom * zoom).clamp(settings.min_zoom, settings.max_zoom);
+    }
+}
+
+fn camera_follow(
+    time: Res<Time>,
+    win_size: Res<WinSize>,
+    settings: Res<CameraSettings>,
+    spectator: Res<Spectator>,
+    mut rig: ResMut<CameraRig>,
+    query_player: Query<(&Transform, &Children), With<Player>>,
+    query_sight: Query<&CanonSight>,
+) {
+    let mut scale = rig.zoom;
+    if !spectator.active {
+        if let Ok((player_tf, children)) = query_player.get_single() {
+            // Zoom out, at most to the whole sea, for the target of the canon to stay on screen.
+            if settings.auto_zoom {
+                let half_view = 0.5 * win_size.w.min(win_size.h);
+                for sight in children
+                    .iter()
+                    .filter_map(|child| query_sight.get(*child).ok())
+                {
+                    let needed = ((sight.0 + SIGHT_MARGIN) / half_view).min(1.);
+                    scale = scale.max(needed);
+                }
+            }
+            // Follow the boat, without looking past the edges of the sea.
+            let bound = (1. - rig.scale).max(0.) * 0.5 * Vec2::new(win_size.w, win_size.h);
+            rig.centre = player_tf.translation.truncate().clamp(-bound, bound);
+        }
+    }
+    let ease = 1. - (-ZOOM_EASE * time.delta_seconds()).exp();
+    rig.scale += ease * (scale - rig.scale);
+}
+
+fn camera_trauma(
+    time: Res<Time>,
+    mut rig: ResMut<CameraRig>,
+    mut explosions: EventReader<ExplosionEvent>,
+    query_player: Query<&Life, With<Player>>,
+) {
+    // Explosions shake the screen, the more the closer they are.
+    let centre = rig.centre.extend(0.);
+    let mut trauma = rig.trauma - TRAUMA_DECAY * time.delta_seconds();
+    for explosion in explosions.iter() {
+        let closeness = 1. - distance(explosion.position, centre) / SHAKE_RANGE;
+        trauma += explosion_trauma(explosion.kind) * closeness.max(0.);
+    }
+    // As well as hits on the hull of the player.
+    let life = query_player.get_single().ok().map(|life| life.0);
+    if let (Some(previous), Some(life)) = (rig.player_life, life) {
+        trauma += HULL_TRAUMA * previous.saturating_sub(life) as f32;
+    }
+    rig.player_life = life;
+    rig.trauma = trauma.clamp(0., 1.);
+}
+
+fn camera_update(
+    time: Res<Time>,
+    settings: Res<CameraSettings>,
+    rig: Res<CameraRig>,
+    mut query_camera: Query<(&mut Transform, &mut OrthographicProjection), With<MainCamera>>,
+) {
+    let shake = settings.shake * rig.trauma * rig.trauma;
+    let t = time.seconds_since_startup() as f32;
+    for (mut camera_tf, mut projection) in query_camera.iter_mut() {
+        let offset = SHAKE_MAX_OFFSET * shake * Vec2::new(shake_noise(t, 0.), shake_noise(t, 1.));
+        camera_tf.translation.x = rig.centre.x + offset.x;
+        camera_tf.translation.y = rig.centre.y + offset.y;
+        camera_tf.rotation = Quat::from_rotation_z(SHAKE_MAX_ANGLE * shake * shake_noise(t, 2.));
+        if projection.scale != rig.scale {
+            projection.scale = rig.scale;
+        }
+    }
+}
+
+//
+// Plugin
+//
+
+/// Zoom with the wheel or PgUp/PgDn, and shake the screen on explosions.
+pub struct CameraPlugin;
+
+impl Plugin for CameraPlugin {
+    fn build(&self, app: &mut App) {
+        // Out of the game systems, to zoom even when a replay is paused.
+        app.init_resource::<CameraSettings>()
+            .init_resource::<CameraRig>()
+            .add_system_to_stage(CoreStage::PostUpdate, camera_zoom)
+            .add_system_to_stage(CoreStage::PostUpdate, camera_follow.after(camera_zoom))
+            .add_system_to_stage(CoreStage::PostUpdate, camera_trauma.after(camera_follow))
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                camera_update
+                    .after(camera_trauma)
+                    .before(TransformSystem::TransformPropagate),
+            );
+    }
+}
@@ -2,11 +2,82 @@ use std::collections::HashMap;
 
 use bevy::{prelude::*, sprite::collide_aabb::collide};
 
-use crate::common::WinSize;
-use crate::explosion::ExplosionToSpawn;
-use crate::island::Mountain;
+use crate::common::*;
+use crate::explosion::{ExplosionEvent, ExplosionKind, HitEvent, Weapon};
+use crate::island::{Height, Mountain};
+use crate::player::{Life, CANON_MAX_DISTANCE, CANON_MIN_DISTANCE};
+use crate::weather::Weather;
 
-const CANONBALL_SPEED: f32 = 150.;
+const GRAVITY: f32 = 100.;
+const CANON_MIN_ELEVATION: f32 = std::f32::consts::PI / 36.;
+const CANON_MAX_ELEVATION: f32 = std::f32::consts::PI / 4.;
+const CANONBALL_DAMAGE: u32 = 10;
+// Part of the wind speed transmitted to canonballs in flight.
+const CANONBALL_WINDAGE: f32 = 0.5;
+// Altitude at which the canonball looks twice as big.
+const ALTITUDE_SCALE: f32 = 100.;
+// Vertical offset on screen between the canonball and its shadow, per unit of altitude.
+const SHADOW_OFFSET: f32 = 0.5;
+
+//
+// Misc functions
+//
+
+/// Compute the muzzle velocity and the elevation of the canon to hit a target at the given
+/// distance: the further the target, the higher the canon is raised.
+pub fn firing_solution(distance: f32) -> (f32, f32) {
+    let ratio =
+        ((distance - CANON_MIN_DISTANCE) / (CANON_MAX_DISTANCE - CANON_MIN_DISTANCE)).clamp(0., 1.);
+    let elevation = CANON_MIN_ELEVATION + ratio * (CANON_MAX_ELEVATION - CANON_MIN_ELEVATION);
+    let velocity = (distance * GRAVITY / (2. * elevation).sin()).sqrt();
+    (velocity, elevation)
+}
+
+/// Spawn a canonball (and its shadow) fired from origin toward a target at the given distance.
+pub fn canonball_spawn(
+    commands: &mut Commands,
+    sprite_materials: &SpriteMaterials,
+    origin: Vec3,
+    rotation: Quat,
+    distance: f32,
+) -> Entity {
+    let (velocity, elevation) = firing_solution(distance);
+    // The canonball entity is its shadow on the sea, the canonball itself is drawn as a child.
+    commands
+        .spawn_bundle(SpriteSheetBundle {
+            texture_atlas: sprite_materials.texture.clone(),
+            sprite: TextureAtlasSprite {
+                index: sprite_materials.canonball_index,
+                color: Color::rgba(0., 0., 0., 0.4),
+                ..Default::default()
+            },
+            transform: Transform {
+                translation: origin,
+                rotation,
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .insert(CanonBall)
+        .insert(Energy(distance))
+        .insert(Flight {
+            range: distance,
+            speed: velocity * elevation.cos(),
+            slope: elevation.tan(),
+        })
+        .insert(Altitude(0.))
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(SpriteSheetBundle {
+                    texture_atlas: sprite_materials.texture.clone(),
+                    sprite: TextureAtlasSprite::new(sprite_materials.canonball_index),
+                    transform: Transform::from_xyz(0., 0., 0.1),
+                    ..Default::default()
+                })
+                .insert(CanonBallSprite);
+        })
+        .id()
+}
 
 //
 // Components
@@ -15,53 +86,159 @@ const CANONBALL_SPEED: f32 = 150.;
 #[derive(Component)]
 pub struct CanonBall;
 
+/// Horizontal distance left to travel before landing.
 #[derive(Component)]
 pub struct Energy(pub f32);
 
+/// Ballistic parameters of a canonball, set when fired.
+#[derive(Component)]
+pub struct Flight {
+    // Horizontal distance between the canon and the landing point.
+    pub range: f32,
+    // Horizontal speed of the canonball.
+    pub speed: f32,
+    // Tangent of the elevation of the canon.
+    pub slope: f32,
+}
+
+#[derive(Component)]
+pub struct Altitude(pub f32);
+
+/// Boat the canonball was fired from.
+#[derive(Component, Clone, Copy)]
+pub struct FiredBy(pub Entity);
+
+#[derive(Component)]
+struct CanonBallSprite;
+
 //
 // Systems
 //
 
 fn canonball_movement(
     mut commands: Commands,
-    time: Res<Time>,
+    time: Res<GameTime>,
     win_size: Res<WinSize>,
-    mut query: Query<(Entity, &mut Transform, &mut Energy, With<CanonBall>)>,
+    weather: Res<Weather>,
+    mut query: Query<
+        (Entity, &mut Transform, &mut Energy, &Flight, &mut Altitude),
+        With<CanonBall>,
+    >,
 ) {
-    for (canonball_entity, mut canonball_tf, mut energy, _) in query.iter_mut() {
+    for (canonball_entity, mut canonball_tf, mut energy, flight, mut altitude) in query.iter_mut() {
+        // Move canonball according to energy left.
+        let distance = energy.0.min(flight.speed * time.delta_seconds());
+        energy.0 -= distance;
+        let translation = canonball_tf.rotation.mul_vec3(Vec3::new(distance, 0., 0.));
+        canonball_tf.translation += translation;
+        // Canonballs are deflected by the wind.
+        canonball_tf.translation +=
+            (CANONBALL_WINDAGE * weather.wind() * time.delta_seconds()).extend(0.);
+        // Follow the parabola going through the canon and the landing point.
+        altitude.0 = flight.slope * (flight.range - energy.0) * energy.0 / flight.range;
+        // Remove canonball if off screen.
+        if (canonball_tf.translation.y < -0.5 * win_size.h)
+            || (0.5 * win_size.h < canonball_tf.translation.y)
+            || (canonball_tf.translation.x < -0.5 * win_size.w)
+            || (0.5 * win_size.w < canonball_tf.translation.x)
+        {
+            commands.entity(canonball_entity).despawn_recursive();
+        }
+    }
+}
+
+fn canonball_altitude(
+    query_canonball: Query<(&Transform, &Altitude), With<CanonBall>>,
+    mut query_sprite: Query<(&Parent, &mut Transform), (With<CanonBallSprite>, Without<CanonBall>)>,
+) {
+    for (parent, mut sprite_tf) in query_sprite.iter_mut() {
+        if let Ok((canonball_tf, altitude)) = query_canonball.get(parent.0) {
+            // Raise and scale the canonball above its shadow.
+            sprite_tf.translation = canonball_tf.rotation.inverse().mul_vec3(Vec3::new(
+                0.,
+                SHADOW_OFFSET * altitude.0,
+                0.1,
+            ));
+            sprite_tf.scale = Vec3::splat(1. + altitude.0 / ALTITUDE_SCALE);
+        }
+    }
+}
+
+fn canonball_landing(
+    mut commands: Commands,
+    friendly_fire: Res<FriendlyFire>,
+    mut explosions: EventWriter<ExplosionEvent>,
+    mut hits: EventWriter<HitEvent>,
+    query: Query<(Entity, &Transform, &Energy, Option<&FiredBy>), With<CanonBall>>,
+    mut query_hull: Query<
+        (
+            Entity,
+            &Transform,
+            &TextureAtlasSprite,
+            &mut Life,
+            Option<&Team>,
+        ),
+        Without<CanonBall>,
+    >,
+    query_team: Query<&Team>,
+) {
+    for (canonball_entity, canonball_tf, energy, fired_by) in query.iter() {
         if energy.0 != 0. {
-            // Move canonball according to energy left.
-            let distance = energy.0.min(CANONBALL_SPEED * time.delta_seconds());
-            energy.0 -= distance;
-            let translation = canonball_tf.rotation.mul_vec3(Vec3::new(distance, 0., 0.));
-            canonball_tf.translation += translation;
-            // Remove canonball if off screen.
-            if (canonball_tf.translation.y < -0.5 * win_size.h)
-                || (0.5 * win_size.h < canonball_tf.translation.y)
-                || (canonball_tf.translation.x < -0.5 * win_size.w)
-                || (0.5 * win_size.w < canonball_tf.translation.x)
-            {
-                commands.entity(canonball_entity).despawn();
+            continue;
+        }
+        commands.entity(canonball_entity).despawn_recursive();
+        // Damage the hull the canonball landed on, if any.
+        let shooter = fired_by.map(|fired_by| fired_by.0);
+        let source = shooter.and_then(|shooter| query_team.get(shooter).ok());
+        let mut hit = false;
+        for (hull_entity, hull_tf, sprite, mut life, team) in query_hull.iter_mut() {
+            if let Some(size) = sprite.custom_size {
+                if hull_contains(hull_tf, size, canonball_tf.translation) {
+                    // A hull the shot spares only gets a splash.
+                    hit = harms(&friendly_fire, source, team);
+                    if hit {
+                        let before = life.0;
+                        life.0 -= life.0.min(CANONBALL_DAMAGE);
+                        hits.send(HitEvent::new(
+                            shooter,
+                            hull_entity,
+                            Weapon::Canon,
+                            before,
+                            life.0,
+                        ));
+                    }
+                    break;
+                }
             }
-        } else {
-            // No more energy: replace canonball by an explosion
-            commands.entity(canonball_entity).despawn();
-            commands
-                .spawn()
-                .insert(ExplosionToSpawn(canonball_tf.translation.clone()));
         }
+        // Replace the canonball by an explosion or a splash.
+        explosions.send(ExplosionEvent {
+            position: canonball_tf.translation,
+            kind: if hit {
+                ExplosionKind::HullHit
+            } else {
+                ExplosionKind::Splash
+            },
+            radius: 0.,
+            source: shooter,
+        });
     }
 }
 
 fn canonball_mountain_collision(
     mut commands: Commands,
-    mut query_canonball: Query<(Entity, &Transform), With<CanonBall>>,
-    query_moutain: Query<&Transform, With<Mountain>>,
+    mut explosions: EventWriter<ExplosionEvent>,
+    mut query_canonball: Query<(Entity, &Transform, &Altitude), With<CanonBall>>,
+    query_moutain: Query<(&Transform, &Height), With<Mountain>>,
 ) {
     let mut despawned = HashMap::new();
     // for each canonball & mountain.
-    for (canonball_entity, canonball_tf) in query_canonball.iter_mut() {
-        for mountain_tf in query_moutain.iter() {
+    for (canonball_entity, canonball_tf, altitude) in query_canonball.iter_mut() {
+        for (mountain_tf, height) in query_moutain.iter() {
+            // Canonballs fly over the mountains lower than them.
+            if altitude.0 >= height.0 {
+                continue;
+            }
             // Check for collision.
             let collision = collide(
                 canonball_tf.translation,
@@ -73,11 +250,14 @@ fn canonball_mountain_collision(
             if collision.is_some() {
                 if !despawned.contains_key(&canonball_entity) {
                     despawned.insert(canonball_entity, true);
-                    commands.entity(canonball_entity).despawn();
+                    commands.entity(canonball_entity).despawn_recursive();
                 }
-                commands
-                    .spawn()
-                    .insert(ExplosionToSpawn(canonball_tf.translation.clone()));
+                explosions.send(ExplosionEvent {
+                    position: mountain_tf.translation,
+                    kind: ExplosionKind::HullHit,
+                    radius: 0.,
+                    source: None,
+                });
             }
         }
     }
@@ -91,7 +271,14 @@ pub struct CanonBallPlugin;
 
 impl Plugin for CanonBallPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system(canonball_movement)
-            .add_system(canonball_mountain_collision);
+        app.add_system_set(
+            SystemSet::new()
+                .label(GameSystem::CanonBalls)
+                .after(GameSystem::Ships)
+                .with_system(canonball_movement)
+                .with_system(canonball_altitude.after(canonball_movement))
+                .with_system(canonball_landing.after(canonball_altitude))
+                .with_system(canonball_mountain_collision.after(canonball_landing)),
+        );
     }
 }
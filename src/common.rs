@@ -1,5 +1,9 @@
+use std::time::Duration;
+
 use bevy::prelude::*;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
 use bevy_kira_audio::{AudioChannel, AudioSource};
+use rand::{rngs::StdRng, SeedableRng};
 
 // Layers to position the sprites
 pub const WEAPON_Z: f32 = 6.;
@@ -8,12 +12,168 @@ pub const BOAT_Z: f32 = 4.;
 pub const MOUNTAIN_Z: f32 = 3.;
 pub const GROUND_Z: f32 = 2.;
 pub const TORPEDO_Z: f32 = 1.;
-// pub const WATER_Z: f32 = 0.;
+pub const WAKE_Z: f32 = 0.5;
+pub const SURF_Z: f32 = 0.2;
+pub const WATER_Z: f32 = 0.;
+
+pub const NUM_TEAMS: usize = 2;
+
+//
+// Misc functions
+//
+
+/// Distance between two positions, regardless of their layers.
+pub fn distance(a: Vec3, b: Vec3) -> f32 {
+    a.truncate().distance(b.truncate())
+}
+
+/// Check if a point lies within the hull of a boat of the given size.
+pub fn hull_contains(hull_tf: &Transform, size: Vec2, point: Vec3) -> bool {
+    let local = hull_tf
+        .rotation
+        .inverse()
+        .mul_vec3(point - hull_tf.translation);
+    local.x.abs() <= size.x / 2. && local.y.abs() <= size.y / 2.
+}
+
+/// Plain white image, to be tinted by the sprites using it.
+pub fn blank_image() -> Image {
+    Image::new_fill(
+        Extent3d {
+            width: 1,
+            height: 1,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &[255, 255, 255, 255],
+        TextureFormat::Rgba8UnormSrgb,
+    )
+}
+
+/// Whether a hit from a weapon of the given team damages a target of the given team.
+pub fn harms(friendly_fire: &FriendlyFire, source: Option<&Team>, target: Option<&Team>) -> bool {
+    friendly_fire.0 || source.is_none() || source != target
+}
+
+/// Tile of the terrain grid under the given position.
+pub fn tile_of(position: Vec3) -> (i32, i32) {
+    (
+        (position.x / 16.).round() as i32,
+        (position.y / 16.).round() as i32,
+    )
+}
+
+//
+// Components
+//
+
+/// Side a boat or a structure belongs to.
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Team(pub usize);
+
+impl Team {
+    pub fn name(&self) -> &'static str {
+        match self.0 {
+            0 => "Blue",
+            _ => "Red",
+        }
+    }
+
+    /// Tint of the sprites of the team.
+    pub fn color(&self) -> Color {
+        match self.0 {
+            0 => Color::rgb(0.6, 0.75, 1.),
+            _ => Color::rgb(1., 0.6, 0.6),
+        }
+    }
+}
+
+/// Camera of the sea, as opposed to the one of the interface.
+#[derive(Component)]
+pub struct MainCamera;
 
 //
 // Resources
 //
 
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GameMode {
+    FreeForAll,
+    CaptureTheFlag,
+    // Fleets of each team, until a single one is left afloat.
+    TeamBattle,
+    // Scripted mission, read from a file.
+    Mission,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+pub enum GameState {
+    Playing,
+    Over,
+    // End of a mission.
+    Won,
+    Lost,
+    // Game hosted elsewhere, only mirrored.
+    Watching,
+}
+
+/// Order in which the systems of the simulation run within an update, one after the other, so
+/// that a game plays the same way from the same seed and actions.
+#[derive(SystemLabel, Clone, PartialEq, Eq, Debug, Hash)]
+pub enum GameSystem {
+    Weather,
+    Ships,
+    CanonBalls,
+    Torpedos,
+    Mines,
+    Ports,
+    Islands,
+    Explosions,
+}
+
+/// Source of all the randomness of the game, so that a game can be played again from its seed.
+#[derive(Clone)]
+pub struct GameRng {
+    pub seed: u64,
+    pub rng: StdRng,
+}
+
+impl GameRng {
+    pub fn new(seed: u64) -> Self {
+        GameRng {
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+}
+
+/// Whether weapons damage the boats and bunkers of their own team.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct FriendlyFire(pub bool);
+
+impl Default for FriendlyFire {
+    fn default() -> Self {
+        FriendlyFire(true)
+    }
+}
+
+/// Time elapsed in the game since the previous update, which the game systems advance by.
+/// It follows the clock, except when recording or replaying where it is a fixed tick.
+#[derive(Default)]
+pub struct GameTime {
+    pub delta: Duration,
+}
+
+impl GameTime {
+    pub fn delta(&self) -> Duration {
+        self.delta
+    }
+
+    pub fn delta_seconds(&self) -> f32 {
+        self.delta.as_secs_f32()
+    }
+}
+
 pub struct WinSize {
     pub w: f32,
     pub h: f32,
@@ -35,15 +195,27 @@ pub struct SpriteMaterials {
     pub mountain_index: usize,
     pub canon_sight_index: usize,
     pub torpedo_sight_index: usize,
+    pub blue_flag_index: usize,
+    pub red_flag_index: usize,
     pub explosion: Handle<TextureAtlas>,
 }
 
+impl SpriteMaterials {
+    pub fn flag_index(&self, team: Team) -> usize {
+        match team.0 {
+            0 => self.blue_flag_index,
+            _ => self.red_flag_index,
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct AudioMaterials {
     // Sounds
     pub canon_sound: Handle<AudioSource>,
     pub torpedo_sound: Handle<AudioSource>,
     pub explosion_sound: Handle<AudioSource>,
+    pub splash_sound: Handle<AudioSource>,
     pub engine_sound: Handle<AudioSource>,
     // Channels
     pub weapon_channel: AudioChannel,
@@ -0,0 +1,432 @@
+use bevy::prelude::*;
+
+use crate::common::*;
+use crate::island::TileMap;
+use crate::player::{
+    canon_fire_at, ship_spawn, Amunitions, CanonSight, Draft, Player, PlayerClass, Ship, ShipClass,
+    ShipSunk, Speed, Steering, CANON_MAX_DISTANCE,
+};
+use crate::port::{alongside, port_spawn};
+use crate::spectator::Spectator;
+
+const SHIPS_PER_TEAM: usize = 1;
+const RESPAWN_DELAY: f32 = 5.;
+const CAPTURES_TO_WIN: u32 = 3;
+// Part of its maximum speed a boat carrying a flag can reach.
+const CARRIER_SPEED_FACTOR: f32 = 0.5;
+// Distance under which a raider opens fire on an enemy boat.
+const RAIDER_FIRE_RANGE: f32 = 0.8 * CANON_MAX_DISTANCE;
+// Distance from its destination over which a raider slows down, to stop alongside.
+const RAIDER_APPROACH: f32 = 200.;
+// Distance ahead of its boat over which a raider looks out for shallow water.
+const RAIDER_LOOKOUT: f32 = 48.;
+
+//
+// Misc functions
+//
+
+/// Spawn the base of a team on the given ground tile, with its flag.
+pub fn base_spawn(
+    commands: &mut Commands,
+    sprite_materials: &SpriteMaterials,
+    tile: (i32, i32),
+    dock: Vec3,
+    team: Team,
+) {
+    let base = port_spawn(commands, sprite_materials, tile, Some(team));
+    commands.entity(base).insert(Base { dock });
+    let home = Vec3::new(16. * tile.0 as f32, 16. * tile.1 as f32, WEAPON_Z);
+    commands
+        .spawn_bundle(SpriteSheetBundle {
+            texture_atlas: sprite_materials.texture.clone(),
+            sprite: TextureAtlasSprite::new(sprite_materials.flag_index(team)),
+            transform: Transform::from_translation(home),
+            ..Default::default()
+        })
+        .insert(Flag { home })
+        .insert(team);
+}
+
+/// Throttle and rudder of a raider heading for the given destination, going round shallow
+/// water, and slowing down on the way to stop there.
+fn raider_course(
+    tile_map: &TileMap,
+    ship_tf: &Transform,
+    draft: f32,
+    destination: Vec3,
+) -> (f32, f32) {
+    let position = ship_tf.translation;
+    let open = |direction: Vec2| {
+        (1..=3).all(|step| {
+            let lookout = position + (step as f32 * RAIDER_LOOKOUT / 3. * direction).extend(0.);
+            tile_map.depth(tile_of(lookout)) >= draft
+        })
+    };
+    // Aground, the raider backs off.
+    if tile_map.depth(tile_of(position)) < draft {
+        return (-1., 0.);
+    }
+    // The direction of the destination, or the closest one in open water.
+    let to_destination = (destination - position).truncate();
+    let bearing = to_destination.y.atan2(to_destination.x);
+    let course = (0..=6)
+        .flat_map(|i| [i as f32, -(i as f32)])
+        .map(|i| bearing + i * std::f32::consts::PI / 6.)
+        .map(|angle| Vec2::new(angle.cos(), angle.sin()))
+        .find(|direction| open(*direction))
+        .unwrap_or_else(|| to_destination.normalize_or_zero());
+    let ahead = ship_tf.rotation.mul_vec3(Vec3::X).truncate();
+    let turn = ahead.perp_dot(course).atan2(ahead.dot(course));
+    // Turning on the spot first when the course is far off the heading.
+    let throttle = if turn.abs() > std::f32::consts::FRAC_PI_4 {
+        0.
+    } else {
+        (distance(destination, position) / RAIDER_APPROACH).min(1.)
+    };
+    (throttle, (2. * turn).clamp(-1., 1.))
+}
+
+fn scoreboard_string(scores: &Scores, winner: Option<Team>) -> String {
+    let mut scoreboard = format!(
+        "{} {} - {} {}",
+        Team(0).name(),
+        scores.0[0],
+        scores.0[1],
+        Team(1).name()
+    );
+    if let Some(team) = winner {
+        scoreboard += &format!("\n{} wins!", team.name());
+    }
+    scoreboard
+}
+
+//
+// Components
+//
+
+/// Port of a team, where its boats spawn and bring back enemy flags.
+#[derive(Component)]
+pub struct Base {
+    pub dock: Vec3,
+}
+
+#[derive(Component)]
+pub struct Flag {
+    pub home: Vec3,
+}
+
+/// Boat carrying a flag.
+#[derive(Component)]
+pub struct CarriedBy(pub Entity);
+
+/// Boat sailed by its crew, after the enemy flag and back home with it.
+#[derive(Component)]
+pub struct Raider;
+
+#[derive(Component)]
+struct ScoreBoard;
+
+//
+// Resources
+//
+
+/// Flags captured by each team.
+#[derive(Default)]
+pub struct Scores(pub [u32; NUM_TEAMS]);
+
+/// Boats waiting to be spawned at their base.
+struct Respawns(Vec<(Team, bool, Timer)>);
+
+impl Default for Respawns {
+    fn default() -> Self {
+        // All the boats are spawned at start, the first of the first team being the player.
+        let mut respawns = Vec::new();
+        for team in 0..NUM_TEAMS {
+            for ship in 0..SHIPS_PER_TEAM {
+                let player = team == 0 && ship == 0;
+                respawns.push((Team(team), player, Timer::from_seconds(0., false)));
+            }
+        }
+        Respawns(respawns)
+    }
+}
+
+//
+// Systems
+//
+
+fn scoreboard_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                align_self: AlignSelf::FlexEnd,
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(5.0),
+                    right: Val::Px(15.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                scoreboard_string(&Scores::default(), None),
+                TextStyle {
+                    font: asset_server.load("FiraSans-Bold.ttf"),
+                    font_size: 20.0,
+                    color: Color::BLACK,
+                },
+                Default::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(ScoreBoard);
+}
+
+fn ship_respawn(
+    mut commands: Commands,
+    time: Res<GameTime>,
+    sprite_materials: Res<SpriteMaterials>,
+    spectator: Res<Spectator>,
+    player_class: Res<PlayerClass>,
+    mut respawns: ResMut<Respawns>,
+    mut ship_sunk: EventReader<ShipSunk>,
+    query_base: Query<(&Base, &Team)>,
+) {
+    // Sunk boats come back at their base after a delay.
+    for sunk in ship_sunk.iter() {
+        respawns.0.push((
+            sunk.team,
+            sunk.player,
+            Timer::from_seconds(RESPAWN_DELAY, false),
+        ));
+    }
+    for (team, player, timer) in respawns.0.iter_mut() {
+        timer.tick(time.delta());
+        if !timer.finished() {
+            continue;
+        }
+        if let Some((base, _)) = query_base.iter().find(|(_, base_team)| *base_team == team) {
+            // Boats leave their base heading toward the center of the sea.
+            let angle = if base.dock.x < 0. {
+                0.
+            } else {
+                std::f32::consts::PI
+            };
+            let player = *player && !spectator.read_only;
+            let class = if player {
+                player_class.0
+            } else {
+                ShipClass::Frigate
+            };
+            let ship = ship_spawn(
+                &mut commands,
+                &sprite_materials,
+                base.dock,
+                angle,
+                *team,
+                class,
+            );
+            if player {
+                commands.entity(ship).insert(Player);
+            } else {
+                commands.entity(ship).insert(Raider).insert(Steering {
+                    throttle: 0.,
+                    rudder: 0.,
+                });
+            }
+        }
+    }
+    respawns.0.retain(|(_, _, timer)| !timer.finished());
+}
+
+fn raider_orders(
+    mut commands: Commands,
+    sprite_materials: Res<SpriteMaterials>,
+    tile_map: Res<TileMap>,
+    mut query_raider: Query<(Entity, &Transform, &Team, &Draft, &mut Steering), With<Raider>>,
+    query_flag: Query<(&Team, &CarriedBy), With<Flag>>,
+    query_base: Query<(&Base, &Team)>,
+    query_ship: Query<(Entity, &Transform, &Team), With<Ship>>,
+    mut query_sight: Query<(&Parent, &mut Amunitions, &mut Timer), With<CanonSight>>,
+) {
+    for (ship, ship_tf, team, draft, mut steering) in query_raider.iter_mut() {
+        let position = ship_tf.translation;
+        let dock = |home: bool| {
+            query_base
+                .iter()
+                .find(|(_, base_team)| (*base_team == team) == home)
+                .map(|(base, _)| base.dock)
+        };
+        // Bring the enemy flag home, chase the enemy carrying ours, or go for the enemy flag.
+        let carrying = query_flag
+            .iter()
+            .any(|(_, carried_by)| carried_by.0 == ship);
+        let thief = query_flag
+            .iter()
+            .find(|(flag_team, _)| *flag_team == team)
+            .and_then(|(_, carried_by)| query_ship.get(carried_by.0).ok())
+            .map(|(_, thief_tf, _)| thief_tf.translation);
+        let destination = if carrying {
+            dock(true)
+        } else {
+            thief.or_else(|| dock(false))
+        };
+        let destination = match destination {
+            Some(destination) => destination,
+            None => continue,
+        };
+
+        let (throttle, rudder) = raider_course(&tile_map, ship_tf, draft.0, destination);
+        steering.throttle = throttle;
+        steering.rudder = rudder;
+
+        // Fire at the closest enemy within range.
+        let enemy = query_ship
+            .iter()
+            .filter(|(_, enemy_tf, enemy_team)| {
+                *enemy_team != team && distance(enemy_tf.translation, position) < RAIDER_FIRE_RANGE
+            })
+            .map(|(_, enemy_tf, _)| enemy_tf.translation)
+            .min_by(|a, b| {
+                distance(*a, position)
+                    .partial_cmp(&distance(*b, position))
+                    .unwrap()
+            });
+        let target = match enemy {
+            Some(target) => target,
+            None => continue,
+        };
+        for (parent, mut amunitions, mut timer) in query_sight.iter_mut() {
+            if parent.0 == ship {
+                canon_fire_at(
+                    &mut commands,
+                    &sprite_materials,
+                    ship,
+                    ship_tf,
+                    target,
+                    &mut amunitions,
+                    &mut timer,
+                );
+            }
+        }
+    }
+}
+
+fn flag_pickup(
+    mut commands: Commands,
+    query_flag: Query<(Entity, &Flag, &Team, &Transform), Without<CarriedBy>>,
+    query_ship: Query<(Entity, &Transform, &Speed, &Team), With<Ship>>,
+) {
+    for (flag_entity, flag, flag_team, flag_tf) in query_flag.iter() {
+        // A flag is taken by an enemy boat stopped alongside its base.
+        if flag_tf.translation != flag.home {
+            continue;
+        }
+        if let Some((ship_entity, _, _, _)) =
+            query_ship.iter().find(|(_, ship_tf, speed, ship_team)| {
+                *ship_team != flag_team && alongside(flag.home, ship_tf.translation, speed.0)
+            })
+        {
+            commands.entity(flag_entity).insert(CarriedBy(ship_entity));
+        }
+    }
+}
+
+fn flag_carrying(
+    mut commands: Commands,
+    mut query_flag: Query<(Entity, &Flag, &CarriedBy, &mut Transform)>,
+    mut query_ship: Query<(&Transform, &ShipClass, &mut Speed), (With<Ship>, Without<Flag>)>,
+) {
+    for (flag_entity, flag, carried_by, mut flag_tf) in query_flag.iter_mut() {
+        match query_ship.get_mut(carried_by.0) {
+            Ok((ship_tf, class, mut speed)) => {
+                // The flag follows its carrier, slowed down by the load.
+                flag_tf.translation = ship_tf.translation.truncate().extend(WEAPON_Z);
+                let max_speed = CARRIER_SPEED_FACTOR * class.spec().max_speed;
+                speed.0 = speed.0.clamp(-max_speed, max_speed);
+            }
+            Err(_) => {
+                // The carrier sunk: the flag goes back home.
+                flag_tf.translation = flag.home;
+                commands.entity(flag_entity).remove::<CarriedBy>();
+            }
+        }
+    }
+}
+
+fn flag_capture(
+    mut commands: Commands,
+    mut scores: ResMut<Scores>,
+    mut game_state: ResMut<State<GameState>>,
+    mut query_carried: Query<(Entity, &Flag, &CarriedBy, &mut Transform)>,
+    query_home: Query<&Team, (With<Flag>, Without<CarriedBy>)>,
+    query_base: Query<(&Transform, &Team), (With<Base>, Without<Flag>)>,
+    query_ship: Query<(&Transform, &Speed, &Team), (With<Ship>, Without<Flag>)>,
+) {
+    for (flag_entity, flag, carried_by, mut flag_tf) in query_carried.iter_mut() {
+        let (ship_tf, speed, ship_team) = match query_ship.get(carried_by.0) {
+            Ok(ship) => ship,
+            Err(_) => continue,
+        };
+        // A flag is captured when brought back to a base whose own flag is home.
+        let at_base = query_base.iter().any(|(base_tf, base_team)| {
+            base_team == ship_team && alongside(base_tf.translation, ship_tf.translation, speed.0)
+        });
+        let own_flag_home = query_home.iter().any(|flag_team| flag_team == ship_team);
+        if !(at_base && own_flag_home) {
+            continue;
+        }
+        scores.0[ship_team.0] += 1;
+        flag_tf.translation = flag.home;
+        commands.entity(flag_entity).remove::<CarriedBy>();
+        if scores.0[ship_team.0] >= CAPTURES_TO_WIN {
+            if let Err(err) = game_state.set(GameState::Over) {
+                warn!("Cannot end the game: {:?}", err);
+            }
+            break;
+        }
+    }
+}
+
+fn update_scoreboard(scores: Res<Scores>, mut query: Query<&mut Text, With<ScoreBoard>>) {
+    let winner = (0..NUM_TEAMS)
+        .find(|team| scores.0[*team] >= CAPTURES_TO_WIN)
+        .map(Team);
+    for mut text in query.iter_mut() {
+        text.sections[0].value = scoreboard_string(&scores, winner);
+    }
+}
+
+//
+// Plugin
+//
+
+pub struct CtfPlugin;
+
+impl Plugin for CtfPlugin {
+    fn build(&self, app: &mut App) {
+        if app.world.get_resource::<GameMode>() != Some(&GameMode::CaptureTheFlag) {
+            return;
+        }
+        app.init_resource::<Scores>()
+            .init_resource::<Respawns>()
+            .add_startup_system(scoreboard_setup)
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    // Orders given before the boats steer and fire.
+                    .after(GameSystem::Weather)
+                    .before(GameSystem::Ships)
+                    .with_system(ship_respawn)
+                    .with_system(raider_orders.after(ship_respawn)),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    // Once the boats moved, fought and sank.
+                    .after(GameSystem::Explosions)
+                    .with_system(flag_pickup)
+                    .with_system(flag_carrying.after(flag_pickup))
+                    .with_system(flag_capture.after(flag_carrying)),
+            )
+            .add_system(update_scoreboard);
+    }
+}
@@ -1,34 +1,147 @@
-use crate::player::{Amunitions, Life, Player, Speed, Torpedos, AMUNITIONS, LIFE, TORPEDOS};
+use crate::player::{
+    Amunitions, CanonSight, Life, Mines, Player, Speed, Torpedos, AMUNITIONS, LIFE, MINES, TORPEDOS,
+};
+use crate::port::{stock_level, Docked};
+use crate::torpedo::{TorpedoKind, TorpedoTube};
+use crate::weather::Weather;
 use bevy::prelude::*;
 
+// Speed of the boat, in knots, for a speed of 1.
+const KNOTS_PER_SPEED: f32 = 20.;
+const METRES_PER_PIXEL: f32 = 2.;
+const BAR_WIDTH: f32 = 120.;
+const BAR_HEIGHT: f32 = 10.;
+
 //
 // Misc functions
 //
 
-fn dashboard_string(life: u32, speed: f32, amunitions: u32, torpedos: u32) -> String {
-    String::from(format!(
-        "Life: {}\nSpeed: {}\nAmunitions: {}\nTorpedos: {}",
-        life, speed, amunitions, torpedos
-    ))
+/// Arrow pointing in the direction of the given angle.
+fn arrow(angle: f32) -> char {
+    let octant = (angle / std::f32::consts::FRAC_PI_4).round() as i32;
+    ['→', '↗', '↑', '↖', '←', '↙', '↓', '↘'][octant.rem_euclid(8) as usize]
+}
+
+/// Compass heading, in degrees clockwise from north, of the given angle.
+pub fn compass(angle: f32) -> f32 {
+    (90. - angle.to_degrees()).rem_euclid(360.)
+}
+
+/// Progress of a reload timer, from 0 (just fired) to 1 (ready).
+pub fn reload_progress(timer: &Timer) -> f32 {
+    if timer.finished() {
+        1.
+    } else {
+        timer.percent()
+    }
+}
+
+fn life_color(life: u32) -> Color {
+    match 100 * life / LIFE {
+        61.. => Color::rgb(0.2, 0.8, 0.2),
+        31..=60 => Color::rgb(0.9, 0.8, 0.1),
+        _ => Color::rgb(0.9, 0.1, 0.1),
+    }
+}
+
+/// Spawn a labelled progress bar, whose fill is the given gauge.
+fn spawn_bar(parent: &mut ChildBuilder, font: &Handle<Font>, label: &str, gauge: Gauge) {
+    parent
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                flex_direction: FlexDirection::Row,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            color: Color::NONE.into(),
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                style: Style {
+                    size: Size::new(Val::Px(60.), Val::Auto),
+                    ..Default::default()
+                },
+                text: Text::with_section(label, text_style(font), Default::default()),
+                ..Default::default()
+            });
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(BAR_WIDTH), Val::Px(BAR_HEIGHT)),
+                        ..Default::default()
+                    },
+                    color: Color::rgba(0., 0., 0., 0.5).into(),
+                    ..Default::default()
+                })
+                .with_children(|parent| {
+                    parent
+                        .spawn_bundle(NodeBundle {
+                            style: Style {
+                                size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                                ..Default::default()
+                            },
+                            color: Color::WHITE.into(),
+                            ..Default::default()
+                        })
+                        .insert(gauge);
+                });
+        });
+}
+
+fn spawn_readout(parent: &mut ChildBuilder, font: &Handle<Font>, readout: Readout) {
+    parent
+        .spawn_bundle(TextBundle {
+            text: Text::with_section("", text_style(font), Default::default()),
+            ..Default::default()
+        })
+        .insert(readout);
+}
+
+fn text_style(font: &Handle<Font>) -> TextStyle {
+    TextStyle {
+        font: font.clone(),
+        font_size: 15.0,
+        color: Color::BLACK,
+    }
 }
 
 //
 // Components
 //
 
-#[derive(Component)]
-struct Dashboard;
+/// Progress bar of the dashboard.
+#[derive(Component, Clone, Copy)]
+enum Gauge {
+    Life,
+    CanonReload,
+    TorpedoReload,
+}
+
+/// Line of text of the dashboard.
+#[derive(Component, Clone, Copy)]
+enum Readout {
+    Speed,
+    Heading,
+    CanonRange,
+    Amunitions,
+    Torpedos,
+    Mines,
+    Wind,
+    Resupply,
+}
 
 //
 // Systems
 //
 
 fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
-    // Spawn the dasboard.
+    let font = asset_server.load("FiraSans-Bold.ttf");
+    // Spawn the dasboard, gauges first then readouts, from top to bottom.
     commands
-        .spawn_bundle(TextBundle {
+        .spawn_bundle(NodeBundle {
             style: Style {
-                align_self: AlignSelf::FlexEnd,
+                flex_direction: FlexDirection::ColumnReverse,
                 position_type: PositionType::Absolute,
                 position: Rect {
                     top: Val::Px(5.0),
@@ -37,31 +150,102 @@ fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
                 },
                 ..Default::default()
             },
-            text: Text::with_section(
-                dashboard_string(LIFE, 0., AMUNITIONS, TORPEDOS),
-                TextStyle {
-                    font: asset_server.load("FiraSans-Bold.ttf"),
-                    font_size: 15.0,
-                    color: Color::BLACK,
-                },
-                Default::default(),
-            ),
+            color: Color::NONE.into(),
             ..Default::default()
         })
-        .insert(Dashboard);
+        .with_children(|parent| {
+            spawn_bar(parent, &font, "Life", Gauge::Life);
+            spawn_bar(parent, &font, "Canon", Gauge::CanonReload);
+            spawn_bar(parent, &font, "Torpedo", Gauge::TorpedoReload);
+            for readout in [
+                Readout::Speed,
+                Readout::Heading,
+                Readout::CanonRange,
+                Readout::Amunitions,
+                Readout::Torpedos,
+                Readout::Mines,
+                Readout::Wind,
+                Readout::Resupply,
+            ] {
+                spawn_readout(parent, &font, readout);
+            }
+        });
 }
 
 fn update_dashboard(
-    mut query_dashboard: Query<&mut Text, With<Dashboard>>,
-    query_player: Query<(&Life, &Speed), With<Player>>,
-    query_canon: Query<&Amunitions>,
-    query_torpedo: Query<&Torpedos>,
+    weather: Res<Weather>,
+    mut query_gauge: Query<(&Gauge, &mut Style, &mut UiColor)>,
+    mut query_readout: Query<(&Readout, &mut Text)>,
+    query_player: Query<(&Life, &Speed, &Transform, Option<&Docked>, &Children), With<Player>>,
+    query_canon: Query<(&CanonSight, &Amunitions, &Timer)>,
+    query_torpedo: Query<(&Torpedos, &TorpedoTube, &Timer)>,
+    query_mines: Query<&Mines>,
 ) {
-    let mut text = query_dashboard.single_mut();
-    let (life, speed) = query_player.single();
-    let amunitions = query_canon.single();
-    let torpedos = query_torpedo.single();
-    text.sections[0].value = dashboard_string(life.0, speed.0, amunitions.0, torpedos.0);
+    // The player may not be on the sea (e.g. waiting to respawn).
+    let (life, speed, player_tf, docked, children) = match query_player.get_single() {
+        Ok(player) => player,
+        Err(_) => return,
+    };
+    let (mut canon_range, mut canon_reload, mut amunitions) = (0., 1., 0);
+    let (mut torpedo_reload, mut torpedos, mut tube) = (1., 0, TorpedoTube::default());
+    let mut mines = 0;
+    for child in children.iter() {
+        if let Ok((sight, canon, timer)) = query_canon.get(*child) {
+            canon_range = sight.0;
+            canon_reload = reload_progress(timer);
+            amunitions = canon.0;
+        }
+        if let Ok((tubes, torpedo_tube, timer)) = query_torpedo.get(*child) {
+            torpedo_reload = reload_progress(timer);
+            torpedos = tubes.0;
+            tube = *torpedo_tube;
+        }
+        if let Ok(rack) = query_mines.get(*child) {
+            mines = rack.0;
+        }
+    }
+
+    for (gauge, mut style, mut color) in query_gauge.iter_mut() {
+        let (progress, bar_color) = match gauge {
+            Gauge::Life => (life.0 as f32 / LIFE as f32, life_color(life.0)),
+            Gauge::CanonReload => (canon_reload, Color::rgb(0.3, 0.3, 0.3)),
+            Gauge::TorpedoReload => (torpedo_reload, Color::rgb(0.2, 0.4, 0.7)),
+        };
+        style.size.width = Val::Percent(100. * progress);
+        color.0 = bar_color;
+    }
+
+    let heading = player_tf.rotation.mul_vec3(Vec3::X);
+    let angle = heading.y.atan2(heading.x);
+    for (readout, mut text) in query_readout.iter_mut() {
+        text.sections[0].value = match readout {
+            Readout::Speed => format!("Speed: {:.1} kn", KNOTS_PER_SPEED * speed.0),
+            Readout::Heading => format!("Heading: {:03.0}° {}", compass(angle), arrow(angle)),
+            Readout::CanonRange => format!("Range: {:.0} m", METRES_PER_PIXEL * canon_range),
+            Readout::Amunitions => format!("Amunitions: {}/{}", amunitions, AMUNITIONS),
+            Readout::Torpedos => {
+                let torpedo_type = match tube.kind {
+                    TorpedoKind::Straight => format!("Straight x{}", tube.spread_count),
+                    TorpedoKind::Homing => String::from("Homing"),
+                    TorpedoKind::WireGuided => String::from("Wire-guided"),
+                };
+                format!("Torpedos: {}/{} ({})", torpedos, TORPEDOS, torpedo_type)
+            }
+            Readout::Mines => format!("Mines: {}/{}", mines, MINES),
+            Readout::Wind => format!(
+                "Wind: {} {:.0}",
+                arrow(weather.wind_angle),
+                weather.wind_strength
+            ),
+            Readout::Resupply => match docked {
+                Some(_) => {
+                    let stock = stock_level(life.0, amunitions, torpedos, mines);
+                    format!("Resupply: {:.0}%", 100. * stock)
+                }
+                None => String::new(),
+            },
+        };
+    }
 }
 
 //
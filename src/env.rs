@@ -0,0 +1,737 @@
+use std::io::{self, BufRead, Write};
+use std::time::Duration;
+
+use bevy::app::StartupSchedule;
+use bevy::asset::AssetPlugin;
+use bevy::core::CorePlugin;
+use bevy::ecs::schedule::{RunOnce, SingleThreadedExecutor};
+use bevy::input::InputPlugin;
+use bevy::prelude::*;
+use bevy::transform::TransformPlugin;
+use bevy_kira_audio::AudioChannel;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::canonball::{CanonBall, CanonBallPlugin};
+use crate::common::*;
+use crate::explosion::{ExplosionEvent, ExplosionKind, ExplosionPlugin, HitEvent};
+use crate::fog::Fog;
+use crate::island::{IslandPlugin, TileMap, DEEP_WATER_DEPTH};
+use crate::mine::{Mine, MinePlugin};
+use crate::player::{
+    canon_fire_at, ship_spawn, Amunitions, CanonSight, Life, PlayerPlugin, Ship, ShipClass, Speed,
+    Steering, CANON_MAX_DISTANCE,
+};
+use crate::port::PortPlugin;
+use crate::spectator::Spectator;
+use crate::torpedo::{Torpedo, TorpedoPlugin};
+use crate::weather::{Weather, WeatherPlugin};
+
+// Duration, in seconds, of a tick of the game, as when recording.
+pub const TICK: f32 = 1. / 60.;
+// Size of the sea, that of the window of the game.
+const SEA_SIZE: (f32, f32) = (1000., 700.);
+// Tiles tried at random to find open sea for a boat.
+const SPAWN_TRIES: u32 = 100;
+
+//
+// Misc functions
+//
+
+/// Headless game: only the systems of the simulation, without window, images nor sounds.
+fn env_app(seed: u64, ships: usize) -> App {
+    let mut app = App::new();
+    app.insert_resource(GameMode::FreeForAll)
+        .insert_resource(GameRng::new(seed))
+        .insert_resource(WinSize {
+            w: SEA_SIZE.0,
+            h: SEA_SIZE.1,
+        })
+        // Every boat is played by a bot, none from the keyboard.
+        .insert_resource(Spectator::new(true))
+        .insert_resource(Bots {
+            count: ships.max(1),
+            ships: Vec::new(),
+            actions: Vec::new(),
+            shots: Vec::new(),
+            hits: Vec::new(),
+            damage_dealt: Vec::new(),
+            kills: Vec::new(),
+        })
+        .init_resource::<GameTime>()
+        .init_resource::<FriendlyFire>()
+        .init_resource::<Fog>()
+        .add_plugin(CorePlugin)
+        .add_plugin(TransformPlugin)
+        .add_plugin(InputPlugin)
+        .add_plugin(AssetPlugin)
+        .add_asset::<TextureAtlas>()
+        // Along with the order of the game systems, so that a game plays the same way from the
+        // same seed and actions.
+        .stage(CoreStage::Update, |stage: &mut SystemStage| {
+            stage.set_executor(Box::new(SingleThreadedExecutor));
+            stage
+        });
+
+    // Sprites are still spawned, but with no image to draw them.
+    let mut texture_atlases = app
+        .world
+        .get_resource_mut::<Assets<TextureAtlas>>()
+        .unwrap();
+    let texture = texture_atlases.add(TextureAtlas::new_empty(
+        Handle::default(),
+        Vec2::new(96., 32.),
+    ));
+    let explosion = texture_atlases.add(TextureAtlas::from_grid(
+        Handle::default(),
+        Vec2::new(64.0, 64.0),
+        4,
+        4,
+    ));
+    app.insert_resource(SpriteMaterials {
+        texture,
+        boat_index: 0,
+        canon_index: 0,
+        canonball_index: 0,
+        torpedo_index: 0,
+        bunker_index: 0,
+        ground1_index: 0,
+        ground2_index: 0,
+        ground3_index: 0,
+        ground4_index: 0,
+        ground5_index: 0,
+        ground6_index: 0,
+        mountain_index: 0,
+        canon_sight_index: 0,
+        torpedo_sight_index: 0,
+        blue_flag_index: 0,
+        red_flag_index: 0,
+        explosion,
+    })
+    .insert_resource(AudioMaterials {
+        canon_sound: Handle::default(),
+        explosion_sound: Handle::default(),
+        torpedo_sound: Handle::default(),
+        splash_sound: Handle::default(),
+        engine_sound: Handle::default(),
+        weapon_channel: AudioChannel::new("weapon".to_string()),
+        explosion_channel: AudioChannel::new("explosion".to_string()),
+        engine_channel: AudioChannel::new("engine".to_string()),
+    })
+    .add_plugin(PlayerPlugin)
+    .add_plugin(CanonBallPlugin)
+    .add_plugin(TorpedoPlugin)
+    .add_plugin(MinePlugin)
+    .add_plugin(PortPlugin)
+    .add_plugin(IslandPlugin)
+    .add_plugin(ExplosionPlugin)
+    .add_plugin(WeatherPlugin)
+    // Once the sea and the first boat are there.
+    .add_startup_stage("env_setup_bots", SystemStage::single(bots_spawn))
+    // Before the game systems, for the actions to be taken at the same point of each tick.
+    .add_system_to_stage(CoreStage::PreUpdate, bot_actions)
+    .add_system_to_stage(CoreStage::PostUpdate, bot_hits);
+    app
+}
+
+/// Serve a bot trainer speaking JSON lines over stdin/stdout, with `naval --env`.
+pub fn serve() {
+    let mut env = Env::new(EnvConfig::default());
+    let stdin = io::stdin();
+    let mut stdout = io::stdout();
+    for line in stdin.lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let reply = match serde_json::from_str::<Request>(&line) {
+            Ok(Request::Config(config)) => {
+                env = Env::new(config);
+                json!({ "config": env.config() })
+            }
+            Ok(Request::Reset { seed }) => json!({ "observation": env.reset(seed) }),
+            Ok(Request::Step { actions }) => {
+                let (observation, rewards, done) = env.step(&actions);
+                json!({ "observation": observation, "rewards": rewards, "done": done })
+            }
+            Err(err) => json!({ "error": err.to_string() }),
+        };
+        if writeln!(stdout, "{}", reply)
+            .and_then(|_| stdout.flush())
+            .is_err()
+        {
+            break;
+        }
+    }
+}
+
+//
+// Resources
+//
+
+/// Requests of a bot trainer, one JSON object per line.
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Request {
+    Config(EnvConfig),
+    Reset { seed: u64 },
+    Step { actions: Vec<Action> },
+}
+
+/// Weights of what happened during a step, summed up into the reward of each bot.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Rewards {
+    // Per point of life the weapons of the bot took from the other boats.
+    pub enemy_damage: f32,
+    // Per point of life lost by the boat of the bot.
+    pub damage_taken: f32,
+    // Per other boat brought down to no life.
+    pub kill: f32,
+    // When the boat of the bot is brought down to no life.
+    pub death: f32,
+    // Per canonball fired.
+    pub shot: f32,
+    // At every step, e.g. negative to hurry the bots.
+    pub step: f32,
+}
+
+impl Default for Rewards {
+    fn default() -> Self {
+        Rewards {
+            enemy_damage: 0.01,
+            damage_taken: -0.01,
+            kill: 1.,
+            death: -1.,
+            shot: -0.001,
+            step: 0.,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EnvConfig {
+    // Number of boats, each played by a bot in its own team.
+    pub ships: usize,
+    // Ticks of the game played at each step.
+    pub ticks_per_step: u32,
+    // Steps after which the game is over.
+    pub max_steps: u32,
+    // Tiles seen around a boat, in each direction.
+    pub view_tiles: i32,
+    // Distance within which the other boats and the projectiles are seen.
+    pub view_range: f32,
+    pub rewards: Rewards,
+}
+
+impl Default for EnvConfig {
+    fn default() -> Self {
+        EnvConfig {
+            ships: 2,
+            ticks_per_step: 4,
+            max_steps: 2000,
+            view_tiles: 4,
+            view_range: CANON_MAX_DISTANCE,
+            rewards: Rewards::default(),
+        }
+    }
+}
+
+/// What a bot does with its boat until the next step.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Action {
+    // From -1 (full astern) to 1 (full ahead).
+    pub throttle: f32,
+    // From -1 (starboard) to 1 (port).
+    pub rudder: f32,
+    // Point to fire the canon at, relative to the boat.
+    pub fire: Option<(f32, f32)>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ShipState {
+    pub x: f32,
+    pub y: f32,
+    pub angle: f32,
+    pub speed: f32,
+    pub life: u32,
+    pub amunitions: u32,
+    // Seconds left before the canon can fire again.
+    pub reload: f32,
+    // Canonballs fired since the start of the game, and those which hit a hull.
+    pub shots: u32,
+    pub hits: u32,
+    // Life taken from the other boats since the start of the game, and the boats it sank.
+    pub damage_dealt: u32,
+    pub kills: u32,
+}
+
+/// Another boat, as seen from a boat.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Contact {
+    pub team: usize,
+    // Position relative to the boat seeing it.
+    pub x: f32,
+    pub y: f32,
+    pub angle: f32,
+    pub speed: f32,
+    pub life: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ProjectileKind {
+    CanonBall,
+    Torpedo,
+    Mine,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Projectile {
+    pub kind: ProjectileKind,
+    // Position relative to the boat seeing it.
+    pub x: f32,
+    pub y: f32,
+    pub angle: f32,
+}
+
+/// What a bot knows of the game.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BotObservation {
+    pub ship: ShipState,
+    // Depth of the water on the tiles around the boat, 0 for land, row by row from the
+    // bottom left one.
+    pub tiles: Vec<f32>,
+    pub ships: Vec<Contact>,
+    pub projectiles: Vec<Projectile>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Observation {
+    pub step: u32,
+    // One per bot, in the order of their actions.
+    pub bots: Vec<BotObservation>,
+}
+
+/// Boats played by the bots, and what they asked for.
+struct Bots {
+    count: usize,
+    ships: Vec<Entity>,
+    actions: Vec<Action>,
+    // Canonballs fired by each boat since the start of the game, and those which hit a hull.
+    shots: Vec<u32>,
+    hits: Vec<u32>,
+    // Life taken from the other boats by the weapons of each boat, and the boats they sank.
+    damage_dealt: Vec<u32>,
+    kills: Vec<u32>,
+}
+
+/// Game played a step at a time by bots, faster than real time, e.g. to train them.
+pub struct Env {
+    config: EnvConfig,
+    app: App,
+    steps: u32,
+    // State of each boat after the previous step.
+    states: Vec<ShipState>,
+}
+
+impl Env {
+    pub fn new(config: EnvConfig) -> Self {
+        // The game is built once, for its systems to keep running in the same order: the same
+        // seed and actions then always play the same game.
+        let mut app = env_app(0, config.ships);
+        app.update();
+        let mut env = Env {
+            app,
+            config,
+            steps: 0,
+            states: Vec::new(),
+        };
+        env.states = env.observe().bots.into_iter().map(|bot| bot.ship).collect();
+        env
+    }
+
+    pub fn config(&self) -> &EnvConfig {
+        &self.config
+    }
+
+    /// Start a new game, on the sea given by the seed.
+    pub fn reset(&mut self, seed: u64) -> Observation {
+        // Clear the sea, and let the game go through what was left of the previous one.
+        let world = &mut self.app.world;
+        let entities: Vec<Entity> = world.query::<Entity>().iter(world).collect();
+        for entity in entities {
+            world.despawn(entity);
+        }
+        world.get_resource_mut::<GameTime>().unwrap().delta = Duration::ZERO;
+        self.app.update();
+
+        // Then play the startup systems again, from the new seed.
+        let world = &mut self.app.world;
+        world.insert_resource(GameRng::new(seed));
+        let weather = Weather::from_world(world);
+        world.insert_resource(weather);
+        world.insert_resource(Fog::default());
+        world.get_resource_mut::<Bots>().unwrap().ships.clear();
+        self.app
+            .schedule
+            .stage(StartupSchedule, |schedule: &mut Schedule| {
+                schedule.set_run_criteria(RunOnce::default())
+            });
+        self.app.update();
+        self.steps = 0;
+        let observation = self.observe();
+        self.states = observation
+            .bots
+            .iter()
+            .map(|bot| bot.ship.clone())
+            .collect();
+        observation
+    }
+
+    /// Number of steps played since the start of the game.
+    pub fn steps(&self) -> u32 {
+        self.steps
+    }
+
+    /// Play the actions of the bots, one per boat, and tell the reward of each bot and
+    /// whether the game is over.
+    pub fn step(&mut self, actions: &[Action]) -> (Observation, Vec<f32>, bool) {
+        self.app.world.get_resource_mut::<Bots>().unwrap().actions = actions.to_vec();
+        for _ in 0..self.config.ticks_per_step {
+            self.app.world.get_resource_mut::<GameTime>().unwrap().delta =
+                Duration::from_secs_f32(TICK);
+            self.app.update();
+        }
+        self.steps += 1;
+        let observation = self.observe();
+
+        let states: Vec<ShipState> = observation
+            .bots
+            .iter()
+            .map(|bot| bot.ship.clone())
+            .collect();
+        let lost: Vec<u32> = self
+            .states
+            .iter()
+            .zip(states.iter())
+            .map(|(before, after)| before.life.saturating_sub(after.life))
+            .collect();
+        let kills: Vec<u32> = self
+            .states
+            .iter()
+            .zip(states.iter())
+            .map(|(before, after)| after.kills.saturating_sub(before.kills))
+            .collect();
+        let sunk: Vec<bool> = self
+            .states
+            .iter()
+            .zip(states.iter())
+            .map(|(before, after)| before.life > 0 && after.life == 0)
+            .collect();
+        let shots: Vec<u32> = self
+            .states
+            .iter()
+            .zip(states.iter())
+            .map(|(before, after)| after.shots.saturating_sub(before.shots))
+            .collect();
+        let dealt: Vec<u32> = self
+            .states
+            .iter()
+            .zip(states.iter())
+            .map(|(before, after)| after.damage_dealt.saturating_sub(before.damage_dealt))
+            .collect();
+        let weights = &self.config.rewards;
+        let rewards = (0..states.len())
+            .map(|i| {
+                weights.enemy_damage * dealt[i] as f32
+                    + weights.damage_taken * lost[i] as f32
+                    + weights.kill * kills[i] as f32
+                    + if sunk[i] { weights.death } else { 0. }
+                    + weights.shot * shots[i] as f32
+                    + weights.step
+            })
+            .collect();
+        self.states = states;
+
+        // Over once a single boat is left afloat, or none when alone.
+        let afloat = self.states.iter().filter(|state| state.life > 0).count();
+        let done = self.steps >= self.config.max_steps || afloat < self.states.len().min(2);
+        (observation, rewards, done)
+    }
+
+    fn observe(&mut self) -> Observation {
+        let world = &mut self.app.world;
+        let mut query_ship = world
+            .query_filtered::<(Entity, &Transform, &Team, &Speed, &Life, &Children), With<Ship>>();
+        let mut query_canon = world.query::<(&Amunitions, &Timer, &CanonSight)>();
+        let mut query_projectile = world.query_filtered::<
+            (&Transform, Option<&CanonBall>, Option<&Torpedo>),
+            Or<(With<CanonBall>, With<Torpedo>, With<Mine>)>,
+        >();
+        let bots = world.get_resource::<Bots>().unwrap();
+        let tile_map = world.get_resource::<TileMap>().unwrap();
+        let view = self.config.view_tiles;
+        let range = self.config.view_range;
+
+        let mut observations = Vec::new();
+        for (i, ship) in bots.ships.iter().enumerate() {
+            let (_, ship_tf, _, speed, life, children) = match query_ship.get(world, *ship) {
+                Ok(ship) => ship,
+                Err(_) => {
+                    observations.push(BotObservation {
+                        ship: ShipState::default(),
+                        tiles: Vec::new(),
+                        ships: Vec::new(),
+                        projectiles: Vec::new(),
+                    });
+                    continue;
+                }
+            };
+            let position = ship_tf.translation;
+            let heading = ship_tf.rotation.mul_vec3(Vec3::X);
+            let mut state = ShipState {
+                x: position.x,
+                y: position.y,
+                angle: heading.y.atan2(heading.x),
+                speed: speed.0,
+                life: life.0,
+                shots: bots.shots[i],
+                hits: bots.hits[i],
+                damage_dealt: bots.damage_dealt[i],
+                kills: bots.kills[i],
+                ..Default::default()
+            };
+            for child in children.iter() {
+                if let Ok((amunitions, timer, _)) = query_canon.get(world, *child) {
+                    state.amunitions = amunitions.0;
+                    state.reload = timer
+                        .duration()
+                        .saturating_sub(timer.elapsed())
+                        .as_secs_f32();
+                }
+            }
+            let (tile_x, tile_y) = tile_of(position);
+            let mut tiles = Vec::new();
+            for y in -view..=view {
+                for x in -view..=view {
+                    tiles.push(tile_map.depth((tile_x + x, tile_y + y)));
+                }
+            }
+            let contacts = query_ship
+                .iter(world)
+                .filter(|(other, other_tf, ..)| {
+                    other != ship && distance(other_tf.translation, position) <= range
+                })
+                .map(|(_, other_tf, other_team, other_speed, other_life, _)| {
+                    let heading = other_tf.rotation.mul_vec3(Vec3::X);
+                    Contact {
+                        team: other_team.0,
+                        x: other_tf.translation.x - position.x,
+                        y: other_tf.translation.y - position.y,
+                        angle: heading.y.atan2(heading.x),
+                        speed: other_speed.0,
+                        life: other_life.0,
+                    }
+                })
+                .collect();
+            let projectiles = query_projectile
+                .iter(world)
+                .filter(|(projectile_tf, ..)| {
+                    distance(projectile_tf.translation, position) <= range
+                })
+                .map(|(projectile_tf, canonball, torpedo)| {
+                    let heading = projectile_tf.rotation.mul_vec3(Vec3::X);
+                    Projectile {
+                        kind: match (canonball, torpedo) {
+                            (Some(_), _) => ProjectileKind::CanonBall,
+                            (_, Some(_)) => ProjectileKind::Torpedo,
+                            _ => ProjectileKind::Mine,
+                        },
+                        x: projectile_tf.translation.x - position.x,
+                        y: projectile_tf.translation.y - position.y,
+                        angle: heading.y.atan2(heading.x),
+                    }
+                })
+                .collect();
+            observations.push(BotObservation {
+                ship: state,
+                tiles,
+                ships: contacts,
+                projectiles,
+            });
+        }
+        Observation {
+            step: self.steps,
+            bots: observations,
+        }
+    }
+}
+
+//
+// Systems
+//
+
+fn bots_spawn(
+    mut commands: Commands,
+    sprite_materials: Res<SpriteMaterials>,
+    tile_map: Res<TileMap>,
+    mut game_rng: ResMut<GameRng>,
+    mut bots: ResMut<Bots>,
+    query_ship: Query<Entity, With<Ship>>,
+) {
+    // The boat of the free-for-all game goes to the first bot.
+    let mut ships: Vec<Entity> = query_ship.iter().collect();
+    let rng = &mut game_rng.rng;
+    while ships.len() < bots.count {
+        // Anywhere on the open sea.
+        let mut tile = (0, 0);
+        for _ in 0..SPAWN_TRIES {
+            tile = (
+                rng.gen_range(-tile_map.w_tiles..=tile_map.w_tiles),
+                rng.gen_range(-tile_map.h_tiles..=tile_map.h_tiles),
+            );
+            if tile_map.depth(tile) >= DEEP_WATER_DEPTH {
+                break;
+            }
+        }
+        let ship = ship_spawn(
+            &mut commands,
+            &sprite_materials,
+            Vec3::new(16. * tile.0 as f32, 16. * tile.1 as f32, BOAT_Z),
+            rng.gen_range(0. ..2. * std::f32::consts::PI),
+            Team(ships.len()),
+            ShipClass::Frigate,
+        );
+        ships.push(ship);
+    }
+    for ship in ships.iter() {
+        commands.entity(*ship).insert(Steering {
+            throttle: 0.,
+            rudder: 0.,
+        });
+    }
+    bots.shots = vec![0; ships.len()];
+    bots.hits = vec![0; ships.len()];
+    bots.damage_dealt = vec![0; ships.len()];
+    bots.kills = vec![0; ships.len()];
+    bots.ships = ships;
+}
+
+fn bot_actions(
+    mut commands: Commands,
+    sprite_materials: Res<SpriteMaterials>,
+    mut bots: ResMut<Bots>,
+    mut query_ship: Query<(&Transform, &Life, &mut Steering)>,
+    mut query_sight: Query<(&Parent, &mut Amunitions, &mut Timer), With<CanonSight>>,
+) {
+    // The actions are taken at the first tick of the step, the steering then kept.
+    let actions: Vec<Action> = bots.actions.drain(..).collect();
+    for (i, action) in actions.iter().enumerate() {
+        let ship = match bots.ships.get(i) {
+            Some(ship) => *ship,
+            None => break,
+        };
+        let (ship_tf, life, mut steering) = match query_ship.get_mut(ship) {
+            Ok(ship) => ship,
+            Err(_) => continue,
+        };
+        // A boat with no life left stops answering.
+        if life.0 == 0 {
+            steering.throttle = 0.;
+            steering.rudder = 0.;
+            continue;
+        }
+        steering.throttle = action.throttle.clamp(-1., 1.);
+        steering.rudder = action.rudder.clamp(-1., 1.);
+        let (x, y) = match action.fire {
+            Some(target) => target,
+            None => continue,
+        };
+        let target = ship_tf.translation + Vec3::new(x, y, 0.);
+        for (parent, mut amunitions, mut timer) in query_sight.iter_mut() {
+            if parent.0 == ship
+                && canon_fire_at(
+                    &mut commands,
+                    &sprite_materials,
+                    ship,
+                    ship_tf,
+                    target,
+                    &mut amunitions,
+                    &mut timer,
+                )
+            {
+                bots.shots[i] += 1;
+            }
+        }
+    }
+}
+
+fn bot_hits(
+    mut bots: ResMut<Bots>,
+    mut explosions: EventReader<ExplosionEvent>,
+    mut hits: EventReader<HitEvent>,
+) {
+    // Damage to the own boat, e.g. from its own mine, is not dealt to an enemy.
+    for hit in hits.iter().filter(|hit| hit.source != Some(hit.target)) {
+        let shooter = hit
+            .source
+            .and_then(|source| bots.ships.iter().position(|ship| *ship == source));
+        if let Some(i) = shooter {
+            bots.damage_dealt[i] += hit.damage;
+            bots.kills[i] += hit.fatal as u32;
+        }
+    }
+    for explosion in explosions.iter() {
+        if explosion.kind != ExplosionKind::HullHit {
+            continue;
+        }
+        let shooter = explosion
+            .source
+            .and_then(|source| bots.ships.iter().position(|ship| *ship == source));
+        if let Some(i) = shooter {
+            bots.hits[i] += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Observations and rewards of a game played with the same actions at every step.
+    fn play(env: &mut Env, seed: u64) -> Vec<(String, Vec<f32>)> {
+        let mut steps = vec![(serde_json::to_string(&env.reset(seed)).unwrap(), Vec::new())];
+        for step in 0..200 {
+            let actions: Vec<Action> = (0..2)
+                .map(|i| Action {
+                    throttle: 1.,
+                    rudder: if i == 0 { 0.3 } else { -0.2 },
+                    fire: (step % 10 == 0).then(|| (120., 40.)),
+                })
+                .collect();
+            let (observation, rewards, done) = env.step(&actions);
+            steps.push((serde_json::to_string(&observation).unwrap(), rewards));
+            if done {
+                break;
+            }
+        }
+        steps
+    }
+
+    #[test]
+    fn same_seed_and_actions_play_the_same_game() {
+        let mut env = Env::new(EnvConfig::default());
+        let first = play(&mut env, 42);
+        let second = play(&mut env, 42);
+        assert_eq!(first.len(), second.len());
+        for (step, (first, second)) in first.iter().zip(second.iter()).enumerate() {
+            assert_eq!(first, second, "games differ at step {}", step);
+        }
+    }
+}
@@ -2,6 +2,7 @@ use bevy::prelude::*;
 use bevy_kira_audio::Audio;
 
 use crate::common::*;
+use crate::fog::Fog;
 
 //
 // Components
@@ -10,46 +11,131 @@ use crate::common::*;
 #[derive(Component)]
 struct Explosion;
 
-#[derive(Component)]
-pub struct ExplosionToSpawn(pub Vec3);
+//
+// Events
+//
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ExplosionKind {
+    // Projectile falling into the water.
+    Splash,
+    // Canonball hitting a hull or a mountain.
+    HullHit,
+    Torpedo,
+    Mine,
+    // Boat blowing up as it sinks.
+    Magazine,
+}
+
+impl ExplosionKind {
+    /// Size, tint and duration of each frame of the explosion.
+    fn spec(&self) -> (f32, Color, f32) {
+        match self {
+            ExplosionKind::Splash => (24., Color::rgba(0.8, 0.95, 1., 0.8), 0.05),
+            ExplosionKind::HullHit => (32., Color::WHITE, 0.04),
+            ExplosionKind::Torpedo => (64., Color::rgb(1., 0.95, 0.9), 0.05),
+            ExplosionKind::Mine => (64., Color::rgb(0.9, 0.95, 1.), 0.05),
+            ExplosionKind::Magazine => (128., Color::rgb(1., 0.8, 0.6), 0.08),
+        }
+    }
+}
+
+/// An explosion, of the given radius of effect, caused by the source entity if known.
+pub struct ExplosionEvent {
+    pub position: Vec3,
+    pub kind: ExplosionKind,
+    pub radius: f32,
+    pub source: Option<Entity>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Weapon {
+    Canon,
+    Torpedo,
+    Mine,
+}
+
+/// A hull hit by a weapon fired by the source entity if known, with the life it lost.
+pub struct HitEvent {
+    pub source: Option<Entity>,
+    pub target: Entity,
+    pub weapon: Weapon,
+    pub damage: u32,
+    // Whether the hit took the last of its life.
+    pub fatal: bool,
+}
+
+impl HitEvent {
+    pub fn new(
+        source: Option<Entity>,
+        target: Entity,
+        weapon: Weapon,
+        before: u32,
+        after: u32,
+    ) -> Self {
+        HitEvent {
+            source,
+            target,
+            weapon,
+            damage: before - after,
+            fatal: before > 0 && after == 0,
+        }
+    }
+}
 
 //
 // Systems
 //
 
-fn explosion_to_spawn(
+fn explosion_spawn(
     mut commands: Commands,
-    audio: Res<Audio>,
+    audio: Option<Res<Audio>>,
     audio_materials: Res<AudioMaterials>,
     sprite_materials: Res<SpriteMaterials>,
-    query: Query<(Entity, &ExplosionToSpawn)>,
+    fog: Res<Fog>,
+    mut explosions: EventReader<ExplosionEvent>,
+    query_team: Query<&Team>,
 ) {
-    for (explosion_spawn_entity, explosion_to_spawn) in query.iter() {
+    for explosion in explosions.iter() {
+        // Explosions out of sight are only heard, unless set off by a friend.
+        let friendly = matches!(
+            explosion.source.map(|source| query_team.get(source)),
+            Some(Ok(team)) if *team == fog.team
+        );
+        let sound = match explosion.kind {
+            ExplosionKind::Splash => &audio_materials.splash_sound,
+            _ => &audio_materials.explosion_sound,
+        };
+        if let Some(audio) = &audio {
+            audio.play_in_channel(sound.clone(), &audio_materials.explosion_channel);
+        }
+        if !friendly && !fog.is_visible(explosion.position) {
+            continue;
+        }
         // Spwan an explosion
+        let (size, color, period) = explosion.kind.spec();
         commands
             .spawn_bundle(SpriteSheetBundle {
                 texture_atlas: sprite_materials.explosion.clone(),
+                sprite: TextureAtlasSprite {
+                    color,
+                    custom_size: Some(Vec2::splat(size)),
+                    ..Default::default()
+                },
                 transform: Transform {
-                    translation: explosion_to_spawn.0,
+                    translation: explosion.position,
                     ..Default::default()
                 },
                 ..Default::default()
             })
             .insert(Explosion)
-            .insert(Timer::from_seconds(0.05, true));
-        // Play explosion sound.
-        audio.play_in_channel(
-            audio_materials.explosion_sound.clone(),
-            &audio_materials.explosion_channel,
-        );
-        // Despawn explosion trigger.
-        commands.entity(explosion_spawn_entity).despawn();
+            .insert(Timer::from_seconds(period, true));
     }
 }
 
 fn animate_explosion(
     mut commands: Commands,
-    time: Res<Time>,
+    time: Res<GameTime>,
     texture_atlases: Res<Assets<TextureAtlas>>,
     mut query: Query<
         (
@@ -81,7 +167,14 @@ pub struct ExplosionPlugin;
 
 impl Plugin for ExplosionPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system(explosion_to_spawn)
-            .add_system(animate_explosion);
+        app.add_event::<ExplosionEvent>()
+            .add_event::<HitEvent>()
+            .add_system_set(
+                SystemSet::new()
+                    .label(GameSystem::Explosions)
+                    .after(GameSystem::Islands)
+                    .with_system(explosion_spawn)
+                    .with_system(animate_explosion.after(explosion_spawn)),
+            );
     }
 }
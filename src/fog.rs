@@ -0,0 +1,282 @@
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+
+use crate::canonball::CanonBall;
+use crate::common::*;
+use crate::ctf::CarriedBy;
+use crate::island::{Ground, Mountain, TerrainChanged, TerrainReset, TileMap};
+use crate::player::{Player, Ship};
+use crate::port::Port;
+use crate::spectator::Spectator;
+use crate::torpedo::Torpedo;
+
+// Radius, in pixels, of the sight of a boat.
+const SIGHT_RADIUS: f32 = 320.;
+const UNEXPLORED_COLOR: Color = Color::rgb(0.35, 0.35, 0.4);
+const GHOST_ALPHA: f32 = 0.3;
+
+//
+// Misc functions
+//
+
+/// Whether a tile can be seen from another one, i.e. no mountain stands in between.
+fn line_of_sight(tile_map: &TileMap, from: (i32, i32), to: (i32, i32)) -> bool {
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let steps = dx.abs().max(dy.abs());
+    (1..steps).all(|step| {
+        let t = step as f32 / steps as f32;
+        let tile = (
+            from.0 + (t * dx as f32).round() as i32,
+            from.1 + (t * dy as f32).round() as i32,
+        );
+        !tile_map.is_mountain(tile)
+    })
+}
+
+/// Tiles in sight from the given tile.
+fn tiles_in_sight(tile_map: &TileMap, from: (i32, i32), radius: f32) -> HashSet<(i32, i32)> {
+    let r = (radius / 16.) as i32;
+    let mut tiles = HashSet::new();
+    for dx in -r..=r {
+        for dy in -r..=r {
+            let tile = (from.0 + dx, from.1 + dy);
+            if dx * dx + dy * dy <= r * r && line_of_sight(tile_map, from, tile) {
+                tiles.insert(tile);
+            }
+        }
+    }
+    tiles
+}
+
+//
+// Components
+//
+
+/// Tiles seen by a boat, only computed again when it moves to another tile.
+#[derive(Component)]
+pub struct Sight {
+    pub radius: f32,
+    tile: Option<(i32, i32)>,
+    tiles: HashSet<(i32, i32)>,
+}
+
+impl Default for Sight {
+    fn default() -> Self {
+        Sight {
+            radius: SIGHT_RADIUS,
+            tile: None,
+            tiles: HashSet::new(),
+        }
+    }
+}
+
+/// Enemy boat currently in sight.
+#[derive(Component)]
+struct Spotted;
+
+/// Last known position of an enemy boat.
+#[derive(Component)]
+struct Ghost(Entity);
+
+//
+// Resources
+//
+
+/// What the team of the player sees and has already seen.
+pub struct Fog {
+    pub team: Team,
+    pub visible: HashSet<(i32, i32)>,
+    pub explored: HashSet<(i32, i32)>,
+}
+
+impl Default for Fog {
+    fn default() -> Self {
+        Fog {
+            team: Team(0),
+            visible: HashSet::new(),
+            explored: HashSet::new(),
+        }
+    }
+}
+
+impl Fog {
+    pub fn is_visible(&self, position: Vec3) -> bool {
+        self.visible.contains(&tile_of(position))
+    }
+}
+
+//
+// Systems
+//
+
+fn fog_update(
+    tile_map: Res<TileMap>,
+    spectator: Res<Spectator>,
+    mut fog: ResMut<Fog>,
+    mut terrain_changed: EventReader<TerrainChanged>,
+    mut terrain_reset: EventReader<TerrainReset>,
+    query_player: Query<&Team, With<Player>>,
+    mut query_ship: Query<(&Transform, &Team, &mut Sight), With<Ship>>,
+) {
+    // Only written to when it changes, the terrain being tinted again then.
+    if let Ok(team) = query_player.get_single() {
+        if fog.team != *team {
+            fog.team = *team;
+        }
+    }
+    // A new terrain is to be explored again.
+    let terrain_reset = terrain_reset.iter().count() > 0;
+    if terrain_reset {
+        fog.explored.clear();
+    }
+    // Destroyed mountains may open new lines of sight.
+    let terrain_changed = terrain_changed.iter().count() > 0;
+    let mut visible = HashSet::new();
+    for (ship_tf, team, mut sight) in query_ship.iter_mut() {
+        if *team != fog.team {
+            continue;
+        }
+        let tile = tile_of(ship_tf.translation);
+        if terrain_changed || sight.tile != Some(tile) {
+            sight.tiles = tiles_in_sight(&tile_map, tile, sight.radius);
+            sight.tile = Some(tile);
+        }
+        visible.extend(sight.tiles.iter().copied());
+    }
+    // Spectators not in the game see the whole sea.
+    if spectator.read_only {
+        for x in -tile_map.w_tiles..=tile_map.w_tiles {
+            visible.extend((-tile_map.h_tiles..=tile_map.h_tiles).map(|y| (x, y)));
+        }
+    }
+    if terrain_reset || visible != fog.visible {
+        fog.explored.extend(visible.iter().copied());
+        fog.visible = visible;
+    }
+}
+
+fn fog_terrain(
+    fog: Res<Fog>,
+    mut query: Query<
+        (&Transform, &mut TextureAtlasSprite, Option<&Team>),
+        Or<(With<Ground>, With<Mountain>, With<Port>)>,
+    >,
+) {
+    if !fog.is_changed() {
+        return;
+    }
+    // Terrain never seen is dimmed, ports of a team keeping its tint.
+    for (tile_tf, mut sprite, team) in query.iter_mut() {
+        let color = team.map_or(Color::WHITE, |team| team.color());
+        sprite.color = if fog.explored.contains(&tile_of(tile_tf.translation)) {
+            color
+        } else {
+            color * Vec4::from(UNEXPLORED_COLOR)
+        };
+    }
+}
+
+fn fog_ships(
+    mut commands: Commands,
+    fog: Res<Fog>,
+    sprite_materials: Res<SpriteMaterials>,
+    mut query_ship: Query<
+        (
+            Entity,
+            &Transform,
+            &TextureAtlasSprite,
+            &Team,
+            &Children,
+            &mut Visibility,
+            Option<&Spotted>,
+        ),
+        With<Ship>,
+    >,
+    mut query_children: Query<&mut Visibility, Without<Ship>>,
+    query_flag: Query<&CarriedBy>,
+) {
+    for (ship_entity, ship_tf, sprite, team, children, mut visibility, spotted) in
+        query_ship.iter_mut()
+    {
+        // Flag carriers are seen by everyone.
+        let visible = *team == fog.team
+            || fog.is_visible(ship_tf.translation)
+            || query_flag
+                .iter()
+                .any(|carried_by| carried_by.0 == ship_entity);
+        if visible && spotted.is_none() && *team != fog.team {
+            commands.entity(ship_entity).insert(Spotted);
+        } else if !visible && spotted.is_some() {
+            // Leave a ghost where the enemy was last seen.
+            commands.entity(ship_entity).remove::<Spotted>();
+            commands
+                .spawn_bundle(SpriteSheetBundle {
+                    texture_atlas: sprite_materials.texture.clone(),
+                    sprite: TextureAtlasSprite {
+                        color: Color::rgba(1., 1., 1., GHOST_ALPHA),
+                        ..sprite.clone()
+                    },
+                    transform: *ship_tf,
+                    ..Default::default()
+                })
+                .insert(Ghost(ship_entity));
+        }
+        visibility.is_visible = visible;
+        for child in children.iter() {
+            if let Ok(mut child_visibility) = query_children.get_mut(*child) {
+                child_visibility.is_visible = visible;
+            }
+        }
+    }
+}
+
+fn fog_ghosts(
+    mut commands: Commands,
+    fog: Res<Fog>,
+    query_ghost: Query<(Entity, &Transform, &Ghost)>,
+    query_spotted: Query<&Spotted>,
+) {
+    // Ghosts vanish once their position or their boat is in sight again.
+    for (ghost_entity, ghost_tf, ghost) in query_ghost.iter() {
+        if fog.is_visible(ghost_tf.translation) || query_spotted.get(ghost.0).is_ok() {
+            commands.entity(ghost_entity).despawn();
+        }
+    }
+}
+
+fn fog_projectiles(
+    fog: Res<Fog>,
+    mut query_canonball: Query<(&Transform, &mut Visibility, &Children), With<CanonBall>>,
+    mut query_torpedo: Query<(&Transform, &mut Visibility), (With<Torpedo>, Without<CanonBall>)>,
+    mut query_children: Query<&mut Visibility, (Without<CanonBall>, Without<Torpedo>)>,
+) {
+    for (canonball_tf, mut visibility, children) in query_canonball.iter_mut() {
+        visibility.is_visible = fog.is_visible(canonball_tf.translation);
+        for child in children.iter() {
+            if let Ok(mut child_visibility) = query_children.get_mut(*child) {
+                child_visibility.is_visible = visibility.is_visible;
+            }
+        }
+    }
+    for (torpedo_tf, mut visibility) in query_torpedo.iter_mut() {
+        visibility.is_visible = fog.is_visible(torpedo_tf.translation);
+    }
+}
+
+//
+// Plugin
+//
+
+pub struct FogPlugin;
+
+impl Plugin for FogPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Fog>()
+            .add_system(fog_update)
+            .add_system(fog_terrain)
+            .add_system(fog_ships)
+            .add_system(fog_ghosts)
+            .add_system(fog_projectiles);
+    }
+}
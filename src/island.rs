@@ -1,11 +1,139 @@
 use crate::common::*;
+use crate::ctf::base_spawn;
+use crate::explosion::{ExplosionEvent, ExplosionKind};
+use crate::port::{port_spawn, Port};
+use crate::team::fort_spawn;
 use bevy::prelude::*;
-use rand::{thread_rng, Rng};
+use rand::{seq::SliceRandom, Rng};
 use std::collections::{hash_map::Entry::Vacant, HashMap};
 
 const NUM_ISLANDS: u32 = 20;
 const SIZE_ISLANDS: u32 = 40;
 const PERCENTAGE_MOUNTAINS: u32 = 30;
+const MOUNTAIN_MIN_HEIGHT: f32 = 10.;
+const MOUNTAIN_MAX_HEIGHT: f32 = 40.;
+const NUM_PORTS: usize = 3;
+// Depth of the water, in metres, one, two and three tiles off the coast.
+const SHALLOW_DEPTHS: [f32; 3] = [1., 2.5, 4.];
+const PERCENTAGE_REEFS: u32 = 8;
+const REEF_DEPTH: f32 = 0.5;
+pub const DEEP_WATER_DEPTH: f32 = 5.5;
+// Hits needed to flatten a mountain or blast away a coastal tile.
+const MOUNTAIN_HEALTH: u32 = 3;
+const GROUND_HEALTH: u32 = 2;
+// Depth, in metres, of the channel left by a blasted tile.
+pub const CRATER_DEPTH: f32 = 2.5;
+
+//
+// Misc functions
+//
+
+/// Sprite & orientation of a ground tile, based on the adjacent land tiles.
+fn ground_sprite(
+    sprite_materials: &SpriteMaterials,
+    tile: (i32, i32),
+    is_land: impl Fn((i32, i32)) -> bool,
+) -> (usize, f32) {
+    let (tile_x, tile_y) = tile;
+    let right = is_land((tile_x + 1, tile_y));
+    let up = is_land((tile_x, tile_y + 1));
+    let left = is_land((tile_x - 1, tile_y));
+    let down = is_land((tile_x, tile_y - 1));
+    match (right, up, left, down) {
+        // 0 adjacent
+        (false, false, false, false) => (sprite_materials.ground6_index, 0.),
+        // 1
+        (true, false, false, false) => (sprite_materials.ground5_index, -2.),
+        (false, true, false, false) => (sprite_materials.ground5_index, -1.),
+        (false, false, true, false) => (sprite_materials.ground5_index, 0.),
+        (false, false, false, true) => (sprite_materials.ground5_index, 1.),
+        // 2
+        (true, true, false, false) => (sprite_materials.ground3_index, -2.),
+        (false, true, true, false) => (sprite_materials.ground3_index, -1.),
+        (false, false, true, true) => (sprite_materials.ground3_index, 0.),
+        (true, false, false, true) => (sprite_materials.ground3_index, 1.),
+
+        (true, false, true, false) => (sprite_materials.ground4_index, 0.),
+        (false, true, false, true) => (sprite_materials.ground4_index, 1.),
+        // 3
+        (true, true, true, false) => (sprite_materials.ground2_index, -1.),
+        (true, true, false, true) => (sprite_materials.ground2_index, 2.),
+        (true, false, true, true) => (sprite_materials.ground2_index, 1.),
+        (false, true, true, true) => (sprite_materials.ground2_index, 0.),
+        // 4
+        (true, true, true, true) => (sprite_materials.ground1_index, 0.),
+    }
+}
+
+/// Spawn the ground of a land tile, its sprite fitting the land around it.
+pub fn ground_spawn(
+    commands: &mut Commands,
+    sprite_materials: &SpriteMaterials,
+    tile: (i32, i32),
+    is_land: impl Fn((i32, i32)) -> bool,
+    health: u32,
+) -> Entity {
+    let (index, rotation) = ground_sprite(sprite_materials, tile, is_land);
+    commands
+        .spawn_bundle(SpriteSheetBundle {
+            texture_atlas: sprite_materials.texture.clone(),
+            sprite: TextureAtlasSprite::new(index),
+            transform: Transform {
+                translation: Vec3::new(16. * tile.0 as f32, 16. * tile.1 as f32, GROUND_Z),
+                rotation: Quat::from_axis_angle(
+                    Vec3::new(0., 0., 1.),
+                    rotation * std::f32::consts::PI / 2.,
+                ),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .insert(Ground)
+        .insert(TerrainHealth(health))
+        .id()
+}
+
+/// Turn the land next to a tile gone under water toward the coast as it now runs.
+pub fn coast_redraw<'a>(
+    sprite_materials: &SpriteMaterials,
+    tile_map: &TileMap,
+    tile: (i32, i32),
+    ground: impl Iterator<Item = (Mut<'a, Transform>, Mut<'a, TextureAtlasSprite>)>,
+) {
+    for (mut ground_tf, mut sprite) in ground {
+        let ground_tile = tile_of(ground_tf.translation);
+        let (dx, dy) = (ground_tile.0 - tile.0, ground_tile.1 - tile.1);
+        if dx.abs() + dy.abs() != 1 {
+            continue;
+        }
+        let (index, rotation) =
+            ground_sprite(sprite_materials, ground_tile, |t| tile_map.is_land(t));
+        sprite.index = index;
+        ground_tf.rotation =
+            Quat::from_axis_angle(Vec3::new(0., 0., 1.), rotation * std::f32::consts::PI / 2.);
+    }
+}
+
+/// Spawn a mountain of the given height on a land tile.
+pub fn mountain_spawn(
+    commands: &mut Commands,
+    sprite_materials: &SpriteMaterials,
+    tile: (i32, i32),
+    height: f32,
+    health: u32,
+) -> Entity {
+    commands
+        .spawn_bundle(SpriteSheetBundle {
+            texture_atlas: sprite_materials.texture.clone(),
+            sprite: TextureAtlasSprite::new(sprite_materials.mountain_index),
+            transform: Transform::from_xyz(16. * tile.0 as f32, 16. * tile.1 as f32, MOUNTAIN_Z),
+            ..Default::default()
+        })
+        .insert(Mountain)
+        .insert(TerrainHealth(health))
+        .insert(Height(height))
+        .id()
+}
 
 //
 // Components
@@ -17,6 +145,68 @@ pub struct Ground;
 #[derive(Component)]
 pub struct Mountain;
 
+#[derive(Component)]
+pub struct Height(pub f32);
+
+/// Hits a terrain tile can still take before being destroyed.
+#[derive(Component)]
+pub struct TerrainHealth(pub u32);
+
+//
+// Events
+//
+
+/// A tile of the terrain changed.
+pub struct TerrainChanged(pub (i32, i32));
+
+/// The whole terrain was replaced, e.g. by loading a saved game.
+pub struct TerrainReset;
+
+//
+// Resources
+//
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Tile {
+    Ground,
+    Mountain,
+    Shallow,
+    Reef,
+}
+
+/// Terrain of the sea, tile by tile. Tiles not in the map are deep water.
+#[derive(Clone)]
+pub struct TileMap {
+    pub w_tiles: i32,
+    pub h_tiles: i32,
+    pub tiles: HashMap<(i32, i32), Tile>,
+    // Minimum depth of the shallow water and reef tiles.
+    pub depths: HashMap<(i32, i32), f32>,
+}
+
+impl TileMap {
+    pub fn get(&self, tile: (i32, i32)) -> Option<Tile> {
+        self.tiles.get(&tile).copied()
+    }
+
+    pub fn is_land(&self, tile: (i32, i32)) -> bool {
+        matches!(self.get(tile), Some(Tile::Ground) | Some(Tile::Mountain))
+    }
+
+    pub fn is_mountain(&self, tile: (i32, i32)) -> bool {
+        self.get(tile) == Some(Tile::Mountain)
+    }
+
+    /// Minimum depth, in metres, of the water on the given tile.
+    pub fn depth(&self, tile: (i32, i32)) -> f32 {
+        if self.is_land(tile) {
+            0.
+        } else {
+            *self.depths.get(&tile).unwrap_or(&DEEP_WATER_DEPTH)
+        }
+    }
+}
+
 //
 // Systems
 //
@@ -24,9 +214,11 @@ pub struct Mountain;
 fn islands_spawn(
     mut commands: Commands,
     win_size: Res<WinSize>,
+    game_mode: Res<GameMode>,
     sprite_materials: Res<SpriteMaterials>,
+    mut game_rng: ResMut<GameRng>,
 ) {
-    let mut rng = thread_rng();
+    let rng = &mut game_rng.rng;
     let w_tiles = (win_size.w / (2. * 16.)) as i32;
     let h_tiles = (win_size.h / (2. * 16.)) as i32;
 
@@ -60,61 +252,31 @@ fn islands_spawn(
         }
     }
 
+    // Go through the land in order, so that the same seed always gives the same map.
+    let mut land: Vec<(i32, i32)> = tiles.keys().copied().collect();
+    land.sort_unstable();
+
     // Spwan the ground tiles
-    for (tile_x, tile_y) in tiles.keys() {
-        // Determine sprite & orientation based on adjacent tiles
-        let right = tiles.contains_key(&(*tile_x + 1, *tile_y));
-        let up = tiles.contains_key(&(*tile_x, *tile_y + 1));
-        let left = tiles.contains_key(&(*tile_x - 1, *tile_y));
-        let down = tiles.contains_key(&(*tile_x, *tile_y - 1));
-        let (index, rotation) = match (right, up, left, down) {
-            // 0 adjacent
-            (false, false, false, false) => (sprite_materials.ground6_index, 0.),
-            // 1
-            (true, false, false, false) => (sprite_materials.ground5_index, -2.),
-            (false, true, false, false) => (sprite_materials.ground5_index, -1.),
-            (false, false, true, false) => (sprite_materials.ground5_index, 0.),
-            (false, false, false, true) => (sprite_materials.ground5_index, 1.),
-            // 2
-            (true, true, false, false) => (sprite_materials.ground3_index, -2.),
-            (false, true, true, false) => (sprite_materials.ground3_index, -1.),
-            (false, false, true, true) => (sprite_materials.ground3_index, 0.),
-            (true, false, false, true) => (sprite_materials.ground3_index, 1.),
-
-            (true, false, true, false) => (sprite_materials.ground4_index, 0.),
-            (false, true, false, true) => (sprite_materials.ground4_index, 1.),
-            // 3
-            (true, true, true, false) => (sprite_materials.ground2_index, -1.),
-            (true, true, false, true) => (sprite_materials.ground2_index, 2.),
-            (true, false, true, true) => (sprite_materials.ground2_index, 1.),
-            (false, true, true, true) => (sprite_materials.ground2_index, 0.),
-            // 4
-            (true, true, true, true) => (sprite_materials.ground1_index, 0.),
-        };
-        // Spawn the ground tile
-        commands
-            .spawn_bundle(SpriteSheetBundle {
-                texture_atlas: sprite_materials.texture.clone(),
-                sprite: TextureAtlasSprite::new(index),
-                transform: Transform {
-                    translation: Vec3::new(
-                        16. * (*tile_x as f32),
-                        16. * (*tile_y as f32),
-                        GROUND_Z,
-                    ),
-                    rotation: Quat::from_axis_angle(
-                        Vec3::new(0., 0., 1.),
-                        rotation * std::f32::consts::PI / 2.,
-                    ),
-                    ..Default::default()
-                },
-                ..Default::default()
-            })
-            .insert(Ground);
+    for tile in land.iter() {
+        ground_spawn(
+            &mut commands,
+            &sprite_materials,
+            *tile,
+            |tile| tiles.contains_key(&tile),
+            GROUND_HEALTH,
+        );
     }
 
+    // Keep track of the terrain.
+    let mut tile_map = TileMap {
+        w_tiles,
+        h_tiles,
+        tiles: tiles.keys().map(|tile| (*tile, Tile::Ground)).collect(),
+        depths: HashMap::new(),
+    };
+
     // Add mountains tiles
-    for (tile_x, tile_y) in tiles.keys() {
+    for (tile_x, tile_y) in land.iter() {
         // Mountains cannot be next to the sea.
         if !(tiles.contains_key(&(*tile_x + 1, *tile_y))
             && tiles.contains_key(&(*tile_x, *tile_y + 1))
@@ -126,21 +288,204 @@ fn islands_spawn(
 
         // Spawn the ground tile
         if rng.gen_range(0..=100) < PERCENTAGE_MOUNTAINS {
-            commands
-                .spawn_bundle(SpriteSheetBundle {
-                    texture_atlas: sprite_materials.texture.clone(),
-                    sprite: TextureAtlasSprite::new(sprite_materials.mountain_index),
-                    transform: Transform {
-                        translation: Vec3::new(
-                            16. * (*tile_x as f32),
-                            16. * (*tile_y as f32),
-                            MOUNTAIN_Z,
-                        ),
-                        ..Default::default()
-                    },
-                    ..Default::default()
-                })
-                .insert(Mountain);
+            mountain_spawn(
+                &mut commands,
+                &sprite_materials,
+                (*tile_x, *tile_y),
+                rng.gen_range(MOUNTAIN_MIN_HEIGHT..MOUNTAIN_MAX_HEIGHT),
+                MOUNTAIN_HEALTH,
+            );
+            tile_map.tiles.insert((*tile_x, *tile_y), Tile::Mountain);
+        }
+    }
+
+    // Add shallow water around the islands, with reefs here and there.
+    for x in -w_tiles..=w_tiles {
+        for y in -h_tiles..=h_tiles {
+            if tiles.contains_key(&(x, y)) {
+                continue;
+            }
+            let distance = (1..=SHALLOW_DEPTHS.len() as i32).find(|d| {
+                (-d..=*d).any(|dx| (-d..=*d).any(|dy| tiles.contains_key(&(x + dx, y + dy))))
+            });
+            if let Some(d) = distance {
+                if d > 1 && rng.gen_range(0..=100) < PERCENTAGE_REEFS {
+                    tile_map.tiles.insert((x, y), Tile::Reef);
+                    tile_map.depths.insert((x, y), REEF_DEPTH);
+                } else {
+                    tile_map.tiles.insert((x, y), Tile::Shallow);
+                    tile_map
+                        .depths
+                        .insert((x, y), SHALLOW_DEPTHS[d as usize - 1]);
+                }
+            }
+        }
+    }
+    commands.insert_resource(tile_map);
+
+    // Add ports on some coastal tiles.
+    let coast: Vec<&(i32, i32)> = land
+        .iter()
+        .filter(|(tile_x, tile_y)| {
+            !(tiles.contains_key(&(*tile_x + 1, *tile_y))
+                && tiles.contains_key(&(*tile_x, *tile_y + 1))
+                && tiles.contains_key(&(*tile_x - 1, *tile_y))
+                && tiles.contains_key(&(*tile_x, *tile_y - 1)))
+        })
+        .collect();
+    match *game_mode {
+        GameMode::FreeForAll | GameMode::Mission => {
+            for tile in coast.choose_multiple(rng, NUM_PORTS) {
+                port_spawn(&mut commands, &sprite_materials, **tile, None);
+            }
+        }
+        GameMode::CaptureTheFlag | GameMode::TeamBattle => {
+            // Each team has its base on the coast of its own side of the sea.
+            let west = coast.iter().min_by_key(|(tile_x, _)| *tile_x);
+            let east = coast.iter().max_by_key(|(tile_x, _)| *tile_x);
+            for (team, tile) in [(Team(0), west), (Team(1), east)] {
+                if let Some(&&(tile_x, tile_y)) = tile {
+                    // Boats dock two tiles off the coast, toward the open water.
+                    let (dx, dy) = [(1, 0), (-1, 0), (0, 1), (0, -1)]
+                        .into_iter()
+                        .find(|(dx, dy)| !tiles.contains_key(&(tile_x + dx, tile_y + dy)))
+                        .unwrap();
+                    let dock = Vec3::new(
+                        16. * (tile_x + 2 * dx) as f32,
+                        16. * (tile_y + 2 * dy) as f32,
+                        BOAT_Z,
+                    );
+                    if *game_mode == GameMode::CaptureTheFlag {
+                        base_spawn(
+                            &mut commands,
+                            &sprite_materials,
+                            (tile_x, tile_y),
+                            dock,
+                            team,
+                        );
+                    } else {
+                        // Bunkers guard the base from the land around it.
+                        let bunker_tiles: Vec<(i32, i32)> = [
+                            (1, 0),
+                            (-1, 0),
+                            (0, 1),
+                            (0, -1),
+                            (1, 1),
+                            (-1, 1),
+                            (1, -1),
+                            (-1, -1),
+                        ]
+                        .into_iter()
+                        .map(|(dx, dy)| (tile_x + dx, tile_y + dy))
+                        .filter(|tile| tiles.contains_key(tile))
+                        .collect();
+                        fort_spawn(
+                            &mut commands,
+                            &sprite_materials,
+                            (tile_x, tile_y),
+                            dock,
+                            team,
+                            &bunker_tiles,
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn terrain_damage(
+    mut commands: Commands,
+    sprite_materials: Res<SpriteMaterials>,
+    mut tile_map: ResMut<TileMap>,
+    mut explosions: EventReader<ExplosionEvent>,
+    mut terrain_changed: EventWriter<TerrainChanged>,
+    mut query_mountain: Query<(Entity, &Transform, &mut TerrainHealth), With<Mountain>>,
+    mut query_ground: Query<
+        (
+            Entity,
+            &mut Transform,
+            &mut TextureAtlasSprite,
+            &mut TerrainHealth,
+        ),
+        (With<Ground>, Without<Mountain>),
+    >,
+    query_port: Query<&Transform, (With<Port>, Without<Ground>, Without<Mountain>)>,
+) {
+    // Splashes leave the terrain untouched.
+    for hit in explosions
+        .iter()
+        .filter(|explosion| explosion.kind != ExplosionKind::Splash)
+    {
+        // Tiles within the blast, at least the one hit.
+        let r = (hit.radius / 16.).ceil() as i32;
+        let center = tile_of(hit.position);
+        let blasted = (-r..=r)
+            .flat_map(|dx| (-r..=r).map(move |dy| (center.0 + dx, center.1 + dy)))
+            .filter(|tile| {
+                *tile == center
+                    || distance(
+                        hit.position,
+                        Vec3::new(16. * tile.0 as f32, 16. * tile.1 as f32, 0.),
+                    ) <= hit.radius
+            });
+        for tile in blasted {
+            match tile_map.get(tile) {
+                Some(Tile::Mountain) => {
+                    // Repeated hits flatten a mountain down to plain ground.
+                    for (entity, mountain_tf, mut health) in query_mountain.iter_mut() {
+                        if tile_of(mountain_tf.translation) != tile {
+                            continue;
+                        }
+                        health.0 -= health.0.min(1);
+                        if health.0 == 0 {
+                            commands.entity(entity).despawn();
+                            tile_map.tiles.insert(tile, Tile::Ground);
+                            terrain_changed.send(TerrainChanged(tile));
+                        }
+                    }
+                }
+                Some(Tile::Ground) => {
+                    // Only the coast can be blasted away, ports excepted.
+                    let (x, y) = tile;
+                    let coastal = [(x + 1, y), (x - 1, y), (x, y + 1), (x, y - 1)]
+                        .iter()
+                        .any(|neighbour| !tile_map.is_land(*neighbour));
+                    let port = query_port
+                        .iter()
+                        .any(|port_tf| tile_of(port_tf.translation) == tile);
+                    if !coastal || port {
+                        continue;
+                    }
+                    let mut destroyed = false;
+                    for (entity, ground_tf, _, mut health) in query_ground.iter_mut() {
+                        if tile_of(ground_tf.translation) != tile {
+                            continue;
+                        }
+                        health.0 -= health.0.min(1);
+                        if health.0 == 0 {
+                            commands.entity(entity).despawn();
+                            destroyed = true;
+                        }
+                    }
+                    if !destroyed {
+                        continue;
+                    }
+                    tile_map.tiles.insert(tile, Tile::Shallow);
+                    tile_map.depths.insert(tile, CRATER_DEPTH);
+                    terrain_changed.send(TerrainChanged(tile));
+                    // Redraw the coast around the new channel.
+                    coast_redraw(
+                        &sprite_materials,
+                        &tile_map,
+                        tile,
+                        query_ground
+                            .iter_mut()
+                            .map(|(_, ground_tf, sprite, _)| (ground_tf, sprite)),
+                    );
+                }
+                _ => {}
+            }
         }
     }
 }
@@ -153,6 +498,13 @@ pub struct IslandPlugin;
 
 impl Plugin for IslandPlugin {
     fn build(&self, app: &mut App) {
-        app.add_startup_stage("game_setup_scene", SystemStage::single(islands_spawn));
+        app.add_event::<TerrainChanged>()
+            .add_event::<TerrainReset>()
+            .add_startup_stage("game_setup_scene", SystemStage::single(islands_spawn))
+            .add_system(
+                terrain_damage
+                    .label(GameSystem::Islands)
+                    .after(GameSystem::Ports),
+            );
     }
 }
@@ -0,0 +1,28 @@
+// The game, as a library for the `naval` binary and for bots driving it through `env`.
+
+pub mod camera;
+pub mod canonball;
+pub mod common;
+pub mod ctf;
+pub mod dashboard;
+pub mod env;
+pub mod explosion;
+pub mod fog;
+pub mod island;
+pub mod mine;
+pub mod minimap;
+pub mod mission;
+pub mod net;
+pub mod player;
+pub mod port;
+pub mod replay;
+pub mod save;
+pub mod script;
+pub mod spectator;
+pub mod stats;
+pub mod team;
+pub mod torpedo;
+pub mod tournament;
+pub mod wake;
+pub mod water;
+pub mod weather;
@@ -1,24 +1,38 @@
 //#![allow(unused)]
 
-mod canonball;
-mod common;
-mod dashboard;
-mod explosion;
-mod island;
-mod player;
-mod torpedo;
-
 use bevy::prelude::*;
 use bevy_kira_audio::{Audio, AudioChannel, AudioPlugin};
 // use bevy::diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin};
 
-use canonball::CanonBallPlugin;
-use common::{AudioMaterials, SpriteMaterials, WinSize};
-use dashboard::DashboardPlugin;
-use explosion::ExplosionPlugin;
-use island::IslandPlugin;
-use player::PlayerPlugin;
-use torpedo::TorpedoPlugin;
+use naval::camera::CameraPlugin;
+use naval::canonball::CanonBallPlugin;
+use naval::common::{
+    AudioMaterials, FriendlyFire, GameMode, GameRng, GameState, MainCamera, SpriteMaterials,
+    WinSize,
+};
+use naval::ctf::CtfPlugin;
+use naval::dashboard::DashboardPlugin;
+use naval::env;
+use naval::explosion::ExplosionPlugin;
+use naval::fog::FogPlugin;
+use naval::island::IslandPlugin;
+use naval::mine::MinePlugin;
+use naval::minimap::MinimapPlugin;
+use naval::mission::MissionPlugin;
+use naval::net::NetPlugin;
+use naval::player::{PlayerClass, PlayerPlugin, ShipClass};
+use naval::port::PortPlugin;
+use naval::replay::ReplayPlugin;
+use naval::save::SavePlugin;
+use naval::script::ScriptPlugin;
+use naval::spectator::SpectatorPlugin;
+use naval::stats::StatsPlugin;
+use naval::team::TeamPlugin;
+use naval::torpedo::TorpedoPlugin;
+use naval::tournament;
+use naval::wake::WakePlugin;
+use naval::water::WaterPlugin;
+use naval::weather::WeatherPlugin;
 
 fn setup(
     mut commands: Commands,
@@ -28,7 +42,9 @@ fn setup(
     mut texture_atlases: ResMut<Assets<TextureAtlas>>,
 ) {
     // Position cameras
-    commands.spawn_bundle(OrthographicCameraBundle::new_2d());
+    commands
+        .spawn_bundle(OrthographicCameraBundle::new_2d())
+        .insert(MainCamera);
     commands.spawn_bundle(UiCameraBundle::default());
 
     // Manage window
@@ -99,6 +115,14 @@ fn setup(
         min: Vec2::new(32., 64.),
         max: Vec2::new(64., 96.),
     });
+    let blue_flag_index = texture_atlas.add_texture(bevy::sprite::Rect {
+        min: Vec2::new(80., 80.),
+        max: Vec2::new(96., 96.),
+    });
+    let red_flag_index = texture_atlas.add_texture(bevy::sprite::Rect {
+        min: Vec2::new(64., 80.),
+        max: Vec2::new(80., 96.),
+    });
     // Read explosion spritesheet
     let texture_handle_explosion = asset_server.load("explosion.png");
     let texture_atlas_explosion =
@@ -120,6 +144,8 @@ fn setup(
         mountain_index,
         canon_sight_index,
         torpedo_sight_index,
+        blue_flag_index,
+        red_flag_index,
         explosion: texture_atlases.add(texture_atlas_explosion),
     });
 
@@ -128,6 +154,7 @@ fn setup(
         canon_sound: asset_server.load("GunShotGverb.ogg"),
         explosion_sound: asset_server.load("ExplosionMetalGverb.ogg"),
         torpedo_sound: asset_server.load("SplashGverb.ogg"),
+        splash_sound: asset_server.load("SplashGverb.ogg"),
         engine_sound: asset_server.load("BattleShipMovementAmbient.ogg"),
         weapon_channel: AudioChannel::new("weapon".to_string()),
         explosion_channel: AudioChannel::new("explosion".to_string()),
@@ -143,7 +170,62 @@ fn setup(
     audio.pause_channel(&audio_materials.engine_channel);
 }
 
+/// Game mode selected on the command line, e.g. `naval --mode ctf`, `naval --mode teams` or
+/// `naval --mission <file>`.
+fn game_mode() -> GameMode {
+    let args: Vec<String> = std::env::args().collect();
+    if args.iter().any(|arg| arg == "--mission") {
+        return GameMode::Mission;
+    }
+    match args.iter().position(|arg| arg == "--mode") {
+        Some(i) => match args.get(i + 1).map(String::as_str) {
+            Some("ctf") => GameMode::CaptureTheFlag,
+            Some("teams") => GameMode::TeamBattle,
+            _ => GameMode::FreeForAll,
+        },
+        None => GameMode::FreeForAll,
+    }
+}
+
+/// Whether weapons spare the own team, with `naval --no-friendly-fire`.
+fn friendly_fire() -> FriendlyFire {
+    FriendlyFire(!std::env::args().any(|arg| arg == "--no-friendly-fire"))
+}
+
+/// Class of the boat of the player, e.g. `naval --class cruiser`, a frigate otherwise.
+fn player_class() -> PlayerClass {
+    let args: Vec<String> = std::env::args().collect();
+    let class = args
+        .iter()
+        .position(|arg| arg == "--class")
+        .and_then(|i| args.get(i + 1))
+        .and_then(|name| ShipClass::from_name(name));
+    PlayerClass(class.unwrap_or_default())
+}
+
+/// Seed of the game given on the command line, e.g. `naval --seed 42`, random otherwise.
+fn game_seed() -> u64 {
+    let args: Vec<String> = std::env::args().collect();
+    match args.iter().position(|arg| arg == "--seed") {
+        Some(i) => args
+            .get(i + 1)
+            .and_then(|seed| seed.parse().ok())
+            .unwrap_or_else(rand::random),
+        None => rand::random(),
+    }
+}
+
 fn main() {
+    // Headless game driven over stdin/stdout, to train bots.
+    if std::env::args().any(|arg| arg == "--env") {
+        env::serve();
+        return;
+    }
+    // Headless games between bots, to rate them.
+    if std::env::args().any(|arg| arg == "--tournament") {
+        tournament::run();
+        return;
+    }
     App::new()
         .insert_resource(ClearColor(Color::rgb(0., 0.4118, 0.5804)))
         .insert_resource(WindowDescriptor {
@@ -152,17 +234,44 @@ fn main() {
             height: 700.0,
             ..Default::default()
         })
+        .insert_resource(game_mode())
+        .insert_resource(friendly_fire())
+        .insert_resource(player_class())
+        .insert_resource(GameRng::new(game_seed()))
         .add_plugins(DefaultPlugins)
         .add_plugin(AudioPlugin)
-        .add_system(bevy::input::system::exit_on_esc_system)
+        // Out of the game systems, to quit even when a replay is paused.
+        .add_system_to_stage(
+            CoreStage::PreUpdate,
+            bevy::input::system::exit_on_esc_system,
+        )
         // .add_plugin(LogDiagnosticsPlugin::default())
         // .add_plugin(FrameTimeDiagnosticsPlugin::default())
+        .add_state(GameState::Playing)
         .add_startup_system(setup)
+        // Before the game plugins, as a mission, a replay or the host joined sets the game to play.
+        .add_plugin(NetPlugin)
+        .add_plugin(MissionPlugin)
+        .add_plugin(ReplayPlugin)
+        .add_plugin(SavePlugin)
+        .add_plugin(SpectatorPlugin)
+        .add_plugin(CameraPlugin)
         .add_plugin(PlayerPlugin)
         .add_plugin(CanonBallPlugin)
         .add_plugin(TorpedoPlugin)
+        .add_plugin(MinePlugin)
+        .add_plugin(PortPlugin)
         .add_plugin(IslandPlugin)
+        .add_plugin(WaterPlugin)
+        .add_plugin(MinimapPlugin)
+        .add_plugin(CtfPlugin)
+        .add_plugin(TeamPlugin)
         .add_plugin(ExplosionPlugin)
         .add_plugin(DashboardPlugin)
+        .add_plugin(WeatherPlugin)
+        .add_plugin(FogPlugin)
+        .add_plugin(WakePlugin)
+        .add_plugin(ScriptPlugin)
+        .add_plugin(StatsPlugin)
         .run();
 }
@@ -0,0 +1,179 @@
+use bevy::prelude::*;
+
+use crate::canonball::{CanonBall, Energy};
+use crate::common::*;
+use crate::explosion::{ExplosionEvent, ExplosionKind, HitEvent, Weapon};
+use crate::player::{Life, Player};
+use crate::weather::Weather;
+
+const MINE_ARMING_DELAY: f32 = 3.;
+const MINE_RADIUS: f32 = 16.;
+const MINE_DAMAGE: u32 = 40;
+// Part of the current speed at which mines drift.
+const MINE_DRIFT: f32 = 0.2;
+// Distance under which mines laid by others become visible.
+const MINE_DETECTION_RANGE: f32 = 80.;
+
+//
+// Misc functions
+//
+
+/// Spawn a mine laid by the given boat.
+pub fn mine_spawn(
+    commands: &mut Commands,
+    sprite_materials: &SpriteMaterials,
+    layer: Entity,
+    position: Vec3,
+) -> Entity {
+    commands
+        .spawn_bundle(SpriteSheetBundle {
+            texture_atlas: sprite_materials.texture.clone(),
+            sprite: TextureAtlasSprite {
+                index: sprite_materials.canonball_index,
+                color: Color::rgb(0.3, 0.1, 0.1),
+                custom_size: Some(Vec2::splat(10.)),
+                ..Default::default()
+            },
+            transform: Transform::from_xyz(position.x, position.y, TORPEDO_Z),
+            ..Default::default()
+        })
+        .insert(Mine)
+        .insert(LaidBy(layer))
+        .insert(Timer::from_seconds(MINE_ARMING_DELAY, false))
+        .id()
+}
+
+//
+// Components
+//
+
+/// A contact mine, armed once its timer is finished.
+#[derive(Component)]
+pub struct Mine;
+
+/// Boat that laid the mine.
+#[derive(Component)]
+pub struct LaidBy(pub Entity);
+
+//
+// Systems
+//
+
+fn mine_drift(
+    time: Res<GameTime>,
+    weather: Res<Weather>,
+    mut query: Query<(&mut Transform, &mut Timer), With<Mine>>,
+) {
+    for (mut mine_tf, mut timer) in query.iter_mut() {
+        // Arm the mine after a delay.
+        timer.tick(time.delta());
+        // Mines slowly drift with the current.
+        let drift = MINE_DRIFT * weather.current_at(mine_tf.translation) * time.delta_seconds();
+        mine_tf.translation += drift.extend(0.);
+    }
+}
+
+fn mine_visibility(
+    query_player: Query<(Entity, &Transform), With<Player>>,
+    mut query_mine: Query<(&Transform, &LaidBy, &mut Visibility), (With<Mine>, Without<Player>)>,
+) {
+    if let Ok((player_entity, player_tf)) = query_player.get_single() {
+        // Mines laid by others are only visible when close enough.
+        for (mine_tf, laid_by, mut visibility) in query_mine.iter_mut() {
+            visibility.is_visible = laid_by.0 == player_entity
+                || distance(mine_tf.translation, player_tf.translation) < MINE_DETECTION_RANGE;
+        }
+    }
+}
+
+fn mine_hull_collision(
+    mut commands: Commands,
+    friendly_fire: Res<FriendlyFire>,
+    mut explosions: EventWriter<ExplosionEvent>,
+    mut hits: EventWriter<HitEvent>,
+    query_mine: Query<(Entity, &Transform, &LaidBy, &Timer), With<Mine>>,
+    mut query_hull: Query<(Entity, &Transform, &mut Life, Option<&Team>), Without<Mine>>,
+    query_team: Query<&Team>,
+) {
+    for (mine_entity, mine_tf, laid_by, timer) in query_mine.iter() {
+        if !timer.finished() {
+            continue;
+        }
+        // Detonate if a hull it can harm enters the radius of the mine, damaging every such
+        // hull around.
+        let source = query_team.get(laid_by.0).ok();
+        let triggered = query_hull.iter().any(|(_, hull_tf, _, team)| {
+            distance(hull_tf.translation, mine_tf.translation) < MINE_RADIUS
+                && harms(&friendly_fire, source, team)
+        });
+        if triggered {
+            for (hull_entity, hull_tf, mut life, team) in query_hull.iter_mut() {
+                if distance(hull_tf.translation, mine_tf.translation) < MINE_RADIUS
+                    && harms(&friendly_fire, source, team)
+                {
+                    let before = life.0;
+                    life.0 -= life.0.min(MINE_DAMAGE);
+                    hits.send(HitEvent::new(
+                        Some(laid_by.0),
+                        hull_entity,
+                        Weapon::Mine,
+                        before,
+                        life.0,
+                    ));
+                }
+            }
+            commands.entity(mine_entity).despawn();
+            explosions.send(ExplosionEvent {
+                position: mine_tf.translation,
+                kind: ExplosionKind::Mine,
+                radius: MINE_RADIUS,
+                source: Some(laid_by.0),
+            });
+        }
+    }
+}
+
+fn mine_canonball_clearing(
+    mut commands: Commands,
+    mut explosions: EventWriter<ExplosionEvent>,
+    query_mine: Query<(Entity, &Transform, &LaidBy), With<Mine>>,
+    query_canonball: Query<(&Transform, &Energy), With<CanonBall>>,
+) {
+    for (canonball_tf, energy) in query_canonball.iter() {
+        // Only canonballs landing in the water can hit mines.
+        if energy.0 != 0. {
+            continue;
+        }
+        for (mine_entity, mine_tf, laid_by) in query_mine.iter() {
+            if distance(canonball_tf.translation, mine_tf.translation) < MINE_RADIUS {
+                commands.entity(mine_entity).despawn();
+                explosions.send(ExplosionEvent {
+                    position: mine_tf.translation,
+                    kind: ExplosionKind::Mine,
+                    radius: MINE_RADIUS,
+                    source: Some(laid_by.0),
+                });
+            }
+        }
+    }
+}
+
+//
+// Plugin
+//
+
+pub struct MinePlugin;
+
+impl Plugin for MinePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+            SystemSet::new()
+                .label(GameSystem::Mines)
+                .after(GameSystem::Torpedos)
+                .with_system(mine_drift)
+                .with_system(mine_visibility.after(mine_drift))
+                .with_system(mine_hull_collision.after(mine_visibility))
+                .with_system(mine_canonball_clearing.after(mine_hull_collision)),
+        );
+    }
+}
@@ -0,0 +1,189 @@
+use bevy::prelude::*;
+use bevy::render::render_resource::{Extent3d, FilterMode, TextureDimension, TextureFormat};
+
+use crate::common::*;
+use crate::fog::Fog;
+use crate::island::{Tile, TileMap};
+use crate::player::{Player, Ship};
+use crate::torpedo::{Launcher, Torpedo};
+
+// Size, in screen pixels, of a tile on the minimap.
+const MINIMAP_SCALE: f32 = 3.;
+const HEADING_LENGTH: i32 = 4;
+
+const WATER_COLOR: [u8; 4] = [0, 80, 120, 200];
+const SHALLOW_COLOR: [u8; 4] = [30, 150, 160, 200];
+const REEF_COLOR: [u8; 4] = [90, 130, 115, 220];
+const GROUND_COLOR: [u8; 4] = [200, 180, 120, 220];
+const MOUNTAIN_COLOR: [u8; 4] = [110, 80, 50, 220];
+const UNEXPLORED_COLOR: [u8; 4] = [30, 30, 40, 220];
+const PLAYER_COLOR: [u8; 4] = [255, 255, 255, 255];
+const FRIEND_COLOR: [u8; 4] = [80, 160, 255, 255];
+const ENEMY_COLOR: [u8; 4] = [255, 40, 40, 255];
+const TORPEDO_COLOR: [u8; 4] = [255, 220, 0, 255];
+
+//
+// Misc functions
+//
+
+/// Paint a pixel of the minimap at the given tile, if within the map.
+fn paint(image: &mut Image, tile_map: &TileMap, tile: (i32, i32), color: [u8; 4]) {
+    if tile.0.abs() > tile_map.w_tiles || tile.1.abs() > tile_map.h_tiles {
+        return;
+    }
+    let width = 2 * tile_map.w_tiles + 1;
+    let x = tile.0 + tile_map.w_tiles;
+    let y = tile_map.h_tiles - tile.1;
+    let i = 4 * (y * width + x) as usize;
+    image.data[i..i + 4].copy_from_slice(&color);
+}
+
+//
+// Components
+//
+
+#[derive(Component)]
+struct Minimap;
+
+//
+// Resources
+//
+
+struct MinimapImage(Handle<Image>);
+
+//
+// Systems
+//
+
+fn minimap_setup(
+    mut commands: Commands,
+    tile_map: Res<TileMap>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    // One pixel per tile, scaled up without smoothing.
+    let (width, height) = (2 * tile_map.w_tiles + 1, 2 * tile_map.h_tiles + 1);
+    let mut image = Image::new_fill(
+        Extent3d {
+            width: width as u32,
+            height: height as u32,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &WATER_COLOR,
+        TextureFormat::Rgba8UnormSrgb,
+    );
+    image.sampler_descriptor.mag_filter = FilterMode::Nearest;
+    let handle = images.add(image);
+    commands
+        .spawn_bundle(ImageBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    bottom: Val::Px(15.0),
+                    right: Val::Px(15.0),
+                    ..Default::default()
+                },
+                size: Size::new(
+                    Val::Px(MINIMAP_SCALE * width as f32),
+                    Val::Px(MINIMAP_SCALE * height as f32),
+                ),
+                ..Default::default()
+            },
+            image: UiImage(handle.clone()),
+            ..Default::default()
+        })
+        .insert(Minimap);
+    commands.insert_resource(MinimapImage(handle));
+}
+
+fn minimap_toggle(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut query: Query<&mut Style, With<Minimap>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::M) {
+        for mut style in query.iter_mut() {
+            style.display = match style.display {
+                Display::Flex => Display::None,
+                Display::None => Display::Flex,
+            };
+        }
+    }
+}
+
+fn minimap_update(
+    tile_map: Res<TileMap>,
+    fog: Res<Fog>,
+    minimap_image: Res<MinimapImage>,
+    mut images: ResMut<Assets<Image>>,
+    query_ship: Query<(&Transform, &Team, Option<&Player>), With<Ship>>,
+    query_torpedo: Query<(&Transform, &Launcher), With<Torpedo>>,
+    query_team: Query<&Team>,
+) {
+    let image = match images.get_mut(&minimap_image.0) {
+        Some(image) => image,
+        None => return,
+    };
+    // Terrain, as far as it has been explored.
+    for x in -tile_map.w_tiles..=tile_map.w_tiles {
+        for y in -tile_map.h_tiles..=tile_map.h_tiles {
+            let color = if !fog.explored.contains(&(x, y)) {
+                UNEXPLORED_COLOR
+            } else {
+                match tile_map.get((x, y)) {
+                    None => WATER_COLOR,
+                    Some(Tile::Ground) => GROUND_COLOR,
+                    Some(Tile::Mountain) => MOUNTAIN_COLOR,
+                    Some(Tile::Shallow) => SHALLOW_COLOR,
+                    Some(Tile::Reef) => REEF_COLOR,
+                }
+            };
+            paint(image, &tile_map, (x, y), color);
+        }
+    }
+    // Torpedos coming from the enemy.
+    for (torpedo_tf, launcher) in query_torpedo.iter() {
+        let friendly = matches!(query_team.get(launcher.0), Ok(team) if *team == fog.team);
+        if !friendly && fog.is_visible(torpedo_tf.translation) {
+            paint(
+                image,
+                &tile_map,
+                tile_of(torpedo_tf.translation),
+                TORPEDO_COLOR,
+            );
+        }
+    }
+    // Friendly boats and enemies in sight, the player with its heading.
+    for (ship_tf, team, player) in query_ship.iter() {
+        let tile = tile_of(ship_tf.translation);
+        if player.is_some() {
+            let heading = ship_tf.rotation.mul_vec3(Vec3::X);
+            for step in 1..=HEADING_LENGTH {
+                let position = ship_tf.translation + 16. * step as f32 * heading;
+                paint(image, &tile_map, tile_of(position), PLAYER_COLOR);
+            }
+            paint(image, &tile_map, tile, PLAYER_COLOR);
+        } else if *team == fog.team {
+            paint(image, &tile_map, tile, FRIEND_COLOR);
+        } else if fog.is_visible(ship_tf.translation) {
+            paint(image, &tile_map, tile, ENEMY_COLOR);
+        }
+    }
+}
+
+//
+// Plugin
+//
+
+pub struct MinimapPlugin;
+
+impl Plugin for MinimapPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_stage_after(
+            "game_setup_scene",
+            "game_setup_minimap",
+            SystemStage::single(minimap_setup),
+        )
+        .add_system(minimap_toggle)
+        .add_system(minimap_update);
+    }
+}
@@ -0,0 +1,484 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::common::*;
+use crate::player::{ship_spawn, Player, PlayerClass, Ship, ShipClass, ShipSunk, Steering};
+use crate::port::{bunker_spawn, Bunker};
+use crate::spectator::Spectator;
+use crate::team::{crew_orders, Crew};
+
+// Team of the player in a mission, the others being the enemies.
+const PLAYER_TEAM: Team = Team(0);
+// Speed, in pixels per second, and turn rate of the boats of a convoy.
+const CONVOY_SPEED: f32 = 30.;
+const CONVOY_TURN_RATE: f32 = std::f32::consts::PI / 8.;
+// Distance under which a convoy has reached its waypoint.
+const CONVOY_ARRIVAL: f32 = 8.;
+// Time the briefing, then each message, stays on screen.
+const BRIEFING_DURATION: f32 = 10.;
+const MESSAGE_DURATION: f32 = 5.;
+
+//
+// Misc functions
+//
+
+fn vec3(position: (f32, f32), z: f32) -> Vec3 {
+    Vec3::new(position.0, position.1, z)
+}
+
+/// Whether a position lies within the rectangle between two corners.
+fn in_region(position: Vec3, a: (f32, f32), b: (f32, f32)) -> bool {
+    let (a, b) = (Vec2::new(a.0, a.1), Vec2::new(b.0, b.1));
+    let position = position.truncate();
+    position.cmpge(a.min(b)).all() && position.cmple(a.max(b)).all()
+}
+
+/// Spawn a boat or a bunker of the mission, keeping track of it if it is named.
+fn mission_spawn(
+    commands: &mut Commands,
+    sprite_materials: &SpriteMaterials,
+    named: &mut HashMap<String, Entity>,
+    spawn: &Spawn,
+) {
+    let entity = match spawn.kind {
+        SpawnKind::Ship => {
+            let ship = ship_spawn(
+                commands,
+                sprite_materials,
+                vec3(spawn.position, BOAT_Z),
+                spawn.angle,
+                Team(spawn.team),
+                spawn.class,
+            );
+            // A boat sails to its waypoint, or else an enemy boat goes after the closest boat
+            // of the player's side.
+            if let Some(waypoint) = spawn.waypoint {
+                commands.entity(ship).insert(Convoy(vec3(waypoint, BOAT_Z)));
+            } else if Team(spawn.team) != PLAYER_TEAM {
+                commands.entity(ship).insert(Crew).insert(Steering {
+                    throttle: 0.,
+                    rudder: 0.,
+                });
+            }
+            ship
+        }
+        SpawnKind::Bunker => bunker_spawn(
+            commands,
+            sprite_materials,
+            tile_of(vec3(spawn.position, 0.)),
+            Team(spawn.team),
+        ),
+    };
+    if let Some(name) = &spawn.name {
+        named.insert(name.clone(), entity);
+    }
+}
+
+//
+// Components
+//
+
+/// Boat of a convoy, sailing to its waypoint.
+#[derive(Component)]
+struct Convoy(Vec3);
+
+#[derive(Component)]
+struct MissionPanel;
+
+//
+// Resources
+//
+
+/// Text of the mission played, as read from its file or from the replay of the mission.
+#[derive(Clone)]
+pub struct MissionSource(pub String);
+
+/// A mission, as read from a RON file given with `naval --mission <file>`.
+#[derive(Deserialize)]
+struct MissionSpec {
+    name: String,
+    briefing: String,
+    // Seed of the map, random if not given.
+    #[serde(default)]
+    seed: Option<u64>,
+    // Position and heading of the boat of the player.
+    start: ((f32, f32), f32),
+    // Class of the boat of the player, the one given with `--class` if not set.
+    #[serde(default)]
+    class: Option<ShipClass>,
+    #[serde(default)]
+    spawns: Vec<Spawn>,
+    objectives: Vec<Objective>,
+    #[serde(default)]
+    triggers: Vec<Trigger>,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+enum SpawnKind {
+    Ship,
+    Bunker,
+}
+
+/// Boat or bunker spawned at the start of the mission or by a trigger.
+#[derive(Deserialize, Clone)]
+struct Spawn {
+    // Name to refer to it in the objectives and triggers.
+    #[serde(default)]
+    name: Option<String>,
+    kind: SpawnKind,
+    #[serde(default)]
+    team: usize,
+    position: (f32, f32),
+    #[serde(default)]
+    angle: f32,
+    #[serde(default)]
+    class: ShipClass,
+    // Point a boat sails to, as part of a convoy.
+    #[serde(default)]
+    waypoint: Option<(f32, f32)>,
+}
+
+#[derive(Deserialize)]
+enum Objective {
+    // Sink this number of enemy boats.
+    SinkShips(u32),
+    // Destroy all the enemy bunkers.
+    DestroyBunkers,
+    // Bring the named boat within a radius of a waypoint.
+    Escort {
+        ship: String,
+        waypoint: (f32, f32),
+        radius: f32,
+    },
+    // Stay afloat for this number of seconds.
+    Survive(f32),
+}
+
+#[derive(Deserialize)]
+enum Condition {
+    // The boat of the player enters the rectangle between two corners.
+    EnterRegion((f32, f32), (f32, f32)),
+    // The named boat or bunker is sunk or destroyed.
+    Destroyed(String),
+    // This number of seconds passed since the start of the mission.
+    Timer(f32),
+}
+
+/// Spawns and message set off, once, by a condition.
+#[derive(Deserialize)]
+struct Trigger {
+    when: Condition,
+    #[serde(default)]
+    spawns: Vec<Spawn>,
+    #[serde(default)]
+    message: Option<String>,
+}
+
+/// Mission being played and its progress.
+struct Mission {
+    spec: MissionSpec,
+    elapsed: f32,
+    named: HashMap<String, Entity>,
+    // Named boats and bunkers once in the game, to tell those destroyed from those just spawned.
+    seen: HashSet<Entity>,
+    fired: Vec<bool>,
+    completed: Vec<bool>,
+    sunk: u32,
+    bunkers_seen: bool,
+    // Last message, and when it was shown.
+    message: Option<(String, f32)>,
+}
+
+//
+// Systems
+//
+
+fn mission_start(
+    mut commands: Commands,
+    sprite_materials: Res<SpriteMaterials>,
+    spectator: Res<Spectator>,
+    player_class: Res<PlayerClass>,
+    mut mission: ResMut<Mission>,
+) {
+    let ((x, y), angle) = mission.spec.start;
+    let ship = ship_spawn(
+        &mut commands,
+        &sprite_materials,
+        Vec3::new(x, y, BOAT_Z),
+        angle,
+        PLAYER_TEAM,
+        mission.spec.class.unwrap_or(player_class.0),
+    );
+    if !spectator.read_only {
+        commands.entity(ship).insert(Player);
+    }
+    let mission = &mut *mission;
+    for spawn in mission.spec.spawns.iter() {
+        mission_spawn(&mut commands, &sprite_materials, &mut mission.named, spawn);
+    }
+}
+
+fn mission_triggers(
+    mut commands: Commands,
+    time: Res<GameTime>,
+    sprite_materials: Res<SpriteMaterials>,
+    mut mission: ResMut<Mission>,
+    query_player: Query<&Transform, With<Player>>,
+    query_entity: Query<Entity>,
+) {
+    let mission = &mut *mission;
+    mission.elapsed += time.delta_seconds();
+    mission.seen.extend(
+        mission
+            .named
+            .values()
+            .filter(|entity| query_entity.get(**entity).is_ok()),
+    );
+    let player = query_player.get_single().ok();
+    for (i, trigger) in mission.spec.triggers.iter().enumerate() {
+        if mission.fired[i] {
+            continue;
+        }
+        let fire = match &trigger.when {
+            Condition::EnterRegion(a, b) => matches!(
+                player,
+                Some(player_tf) if in_region(player_tf.translation, *a, *b)
+            ),
+            Condition::Destroyed(name) => matches!(
+                mission.named.get(name),
+                Some(entity) if mission.seen.contains(entity) && query_entity.get(*entity).is_err()
+            ),
+            Condition::Timer(seconds) => mission.elapsed >= *seconds,
+        };
+        if !fire {
+            continue;
+        }
+        mission.fired[i] = true;
+        for spawn in trigger.spawns.iter() {
+            mission_spawn(&mut commands, &sprite_materials, &mut mission.named, spawn);
+        }
+        if let Some(message) = &trigger.message {
+            mission.message = Some((message.clone(), mission.elapsed));
+        }
+    }
+}
+
+fn mission_convoy(time: Res<GameTime>, mut query: Query<(&mut Transform, &Convoy)>) {
+    for (mut ship_tf, convoy) in query.iter_mut() {
+        let to_waypoint = (convoy.0 - ship_tf.translation).truncate();
+        if to_waypoint.length() < CONVOY_ARRIVAL {
+            continue;
+        }
+        // Turn toward the waypoint while sailing on.
+        let heading = ship_tf.rotation.mul_vec3(Vec3::X).truncate();
+        let max_angle = CONVOY_TURN_RATE * time.delta_seconds();
+        let angle = heading
+            .angle_between(to_waypoint)
+            .clamp(-max_angle, max_angle);
+        ship_tf.rotate(Quat::from_rotation_z(angle));
+        let step = CONVOY_SPEED * time.delta_seconds();
+        let translation = ship_tf.rotation.mul_vec3(Vec3::new(step, 0., 0.));
+        ship_tf.translation += translation;
+    }
+}
+
+fn mission_objectives(
+    mut mission: ResMut<Mission>,
+    mut game_state: ResMut<State<GameState>>,
+    mut ship_sunk: EventReader<ShipSunk>,
+    query_bunker: Query<&Team, With<Bunker>>,
+    query_ship: Query<&Transform, With<Ship>>,
+) {
+    let mission = &mut *mission;
+    let mut lost = false;
+    for sunk in ship_sunk.iter() {
+        if sunk.player {
+            lost = true;
+        } else if sunk.team != PLAYER_TEAM {
+            mission.sunk += 1;
+        }
+    }
+    let enemy_bunkers = query_bunker
+        .iter()
+        .filter(|team| **team != PLAYER_TEAM)
+        .count();
+    mission.bunkers_seen |= enemy_bunkers > 0;
+    for (i, objective) in mission.spec.objectives.iter().enumerate() {
+        if mission.completed[i] {
+            continue;
+        }
+        mission.completed[i] = match objective {
+            Objective::SinkShips(count) => mission.sunk >= *count,
+            Objective::DestroyBunkers => mission.bunkers_seen && enemy_bunkers == 0,
+            Objective::Escort {
+                ship,
+                waypoint,
+                radius,
+            } => match mission.named.get(ship) {
+                Some(ship) => match query_ship.get(*ship) {
+                    Ok(ship_tf) => distance(ship_tf.translation, vec3(*waypoint, 0.)) < *radius,
+                    // The boat to escort was sunk.
+                    Err(_) if mission.seen.contains(ship) => {
+                        lost = true;
+                        false
+                    }
+                    // Or is only about to set sail.
+                    Err(_) => false,
+                },
+                None => false,
+            },
+            Objective::Survive(seconds) => mission.elapsed >= *seconds,
+        };
+    }
+    let end = if lost {
+        GameState::Lost
+    } else if mission.completed.iter().all(|completed| *completed) {
+        GameState::Won
+    } else {
+        return;
+    };
+    if let Err(err) = game_state.set(end) {
+        warn!("Cannot end the mission: {:?}", err);
+    }
+}
+
+fn mission_panel_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(5.0),
+                    left: Val::Percent(35.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("FiraSans-Bold.ttf"),
+                    font_size: 15.0,
+                    color: Color::BLACK,
+                },
+                Default::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(MissionPanel);
+}
+
+fn mission_panel(
+    mission: Res<Mission>,
+    game_state: Res<State<GameState>>,
+    mut query: Query<&mut Text, With<MissionPanel>>,
+) {
+    let mut lines = vec![mission.spec.name.clone()];
+    if mission.elapsed < BRIEFING_DURATION {
+        lines.push(mission.spec.briefing.clone());
+    }
+    // Objectives, ticked once completed.
+    for (objective, completed) in mission.spec.objectives.iter().zip(mission.completed.iter()) {
+        let text = match objective {
+            Objective::SinkShips(count) => {
+                format!(
+                    "Sink {} enemy boats ({}/{})",
+                    count,
+                    mission.sunk.min(*count),
+                    count
+                )
+            }
+            Objective::DestroyBunkers => String::from("Destroy the enemy bunkers"),
+            Objective::Escort { ship, .. } => format!("Escort the {} to its waypoint", ship),
+            Objective::Survive(seconds) => format!(
+                "Survive for {:.0} s ({:.0} s left)",
+                seconds,
+                (seconds - mission.elapsed).max(0.)
+            ),
+        };
+        lines.push(format!("[{}] {}", if *completed { "x" } else { " " }, text));
+    }
+    match game_state.current() {
+        GameState::Won => lines.push(String::from("Mission accomplished!")),
+        GameState::Lost => lines.push(String::from("Mission failed.")),
+        _ => {
+            if let Some((message, shown)) = &mission.message {
+                if mission.elapsed - shown < MESSAGE_DURATION {
+                    lines.push(message.clone());
+                }
+            }
+        }
+    }
+    for mut text in query.iter_mut() {
+        text.sections[0].value = lines.join("\n");
+    }
+}
+
+//
+// Plugin
+//
+
+/// Play the mission given with `naval --mission <file>`.
+pub struct MissionPlugin;
+
+impl Plugin for MissionPlugin {
+    fn build(&self, app: &mut App) {
+        if app.world.get_resource::<GameMode>() != Some(&GameMode::Mission) {
+            return;
+        }
+        let args: Vec<String> = std::env::args().collect();
+        // A replay brings the mission it recorded.
+        let (path, source) = match app.world.get_resource::<MissionSource>() {
+            Some(source) => (String::from("of the replay"), source.clone()),
+            None => {
+                let path = args
+                    .iter()
+                    .position(|arg| arg == "--mission")
+                    .and_then(|i| args.get(i + 1))
+                    .expect("No mission file given");
+                let text = fs::read_to_string(path)
+                    .unwrap_or_else(|err| panic!("Cannot read the mission {}: {}", path, err));
+                (path.clone(), MissionSource(text))
+            }
+        };
+        let spec: MissionSpec = ron::from_str(&source.0)
+            .unwrap_or_else(|err| panic!("Cannot read the mission {}: {}", path, err));
+        // The map of the mission, unless given on the command line.
+        if let (Some(seed), false) = (spec.seed, args.iter().any(|arg| arg == "--seed")) {
+            app.insert_resource(GameRng::new(seed));
+        }
+        app.insert_resource(source)
+            .insert_resource(Mission {
+                fired: vec![false; spec.triggers.len()],
+                completed: vec![false; spec.objectives.len()],
+                spec,
+                elapsed: 0.,
+                named: HashMap::new(),
+                seen: HashSet::new(),
+                sunk: 0,
+                bunkers_seen: false,
+                message: None,
+            })
+            .add_startup_system_to_stage(StartupStage::PostStartup, mission_start)
+            .add_startup_system(mission_panel_setup)
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    // Orders given before the boats steer and fire.
+                    .after(GameSystem::Weather)
+                    .before(GameSystem::Ships)
+                    .with_system(mission_triggers)
+                    .with_system(crew_orders.after(mission_triggers))
+                    .with_system(mission_convoy.after(crew_orders)),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    // Once the boats moved, fought and sank.
+                    .after(GameSystem::Explosions)
+                    .with_system(mission_objectives),
+            )
+            .add_system(mission_panel);
+    }
+}
@@ -0,0 +1,689 @@
+use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::Duration;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::canonball::CanonBall;
+use crate::common::*;
+use crate::ctf::{Flag, Scores};
+use crate::island::{coast_redraw, Ground, Mountain, TerrainChanged, Tile, TileMap, CRATER_DEPTH};
+use crate::mine::Mine;
+use crate::player::{ship_spawn, Amunitions, Life, Mines, Ship, ShipClass, Speed, Torpedos};
+use crate::port::{bunker_spawn, Bunker};
+use crate::team::TeamScores;
+use crate::torpedo::Torpedo;
+
+// Time between two snapshots sent to the spectators, in seconds.
+const SNAPSHOT_PERIOD: f32 = 0.05;
+// Time a slow spectator may hold up the game before being dropped.
+const WRITE_TIMEOUT: Duration = Duration::from_millis(20);
+
+//
+// Misc functions
+//
+
+/// Value given on the command line after the given option, e.g. `naval --join host:4000`.
+fn arg_value(name: &str) -> Option<String> {
+    let args: Vec<String> = std::env::args().collect();
+    args.iter()
+        .position(|arg| arg == name)
+        .and_then(|i| args.get(i + 1))
+        .cloned()
+}
+
+/// Name of a game mode, as given with `--mode` on the command line.
+fn mode_name(mode: GameMode) -> &'static str {
+    match mode {
+        GameMode::FreeForAll => "ffa",
+        GameMode::CaptureTheFlag => "ctf",
+        GameMode::TeamBattle => "teams",
+        GameMode::Mission => "mission",
+    }
+}
+
+fn mode_of(name: &str) -> GameMode {
+    match name {
+        "ctf" => GameMode::CaptureTheFlag,
+        "teams" => GameMode::TeamBattle,
+        // Spectators do not have the file of a mission, only its sea and the bunkers streamed.
+        _ => GameMode::FreeForAll,
+    }
+}
+
+fn angle_of(rotation: Quat) -> f32 {
+    let heading = rotation.mul_vec3(Vec3::X);
+    heading.y.atan2(heading.x)
+}
+
+fn send_line(stream: &mut TcpStream, message: &Message) -> std::io::Result<()> {
+    let mut line = serde_json::to_string(message)?;
+    line.push('\n');
+    stream.write_all(line.as_bytes())
+}
+
+/// Connect to the game hosted at the given address and read the game it plays.
+fn join(address: &str) -> std::io::Result<(NetClient, Welcome)> {
+    let stream = TcpStream::connect(address)?;
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let welcome = match serde_json::from_str(&line)? {
+        Message::Welcome(welcome) => welcome,
+        Message::Snapshot(_) => {
+            return Err(std::io::Error::new(
+                ErrorKind::InvalidData,
+                "no welcome from the host",
+            ))
+        }
+    };
+    // Snapshots already read along with the welcome.
+    let pending = reader.buffer().to_vec();
+    let stream = reader.into_inner();
+    stream.set_nonblocking(true)?;
+    let client = NetClient {
+        stream: Some(stream),
+        pending,
+        snapshot: None,
+        ships: HashMap::new(),
+    };
+    Ok((client, welcome))
+}
+
+//
+// Messages
+//
+
+/// Line sent by the host to its spectators, as JSON.
+#[derive(Serialize, Deserialize)]
+enum Message {
+    Welcome(Welcome),
+    Snapshot(Snapshot),
+}
+
+/// Game played by the host, for the spectator to build the same sea.
+#[derive(Serialize, Deserialize)]
+struct Welcome {
+    seed: u64,
+    mode: String,
+    friendly_fire: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Snapshot {
+    ships: Vec<ShipState>,
+    projectiles: Vec<ProjectileState>,
+    flags: Vec<FlagState>,
+    bunkers: Vec<BunkerState>,
+    // Tiles of mountains flattened, then of coast blasted into craters, since the start.
+    flattened: Vec<(i32, i32)>,
+    craters: Vec<(i32, i32)>,
+    // Points of each team, and the winner once the game is over.
+    scores: Option<[u32; NUM_TEAMS]>,
+    winner: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ShipState {
+    // Entity of the boat in the game of the host.
+    id: u64,
+    team: usize,
+    class: String,
+    x: f32,
+    y: f32,
+    angle: f32,
+    speed: f32,
+    life: u32,
+    amunitions: u32,
+    torpedos: u32,
+    mines: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ProjectileState {
+    kind: ProjectileKind,
+    x: f32,
+    y: f32,
+    angle: f32,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+enum ProjectileKind {
+    CanonBall,
+    Torpedo,
+    Mine,
+}
+
+#[derive(Serialize, Deserialize)]
+struct FlagState {
+    team: usize,
+    x: f32,
+    y: f32,
+}
+
+/// Bunker still standing, including those spawned during a mission.
+#[derive(Serialize, Deserialize)]
+struct BunkerState {
+    tile: (i32, i32),
+    team: usize,
+    life: u32,
+}
+
+//
+// Components
+//
+
+/// Canonball, torpedo or mine of the host, only drawn.
+#[derive(Component)]
+struct RemoteProjectile;
+
+//
+// Resources
+//
+
+/// Game hosted with `naval --host <port>`, streamed to its spectators.
+struct NetHost {
+    listener: TcpListener,
+    spectators: Vec<TcpStream>,
+    timer: Timer,
+    // Terrain changed since the start, for the spectators joining later.
+    flattened: Vec<(i32, i32)>,
+    craters: Vec<(i32, i32)>,
+}
+
+/// Game joined with `naval --join <address>`, mirrored from the snapshots of the host.
+struct NetClient {
+    // None once the host is gone.
+    stream: Option<TcpStream>,
+    // Bytes received after the last full line.
+    pending: Vec<u8>,
+    // Latest snapshot, not mirrored yet.
+    snapshot: Option<Snapshot>,
+    // Boats of the host, by their entity there, and their mirror here.
+    ships: HashMap<u64, Entity>,
+}
+
+//
+// Systems
+//
+
+fn host_accept(
+    game_rng: Res<GameRng>,
+    game_mode: Res<GameMode>,
+    friendly_fire: Res<FriendlyFire>,
+    mut host: ResMut<NetHost>,
+) {
+    loop {
+        let (mut stream, address) = match host.listener.accept() {
+            Ok(connection) => connection,
+            Err(err) if err.kind() == ErrorKind::WouldBlock => return,
+            Err(err) => {
+                warn!("Cannot accept a spectator: {}", err);
+                return;
+            }
+        };
+        let welcome = Message::Welcome(Welcome {
+            seed: game_rng.seed,
+            mode: mode_name(*game_mode).to_string(),
+            friendly_fire: friendly_fire.0,
+        });
+        let joined = stream
+            .set_nonblocking(false)
+            .and_then(|_| stream.set_write_timeout(Some(WRITE_TIMEOUT)))
+            .and_then(|_| stream.set_nodelay(true))
+            .and_then(|_| send_line(&mut stream, &welcome));
+        match joined {
+            Ok(_) => {
+                info!("Spectator joined from {}", address);
+                host.spectators.push(stream);
+            }
+            Err(err) => warn!("Cannot welcome the spectator {}: {}", address, err),
+        }
+    }
+}
+
+fn host_terrain(
+    tile_map: Res<TileMap>,
+    mut host: ResMut<NetHost>,
+    mut terrain_changed: EventReader<TerrainChanged>,
+) {
+    for TerrainChanged(tile) in terrain_changed.iter() {
+        // A flattened mountain leaves ground, a blasted coast a crater.
+        if tile_map.get(*tile) == Some(Tile::Ground) {
+            host.flattened.push(*tile);
+        } else {
+            host.craters.push(*tile);
+        }
+    }
+}
+
+fn host_snapshot(
+    time: Res<Time>,
+    mut host: ResMut<NetHost>,
+    scores: Option<Res<Scores>>,
+    team_scores: Option<Res<TeamScores>>,
+    query_ship: Query<
+        (
+            Entity,
+            &Transform,
+            &Team,
+            &ShipClass,
+            &Speed,
+            &Life,
+            &Children,
+        ),
+        With<Ship>,
+    >,
+    query_weapon: Query<(Option<&Amunitions>, Option<&Torpedos>, Option<&Mines>)>,
+    query_projectile: Query<
+        (&Transform, Option<&CanonBall>, Option<&Torpedo>),
+        Or<(With<CanonBall>, With<Torpedo>, With<Mine>)>,
+    >,
+    query_flag: Query<(&Transform, &Team), With<Flag>>,
+    query_bunker: Query<(&Transform, &Team, &Life), With<Bunker>>,
+) {
+    if !host.timer.tick(time.delta()).just_finished() || host.spectators.is_empty() {
+        return;
+    }
+    let ships = query_ship
+        .iter()
+        .map(|(entity, ship_tf, team, class, speed, life, children)| {
+            let mut ship = ShipState {
+                id: entity.to_bits(),
+                team: team.0,
+                class: class.name().to_string(),
+                x: ship_tf.translation.x,
+                y: ship_tf.translation.y,
+                angle: angle_of(ship_tf.rotation),
+                speed: speed.0,
+                life: life.0,
+                amunitions: 0,
+                torpedos: 0,
+                mines: 0,
+            };
+            for (amunitions, torpedos, mines) in children
+                .iter()
+                .filter_map(|child| query_weapon.get(*child).ok())
+            {
+                ship.amunitions += amunitions.map_or(0, |amunitions| amunitions.0);
+                ship.torpedos += torpedos.map_or(0, |torpedos| torpedos.0);
+                ship.mines += mines.map_or(0, |mines| mines.0);
+            }
+            ship
+        })
+        .collect();
+    let projectiles = query_projectile
+        .iter()
+        .map(|(projectile_tf, canonball, torpedo)| ProjectileState {
+            kind: match (canonball, torpedo) {
+                (Some(_), _) => ProjectileKind::CanonBall,
+                (_, Some(_)) => ProjectileKind::Torpedo,
+                _ => ProjectileKind::Mine,
+            },
+            x: projectile_tf.translation.x,
+            y: projectile_tf.translation.y,
+            angle: angle_of(projectile_tf.rotation),
+        })
+        .collect();
+    let flags = query_flag
+        .iter()
+        .map(|(flag_tf, team)| FlagState {
+            team: team.0,
+            x: flag_tf.translation.x,
+            y: flag_tf.translation.y,
+        })
+        .collect();
+    let bunkers = query_bunker
+        .iter()
+        .map(|(bunker_tf, team, life)| BunkerState {
+            tile: tile_of(bunker_tf.translation),
+            team: team.0,
+            life: life.0,
+        })
+        .collect();
+    let (scores, winner) = match (scores, team_scores) {
+        (Some(scores), _) => (Some(scores.0), None),
+        (_, Some(team_scores)) => (
+            Some(team_scores.points),
+            team_scores.winner.map(|team| team.0),
+        ),
+        _ => (None, None),
+    };
+    let snapshot = Message::Snapshot(Snapshot {
+        ships,
+        projectiles,
+        flags,
+        bunkers,
+        flattened: host.flattened.clone(),
+        craters: host.craters.clone(),
+        scores,
+        winner,
+    });
+    // Spectators too slow or gone are dropped.
+    let spectators = std::mem::take(&mut host.spectators);
+    host.spectators = spectators
+        .into_iter()
+        .filter_map(|mut stream| match send_line(&mut stream, &snapshot) {
+            Ok(_) => Some(stream),
+            Err(err) => {
+                info!("Spectator left: {}", err);
+                None
+            }
+        })
+        .collect();
+}
+
+fn client_receive(mut client: ResMut<NetClient>) {
+    let client = &mut *client;
+    let stream = match client.stream.as_mut() {
+        Some(stream) => stream,
+        None => return,
+    };
+    let mut buffer = [0; 4096];
+    loop {
+        match stream.read(&mut buffer) {
+            Ok(0) => {
+                warn!("The host left the game");
+                client.stream = None;
+                break;
+            }
+            Ok(n) => client.pending.extend_from_slice(&buffer[..n]),
+            Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+            Err(err) => {
+                warn!("Lost the host: {}", err);
+                client.stream = None;
+                break;
+            }
+        }
+    }
+    // Only the latest snapshot is mirrored.
+    while let Some(end) = client.pending.iter().position(|byte| *byte == b'\n') {
+        let line: Vec<u8> = client.pending.drain(..=end).collect();
+        match serde_json::from_slice(&line) {
+            Ok(Message::Snapshot(snapshot)) => client.snapshot = Some(snapshot),
+            Ok(Message::Welcome(_)) => {}
+            Err(err) => warn!("Cannot read a snapshot from the host: {}", err),
+        }
+    }
+}
+
+fn mirror_ships(
+    mut commands: Commands,
+    sprite_materials: Res<SpriteMaterials>,
+    mut client: ResMut<NetClient>,
+    mut query_ship: Query<(Entity, &mut Transform, &mut Speed, &mut Life, &Children), With<Ship>>,
+    mut query_weapon: Query<(
+        Option<&mut Amunitions>,
+        Option<&mut Torpedos>,
+        Option<&mut Mines>,
+    )>,
+) {
+    let client = &mut *client;
+    let snapshot = match client.snapshot.as_ref() {
+        Some(snapshot) => snapshot,
+        None => return,
+    };
+    let mut mirrored = HashSet::new();
+    for ship in snapshot.ships.iter() {
+        let position = Vec3::new(ship.x, ship.y, BOAT_Z);
+        let entity = client.ships.get(&ship.id).copied();
+        let (mut ship_tf, mut speed, mut life, children) =
+            match entity.and_then(|entity| query_ship.get_mut(entity).ok()) {
+                Some((_, ship_tf, speed, life, children)) => (ship_tf, speed, life, children),
+                // New to the spectator: its weapons are set from the next snapshot.
+                None => {
+                    let class = ShipClass::from_name(&ship.class).unwrap_or_default();
+                    let entity = ship_spawn(
+                        &mut commands,
+                        &sprite_materials,
+                        position,
+                        ship.angle,
+                        Team(ship.team),
+                        class,
+                    );
+                    commands
+                        .entity(entity)
+                        .insert(Speed(ship.speed))
+                        .insert(Life(ship.life));
+                    client.ships.insert(ship.id, entity);
+                    mirrored.insert(entity);
+                    continue;
+                }
+            };
+        mirrored.insert(client.ships[&ship.id]);
+        ship_tf.translation = position;
+        ship_tf.rotation = Quat::from_rotation_z(ship.angle);
+        speed.0 = ship.speed;
+        life.0 = ship.life;
+        for child in children.iter() {
+            let (amunitions, torpedos, mines) = match query_weapon.get_mut(*child) {
+                Ok(weapons) => weapons,
+                Err(_) => continue,
+            };
+            if let Some(mut amunitions) = amunitions {
+                amunitions.0 = ship.amunitions;
+            }
+            if let Some(mut torpedos) = torpedos {
+                torpedos.0 = ship.torpedos;
+            }
+            if let Some(mut mines) = mines {
+                mines.0 = ship.mines;
+            }
+        }
+    }
+    // Boats sunk on the host, and those spawned by the game of the spectator.
+    for (entity, ..) in query_ship.iter() {
+        if !mirrored.contains(&entity) {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+    client.ships.retain(|_, entity| mirrored.contains(entity));
+}
+
+fn mirror_projectiles(
+    mut commands: Commands,
+    sprite_materials: Res<SpriteMaterials>,
+    client: Res<NetClient>,
+    query_projectile: Query<Entity, With<RemoteProjectile>>,
+) {
+    let snapshot = match client.snapshot.as_ref() {
+        Some(snapshot) => snapshot,
+        None => return,
+    };
+    for entity in query_projectile.iter() {
+        commands.entity(entity).despawn();
+    }
+    for projectile in snapshot.projectiles.iter() {
+        let sprite = match projectile.kind {
+            ProjectileKind::CanonBall => TextureAtlasSprite::new(sprite_materials.canonball_index),
+            ProjectileKind::Torpedo => TextureAtlasSprite::new(sprite_materials.torpedo_index),
+            ProjectileKind::Mine => TextureAtlasSprite {
+                index: sprite_materials.canonball_index,
+                color: Color::rgb(0.3, 0.1, 0.1),
+                custom_size: Some(Vec2::splat(10.)),
+                ..Default::default()
+            },
+        };
+        let z = match projectile.kind {
+            ProjectileKind::CanonBall => PROJECTILE_Z,
+            ProjectileKind::Torpedo | ProjectileKind::Mine => TORPEDO_Z,
+        };
+        commands
+            .spawn_bundle(SpriteSheetBundle {
+                texture_atlas: sprite_materials.texture.clone(),
+                sprite,
+                transform: Transform {
+                    translation: Vec3::new(projectile.x, projectile.y, z),
+                    rotation: Quat::from_rotation_z(projectile.angle),
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+            .insert(RemoteProjectile);
+    }
+}
+
+fn mirror_terrain(
+    mut commands: Commands,
+    sprite_materials: Res<SpriteMaterials>,
+    client: Res<NetClient>,
+    mut tile_map: ResMut<TileMap>,
+    mut terrain_changed: EventWriter<TerrainChanged>,
+    query_mountain: Query<(Entity, &Transform), With<Mountain>>,
+    mut query_ground: Query<
+        (Entity, &mut Transform, &mut TextureAtlasSprite),
+        (With<Ground>, Without<Mountain>),
+    >,
+) {
+    let snapshot = match client.snapshot.as_ref() {
+        Some(snapshot) => snapshot,
+        None => return,
+    };
+    for tile in snapshot.flattened.iter() {
+        if !tile_map.is_mountain(*tile) {
+            continue;
+        }
+        for (entity, mountain_tf) in query_mountain.iter() {
+            if tile_of(mountain_tf.translation) == *tile {
+                commands.entity(entity).despawn();
+            }
+        }
+        tile_map.tiles.insert(*tile, Tile::Ground);
+        terrain_changed.send(TerrainChanged(*tile));
+    }
+    for tile in snapshot.craters.iter() {
+        if tile_map.get(*tile) != Some(Tile::Ground) {
+            continue;
+        }
+        for (entity, ground_tf, _) in query_ground.iter() {
+            if tile_of(ground_tf.translation) == *tile {
+                commands.entity(entity).despawn();
+            }
+        }
+        tile_map.tiles.insert(*tile, Tile::Shallow);
+        tile_map.depths.insert(*tile, CRATER_DEPTH);
+        terrain_changed.send(TerrainChanged(*tile));
+        coast_redraw(
+            &sprite_materials,
+            &tile_map,
+            *tile,
+            query_ground
+                .iter_mut()
+                .map(|(_, ground_tf, sprite)| (ground_tf, sprite)),
+        );
+    }
+}
+
+fn mirror_bases(
+    mut commands: Commands,
+    sprite_materials: Res<SpriteMaterials>,
+    client: Res<NetClient>,
+    scores: Option<ResMut<Scores>>,
+    team_scores: Option<ResMut<TeamScores>>,
+    mut query_flag: Query<(&Team, &mut Transform), (With<Flag>, Without<Bunker>)>,
+    mut query_bunker: Query<(&Transform, &mut Life), With<Bunker>>,
+) {
+    let snapshot = match client.snapshot.as_ref() {
+        Some(snapshot) => snapshot,
+        None => return,
+    };
+    for (team, mut flag_tf) in query_flag.iter_mut() {
+        if let Some(flag) = snapshot.flags.iter().find(|flag| flag.team == team.0) {
+            flag_tf.translation.x = flag.x;
+            flag_tf.translation.y = flag.y;
+        }
+    }
+    // Bunkers destroyed on the host are destroyed here too, once out of life.
+    let mut standing = HashSet::new();
+    for (bunker_tf, mut life) in query_bunker.iter_mut() {
+        let tile = tile_of(bunker_tf.translation);
+        standing.insert(tile);
+        life.0 = snapshot
+            .bunkers
+            .iter()
+            .find(|bunker| bunker.tile == tile)
+            .map_or(0, |bunker| bunker.life);
+    }
+    // And those built on the host since, e.g. by a mission, are built here.
+    for bunker in snapshot.bunkers.iter() {
+        if !standing.contains(&bunker.tile) {
+            let entity = bunker_spawn(
+                &mut commands,
+                &sprite_materials,
+                bunker.tile,
+                Team(bunker.team),
+            );
+            commands.entity(entity).insert(Life(bunker.life));
+        }
+    }
+    if let (Some(mut scores), Some(points)) = (scores, snapshot.scores) {
+        scores.0 = points;
+    }
+    if let (Some(mut team_scores), Some(points)) = (team_scores, snapshot.scores) {
+        team_scores.points = points;
+        team_scores.winner = snapshot.winner.map(Team);
+    }
+}
+
+fn mirror_done(mut client: ResMut<NetClient>) {
+    client.snapshot = None;
+}
+
+//
+// Plugin
+//
+
+/// Stream the game to spectators with `naval --host <port>`, or watch a game hosted
+/// elsewhere with `naval --join <address>`.
+pub struct NetPlugin;
+
+impl Plugin for NetPlugin {
+    fn build(&self, app: &mut App) {
+        if let Some(port) = arg_value("--host") {
+            let listener = TcpListener::bind(format!("0.0.0.0:{}", port))
+                .and_then(|listener| listener.set_nonblocking(true).map(|_| listener))
+                .unwrap_or_else(|err| panic!("Cannot host the game on port {}: {}", port, err));
+            app.insert_resource(NetHost {
+                listener,
+                spectators: Vec::new(),
+                timer: Timer::from_seconds(SNAPSHOT_PERIOD, true),
+                flattened: Vec::new(),
+                craters: Vec::new(),
+            })
+            // Once the game systems played the frame.
+            .add_system_to_stage(CoreStage::Last, host_accept)
+            .add_system_to_stage(CoreStage::Last, host_terrain)
+            .add_system_to_stage(
+                CoreStage::Last,
+                host_snapshot.after(host_accept).after(host_terrain),
+            );
+        } else if let Some(address) = arg_value("--join") {
+            let (client, welcome) =
+                join(&address).unwrap_or_else(|err| panic!("Cannot join {}: {}", address, err));
+            // The sea of the host, with the game itself left to it.
+            app.insert_resource(mode_of(&welcome.mode))
+                .insert_resource(FriendlyFire(welcome.friendly_fire))
+                .insert_resource(GameRng::new(welcome.seed))
+                .insert_resource(State::new(GameState::Watching))
+                .insert_resource(client)
+                .add_system_to_stage(CoreStage::PreUpdate, client_receive)
+                .add_system_to_stage(CoreStage::PreUpdate, mirror_ships.after(client_receive))
+                .add_system_to_stage(
+                    CoreStage::PreUpdate,
+                    mirror_projectiles.after(client_receive),
+                )
+                .add_system_to_stage(CoreStage::PreUpdate, mirror_bases.after(client_receive))
+                .add_system_to_stage(CoreStage::PreUpdate, mirror_terrain.after(client_receive))
+                .add_system_to_stage(
+                    CoreStage::PreUpdate,
+                    mirror_done
+                        .after(mirror_ships)
+                        .after(mirror_projectiles)
+                        .after(mirror_bases)
+                        .after(mirror_terrain),
+                );
+        }
+    }
+}
@@ -2,26 +2,40 @@ use std::time::Duration;
 
 use bevy::{prelude::*, sprite::collide_aabb::collide};
 use bevy_kira_audio::Audio;
+use rand::Rng;
+use serde::Deserialize;
 
 use lyon_geom::{point, LineSegment, Point};
 
-use crate::canonball::{CanonBall, Energy};
+use crate::canonball::{canonball_spawn, FiredBy};
 use crate::common::*;
-use crate::island::Ground;
-use crate::torpedo::Torpedo;
+use crate::explosion::{ExplosionEvent, ExplosionKind};
+use crate::fog::Sight;
+use crate::island::{Ground, TileMap};
+use crate::mine::mine_spawn;
+use crate::spectator::Spectator;
+use crate::torpedo::{torpedo_spawn, TorpedoTube, SPREAD_MAX_COUNT, SPREAD_MAX_FAN};
+use crate::weather::Weather;
 
 const BOAT_INIT_POSITION: (f32, f32) = (0., 0.);
 const BOAT_INIT_ANGLE: f32 = 0.;
 
-const BOAT_MAX_SPEED_FORWARD: f32 = 1.5;
+// Maximum speed of a frigate, the other classes being faster or slower.
+pub const BOAT_MAX_SPEED_FORWARD: f32 = 1.5;
 const BOAT_MAX_SPEED_BACKWARD: f32 = -0.5;
 const BOAT_ACCELERATION: f32 = 0.5;
 const BOAT_FRICTION: f32 = 0.2;
 const BOAT_ROTATION_SPEED: f32 = std::f32::consts::PI / 6.;
+// Chances per second, at full speed, to be damaged when touching the bottom.
+const GROUNDING_RATE: f32 = 2.;
+const GROUNDING_DAMAGE: u32 = 5;
+const GROUNDING_DRAG: f32 = 1.;
+// Radius of the explosion of a sinking boat.
+const MAGAZINE_BLAST_RADIUS: f32 = 24.;
 
 const CANON_INIT_ANGLE: f32 = 0.;
-const CANON_MIN_DISTANCE: f32 = 60.;
-const CANON_MAX_DISTANCE: f32 = 500.;
+pub const CANON_MIN_DISTANCE: f32 = 60.;
+pub const CANON_MAX_DISTANCE: f32 = 500.;
 const CANON_ROTATION_SPEED: f32 = std::f32::consts::PI / 2.;
 const CANON_DISTANCE_SPEED: f32 = 100.;
 const CANON_RELOAD: u64 = 2;
@@ -29,9 +43,14 @@ const CANON_RELOAD: u64 = 2;
 const TORPEDO_INIT_ANGLE: f32 = 0.;
 const TORPEDO_SIGHT_DIST: f32 = 48.;
 const TORPEDO_RELOAD: u64 = 5;
+const TORPEDO_FAN_SPEED: f32 = 0.5;
+
+const MINE_RACK_DIST: f32 = 24.;
+const MINE_RELOAD: u64 = 3;
 
 pub const AMUNITIONS: u32 = 50;
 pub const TORPEDOS: u32 = 15;
+pub const MINES: u32 = 10;
 pub const LIFE: u32 = 100;
 
 //
@@ -53,65 +72,49 @@ fn vec_to_point(vec: &Vec3) -> Point<f32> {
     point(vec[0], vec[1])
 }
 
-//
-// Components
-//
-
-#[derive(Component)]
-pub struct Player;
-
-#[derive(Component, Default)]
-pub struct Speed(pub f32);
-
-#[derive(Component)]
-struct Canon;
-
-#[derive(Component)]
-struct CanonSight(f32);
-
-#[derive(Component)]
-struct TorpedoSight;
-
-#[derive(Component)]
-struct CollisionReady(bool);
-
-#[derive(Component)]
-pub struct Life(pub u32);
-
-#[derive(Component)]
-pub struct Amunitions(pub u32);
-
-#[derive(Component)]
-pub struct Torpedos(pub u32);
-
-//
-// Systems
-//
+/// Characteristics of a class of boat.
+pub struct ShipSpec {
+    // Length and beam of the hull, in pixels.
+    pub size: Vec2,
+    // Depth, in metres, under which the boat touches the bottom.
+    pub draft: f32,
+    pub max_speed: f32,
+}
 
-fn player_spawn(
-    mut commands: Commands,
-    sprite_materials: Res<SpriteMaterials>,
-    _win_size: Res<WinSize>,
-) {
+/// Spawn a boat of the given class and team, with its canon sight, torpedo sight and mine rack.
+pub fn ship_spawn(
+    commands: &mut Commands,
+    sprite_materials: &SpriteMaterials,
+    position: Vec3,
+    angle: f32,
+    team: Team,
+    class: ShipClass,
+) -> Entity {
+    let spec = class.spec();
     // Spwan the boat, canon sight and torpedo sight.
     commands
         .spawn_bundle(SpriteSheetBundle {
             texture_atlas: sprite_materials.texture.clone(),
             sprite: TextureAtlasSprite {
                 index: sprite_materials.boat_index,
-                custom_size: Some(Vec2::new(40., 8.)),
+                color: team.color(),
+                custom_size: Some(spec.size),
                 ..Default::default()
             },
             transform: Transform {
-                translation: Vec3::new(BOAT_INIT_POSITION.0, BOAT_INIT_POSITION.1, BOAT_Z),
-                rotation: Quat::from_axis_angle(Vec3::new(0., 0., 1.), BOAT_INIT_ANGLE),
+                translation: Vec3::new(position.x, position.y, BOAT_Z),
+                rotation: Quat::from_axis_angle(Vec3::new(0., 0., 1.), angle),
                 ..Default::default()
             },
             ..Default::default()
         })
-        .insert(Player)
+        .insert(Ship)
+        .insert(team)
+        .insert(class)
+        .insert(Sight::default())
         .insert(Speed::default())
         .insert(Life(LIFE))
+        .insert(Draft(spec.draft))
         .insert(CollisionReady(true))
         // Canon sight
         .with_children(|parent| {
@@ -144,19 +147,267 @@ fn player_spawn(
                     ..Default::default()
                 })
                 .insert(TorpedoSight)
+                .insert(TorpedoTube::default())
                 .insert(Timer::from_seconds(0.0, false))
                 .insert(Torpedos(TORPEDOS));
-        });
+        })
+        // Mine rack
+        .with_children(|parent| {
+            parent
+                .spawn_bundle((
+                    Transform::from_xyz(-MINE_RACK_DIST, 0., 0.),
+                    GlobalTransform::identity(),
+                ))
+                .insert(MineRack)
+                .insert(Timer::from_seconds(0.0, false))
+                .insert(Mines(MINES));
+        })
+        .id()
+}
+
+/// Fire the canon of a boat at a point of the sea, if loaded, and tell whether it fired.
+pub fn canon_fire_at(
+    commands: &mut Commands,
+    sprite_materials: &SpriteMaterials,
+    ship: Entity,
+    ship_tf: &Transform,
+    target: Vec3,
+    amunitions: &mut Amunitions,
+    timer: &mut Timer,
+) -> bool {
+    if !timer.finished() || amunitions.0 == 0 {
+        return false;
+    }
+    let aim = (target - ship_tf.translation).truncate();
+    let canonball = canonball_spawn(
+        commands,
+        sprite_materials,
+        ship_tf.translation.truncate().extend(PROJECTILE_Z),
+        Quat::from_rotation_z(aim.y.atan2(aim.x)),
+        aim.length().clamp(CANON_MIN_DISTANCE, CANON_MAX_DISTANCE),
+    );
+    commands.entity(canonball).insert(FiredBy(ship));
+    amunitions.0 -= 1;
+    timer.set_duration(Duration::from_secs(CANON_RELOAD));
+    timer.reset();
+    true
+}
+
+/// Launch a salvo from the torpedo tube of a boat toward a point of the sea, if loaded, and tell
+/// whether it fired.
+pub fn torpedo_fire_at(
+    commands: &mut Commands,
+    sprite_materials: &SpriteMaterials,
+    ship: Entity,
+    ship_tf: &Transform,
+    target: Vec3,
+    tube: &TorpedoTube,
+    torpedos: &mut Torpedos,
+    timer: &mut Timer,
+) -> bool {
+    if !timer.finished() || torpedos.0 == 0 {
+        return false;
+    }
+    let aim = (target - ship_tf.translation).truncate();
+    for angle in tube.salvo() {
+        torpedo_spawn(
+            commands,
+            sprite_materials,
+            tube.kind,
+            ship,
+            ship_tf.translation,
+            Quat::from_rotation_z(aim.y.atan2(aim.x) + angle),
+        );
+    }
+    torpedos.0 -= 1;
+    timer.set_duration(Duration::from_secs(TORPEDO_RELOAD));
+    timer.reset();
+    true
+}
+
+/// Drop a mine from the rack of a boat, if any left, and tell whether it dropped one.
+pub fn mine_drop_at(
+    commands: &mut Commands,
+    sprite_materials: &SpriteMaterials,
+    ship: Entity,
+    ship_tf: &Transform,
+    rack_tf: &Transform,
+    mines: &mut Mines,
+    timer: &mut Timer,
+) -> bool {
+    if !timer.finished() || mines.0 == 0 {
+        return false;
+    }
+    mine_spawn(
+        commands,
+        sprite_materials,
+        ship,
+        ship_tf.mul_transform(*rack_tf).translation,
+    );
+    mines.0 -= 1;
+    timer.set_duration(Duration::from_secs(MINE_RELOAD));
+    timer.reset();
+    true
+}
+
+//
+// Components
+//
+
+#[derive(Component)]
+pub struct Player;
+
+#[derive(Component)]
+pub struct Ship;
+
+#[derive(Component, Clone, Default)]
+pub struct Speed(pub f32);
+
+/// Class of a boat, setting its size, draft and speed.
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug, Deserialize)]
+pub enum ShipClass {
+    // Small craft, sailing over most shallow waters.
+    Patrol,
+    Frigate,
+    // Large ship, kept off the coasts by its draft.
+    Cruiser,
+}
+
+impl Default for ShipClass {
+    fn default() -> Self {
+        ShipClass::Frigate
+    }
+}
+
+impl ShipClass {
+    pub fn spec(&self) -> ShipSpec {
+        match self {
+            ShipClass::Patrol => ShipSpec {
+                size: Vec2::new(28., 6.),
+                draft: 0.8,
+                max_speed: 2.,
+            },
+            ShipClass::Frigate => ShipSpec {
+                size: Vec2::new(40., 8.),
+                draft: 2.,
+                max_speed: BOAT_MAX_SPEED_FORWARD,
+            },
+            ShipClass::Cruiser => ShipSpec {
+                size: Vec2::new(56., 12.),
+                draft: 3.5,
+                max_speed: 1.1,
+            },
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ShipClass::Patrol => "patrol",
+            ShipClass::Frigate => "frigate",
+            ShipClass::Cruiser => "cruiser",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<ShipClass> {
+        [ShipClass::Patrol, ShipClass::Frigate, ShipClass::Cruiser]
+            .into_iter()
+            .find(|class| class.name() == name)
+    }
+}
+
+#[derive(Component)]
+struct Canon;
+
+#[derive(Component, Clone)]
+pub struct CanonSight(pub f32);
+
+#[derive(Component)]
+pub struct TorpedoSight;
+
+#[derive(Component)]
+pub struct MineRack;
+
+#[derive(Component, Clone)]
+pub struct CollisionReady(bool);
+
+#[derive(Component, Clone)]
+pub struct Life(pub u32);
+
+/// Depth, in metres, of the hull under the water line.
+#[derive(Component)]
+pub struct Draft(pub f32);
+
+#[derive(Component, Clone)]
+pub struct Amunitions(pub u32);
+
+#[derive(Component, Clone)]
+pub struct Torpedos(pub u32);
+
+#[derive(Component, Clone)]
+pub struct Mines(pub u32);
+
+/// Throttle and rudder, from -1 to 1, of a boat steered by a script or a bot.
+#[derive(Component)]
+pub struct Steering {
+    pub throttle: f32,
+    pub rudder: f32,
+}
+
+//
+// Resources
+//
+
+/// Class of the boat of the player, e.g. `naval --class patrol`.
+#[derive(Clone, Copy, Default)]
+pub struct PlayerClass(pub ShipClass);
+
+//
+// Events
+//
+
+/// A ship lost all its life and sank.
+pub struct ShipSunk {
+    pub team: Team,
+    pub player: bool,
+}
+
+//
+// Systems
+//
+
+fn player_spawn(
+    mut commands: Commands,
+    sprite_materials: Res<SpriteMaterials>,
+    game_mode: Res<GameMode>,
+    spectator: Res<Spectator>,
+    player_class: Res<PlayerClass>,
+) {
+    // In capture-the-flag and team modes, boats are spawned at the bases of their team.
+    if *game_mode == GameMode::FreeForAll {
+        let ship = ship_spawn(
+            &mut commands,
+            &sprite_materials,
+            Vec3::new(BOAT_INIT_POSITION.0, BOAT_INIT_POSITION.1, BOAT_Z),
+            BOAT_INIT_ANGLE,
+            Team(0),
+            player_class.0,
+        );
+        if !spectator.read_only {
+            commands.entity(ship).insert(Player);
+        }
+    }
 }
 
 fn player_movement(
     keyboard_input: Res<Input<KeyCode>>,
-    audio: Res<Audio>,
-    time: Res<Time>,
+    audio: Option<Res<Audio>>,
+    time: Res<GameTime>,
     audio_materials: Res<AudioMaterials>,
-    mut query: Query<(&mut Speed, &mut Transform, With<Player>)>,
+    weather: Res<Weather>,
+    mut query: Query<(&mut Speed, &mut Transform, &ShipClass), With<Player>>,
 ) {
-    if let Ok((mut speed, mut transform, _)) = query.get_single_mut() {
+    if let Ok((mut speed, mut transform, class)) = query.get_single_mut() {
+        let max_speed = class.spec().max_speed;
         //Determine new direction of the boat.
         let delta_angle = if keyboard_input.pressed(KeyCode::A) {
             BOAT_ROTATION_SPEED * time.delta_seconds()
@@ -168,7 +419,7 @@ fn player_movement(
         transform.rotate(Quat::from_rotation_z(delta_angle));
         // Determine new position of the boat.
         speed.0 = if keyboard_input.pressed(KeyCode::W) {
-            (speed.0 + BOAT_ACCELERATION * time.delta_seconds()).min(BOAT_MAX_SPEED_FORWARD)
+            (speed.0 + BOAT_ACCELERATION * time.delta_seconds()).min(max_speed)
         } else if keyboard_input.pressed(KeyCode::S) {
             (speed.0 - BOAT_ACCELERATION * time.delta_seconds()).max(BOAT_MAX_SPEED_BACKWARD)
         } else {
@@ -176,19 +427,27 @@ fn player_movement(
         };
         let translation = transform.rotation.mul_vec3(Vec3::new(speed.0, 0., 0.));
         transform.translation += translation;
-        // Start/stop engine sound
-        if speed.0.abs() < 0.1 {
-            audio.pause_channel(&audio_materials.engine_channel);
-        } else {
-            audio.resume_channel(&audio_materials.engine_channel);
+        // The slower the boat, the more it drifts with the current.
+        let drift = (1. - speed.0.abs() / max_speed).max(0.)
+            * weather.current_at(transform.translation)
+            * time.delta_seconds();
+        transform.translation += drift.extend(0.);
+        // Start/stop engine sound, unless running without sound.
+        if let Some(audio) = audio {
+            if speed.0.abs() < 0.1 {
+                audio.pause_channel(&audio_materials.engine_channel);
+            } else {
+                audio.resume_channel(&audio_materials.engine_channel);
+            }
         }
     };
 }
 
 fn canon_movement(
     keyboard_input: Res<Input<KeyCode>>,
-    time: Res<Time>,
-    mut query: Query<(&mut Transform, &mut CanonSight)>,
+    time: Res<GameTime>,
+    query_player: Query<&Player>,
+    mut query: Query<(&Parent, &mut Transform, &mut CanonSight)>,
 ) {
     // Determine new parameters of the canon.
     let delta_angle = if keyboard_input.pressed(KeyCode::J) {
@@ -206,7 +465,10 @@ fn canon_movement(
         0.
     };
     // Update canon sight
-    for (mut transform, mut canon_sight) in query.iter_mut() {
+    for (parent, mut transform, mut canon_sight) in query.iter_mut() {
+        if query_player.get(parent.0).is_err() {
+            continue;
+        }
         canon_sight.0 =
             CANON_MAX_DISTANCE.min(CANON_MIN_DISTANCE.max(canon_sight.0 + delta_distance));
         transform.rotation = transform
@@ -220,8 +482,9 @@ fn canon_movement(
 
 fn torpedo_sight_movement(
     keyboard_input: Res<Input<KeyCode>>,
-    time: Res<Time>,
-    mut query: Query<&mut Transform, With<TorpedoSight>>,
+    time: Res<GameTime>,
+    query_player: Query<&Player>,
+    mut query: Query<(&Parent, &mut Transform), With<TorpedoSight>>,
 ) {
     // Determine new parameters of the torpedo sight.
     let delta_angle = if keyboard_input.pressed(KeyCode::U) {
@@ -232,7 +495,10 @@ fn torpedo_sight_movement(
         0.
     };
     // Update torpedo sight
-    for mut transform in query.iter_mut() {
+    for (parent, mut transform) in query.iter_mut() {
+        if query_player.get(parent.0).is_err() {
+            continue;
+        }
         transform.rotation = transform
             .rotation
             .mul_quat(Quat::from_axis_angle(Vec3::new(0., 0., 1.), delta_angle));
@@ -242,51 +508,86 @@ fn torpedo_sight_movement(
     }
 }
 
+fn torpedo_tube_setting(
+    keyboard_input: Res<Input<KeyCode>>,
+    time: Res<GameTime>,
+    query_player: Query<&Player>,
+    mut query: Query<(&Parent, &mut TorpedoTube), With<TorpedoSight>>,
+) {
+    for (parent, mut tube) in query.iter_mut() {
+        if query_player.get(parent.0).is_err() {
+            continue;
+        }
+        // Select the next kind of torpedo.
+        if keyboard_input.just_pressed(KeyCode::T) {
+            tube.kind = tube.kind.next();
+        }
+        // Widen or narrow the spread.
+        let delta_fan = if keyboard_input.pressed(KeyCode::H) {
+            TORPEDO_FAN_SPEED * time.delta_seconds()
+        } else if keyboard_input.pressed(KeyCode::G) {
+            -TORPEDO_FAN_SPEED * time.delta_seconds()
+        } else {
+            0.
+        };
+        tube.spread_fan = (tube.spread_fan + delta_fan).clamp(0., SPREAD_MAX_FAN);
+        // Add or remove a torpedo from the spread.
+        if keyboard_input.just_pressed(KeyCode::X) {
+            tube.spread_count = (tube.spread_count + 1).min(SPREAD_MAX_COUNT);
+        } else if keyboard_input.just_pressed(KeyCode::Z) {
+            tube.spread_count = (tube.spread_count - 1).max(1);
+        }
+    }
+}
+
 fn canon_fire(
     mut commands: Commands,
     kb: Res<Input<KeyCode>>,
-    audio: Res<Audio>,
-    time: Res<Time>,
+    audio: Option<Res<Audio>>,
+    time: Res<GameTime>,
     sprite_materials: Res<SpriteMaterials>,
     audio_materials: Res<AudioMaterials>,
-    query_boat: Query<&GlobalTransform, With<Player>>,
-    mut query_sight: Query<
-        (&Parent, &GlobalTransform, &mut Amunitions, &mut Timer),
-        With<CanonSight>,
-    >,
+    query_boat: Query<&Transform, With<Player>>,
+    mut query_sight: Query<(&Parent, &Transform, &mut Amunitions, &mut Timer), With<CanonSight>>,
 ) {
-    let (parent, canon_sight_gtf, mut amunitions, mut timer) = query_sight.single_mut();
-    // Increment timer measuring time to reload.
-    timer.tick(time.delta());
+    for (parent, canon_sight_tf, mut amunitions, mut timer) in query_sight.iter_mut() {
+        // Increment timer measuring time to reload.
+        timer.tick(time.delta());
+
+        // Only the canon of the player is fired from the keyboard.
+        let boat_tf = match query_boat.get(parent.0) {
+            Ok(boat_tf) => boat_tf,
+            Err(_) => continue,
+        };
 
-    // If ready to fire, amunitions left and key pressed, trigger fire.
-    if timer.finished() && amunitions.0 > 0 && kb.pressed(KeyCode::Space) {
+        // If ready to fire, amunitions left and key pressed, trigger fire.
+        if !(timer.finished() && amunitions.0 > 0 && kb.pressed(KeyCode::Space)) {
+            continue;
+        }
+        // Position of the sight on the sea, from the boat it is attached to.
+        let canon_sight_gtf = boat_tf.mul_transform(*canon_sight_tf);
         // Compute origin and energy of canonball.
-        let boat_gtf = query_boat.get(parent.0).unwrap();
         let x_dest = canon_sight_gtf.translation.x;
         let y_dest = canon_sight_gtf.translation.y;
-        let x_org = boat_gtf.translation.x;
-        let y_org = boat_gtf.translation.y;
+        let x_org = boat_tf.translation.x;
+        let y_org = boat_tf.translation.y;
         let distance = Vec3::new(x_dest - x_org, y_dest - y_org, 0.).length();
         // Spawn the canonball.
-        commands
-            .spawn_bundle(SpriteSheetBundle {
-                texture_atlas: sprite_materials.texture.clone(),
-                sprite: TextureAtlasSprite::new(sprite_materials.canonball_index),
-                transform: Transform {
-                    translation: Vec3::new(x_org, y_org, PROJECTILE_Z),
-                    rotation: canon_sight_gtf.rotation,
-                    ..Default::default()
-                },
-                ..Default::default()
-            })
-            .insert(CanonBall)
-            .insert(Energy(distance));
-        // Play canon sound.
-        audio.play_in_channel(
-            audio_materials.canon_sound.clone(),
-            &audio_materials.weapon_channel,
+        let canonball = canonball_spawn(
+            &mut commands,
+            &sprite_materials,
+            Vec3::new(x_org, y_org, PROJECTILE_Z),
+            canon_sight_gtf.rotation,
+            distance,
         );
+        commands.entity(canonball).insert(FiredBy(parent.0));
+        // Play canon sound.
+        if let Some(audio) = &audio {
+            audio.play_in_channel(
+                audio_materials.canon_sound.clone(),
+                &audio_materials.weapon_channel,
+            );
+        }
         // Decrease number of amunitions.
         amunitions.0 -= 1;
         // Player will have to wait for reload to fire again.
@@ -298,49 +599,51 @@ fn canon_fire(
 fn torpedo_fire(
     mut commands: Commands,
     kb: Res<Input<KeyCode>>,
-    audio: Res<Audio>,
-    time: Res<Time>,
+    audio: Option<Res<Audio>>,
+    time: Res<GameTime>,
     sprite_materials: Res<SpriteMaterials>,
     audio_materials: Res<AudioMaterials>,
-    query_boat: Query<&GlobalTransform, With<Player>>,
+    query_boat: Query<&Transform, With<Player>>,
     mut query_sight: Query<
-        (&Parent, &GlobalTransform, &mut Torpedos, &mut Timer),
+        (&Parent, &Transform, &TorpedoTube, &mut Torpedos, &mut Timer),
         With<TorpedoSight>,
     >,
 ) {
-    let (parent, torpedo_sight_gtf, mut torpedos, mut timer) = query_sight.single_mut();
-    // Increment timer measuring time to reload.
-    timer.tick(time.delta());
+    for (parent, torpedo_sight_tf, tube, mut torpedos, mut timer) in query_sight.iter_mut() {
+        // Increment timer measuring time to reload.
+        timer.tick(time.delta());
+
+        // Only the torpedos of the player are fired from the keyboard.
+        let boat_tf = match query_boat.get(parent.0) {
+            Ok(boat_tf) => boat_tf,
+            Err(_) => continue,
+        };
 
-    // If ready to fire, amunitions left and key pressed, trigger fire.
-    if timer.finished() && torpedos.0 > 0 && kb.pressed(KeyCode::Return) {
+        // If ready to fire, amunitions left and key pressed, trigger fire.
+        if !(timer.finished() && torpedos.0 > 0 && kb.pressed(KeyCode::Return)) {
+            continue;
+        }
+        let torpedo_sight_gtf = boat_tf.mul_transform(*torpedo_sight_tf);
         // Spawn the torpedos
-        let boat_gtf = query_boat.get(parent.0).unwrap();
-        for angle in [-0.1, 0., 0.1] {
-            commands
-                .spawn_bundle(SpriteSheetBundle {
-                    texture_atlas: sprite_materials.texture.clone(),
-                    sprite: TextureAtlasSprite::new(sprite_materials.torpedo_index),
-                    transform: Transform {
-                        translation: Vec3::new(
-                            boat_gtf.translation.x,
-                            boat_gtf.translation.y,
-                            TORPEDO_Z,
-                        ),
-                        rotation: torpedo_sight_gtf
-                            .rotation
-                            .mul_quat(Quat::from_rotation_z(angle)),
-                        ..Default::default()
-                    },
-                    ..Default::default()
-                })
-                .insert(Torpedo);
+        for angle in tube.salvo() {
+            torpedo_spawn(
+                &mut commands,
+                &sprite_materials,
+                tube.kind,
+                parent.0,
+                boat_tf.translation,
+                torpedo_sight_gtf
+                    .rotation
+                    .mul_quat(Quat::from_rotation_z(angle)),
+            );
         }
         // Play torpedo sound.
-        audio.play_in_channel(
-            audio_materials.torpedo_sound.clone(),
-            &audio_materials.weapon_channel,
-        );
+        if let Some(audio) = &audio {
+            audio.play_in_channel(
+                audio_materials.torpedo_sound.clone(),
+                &audio_materials.weapon_channel,
+            );
+        }
         // Decrease number of torpedos
         torpedos.0 -= 1;
         // Player will have to wait for reload to fire again.
@@ -349,9 +652,71 @@ fn torpedo_fire(
     }
 }
 
-fn player_ground_collision(
-    time: Res<Time>,
-    mut query_player: Query<
+fn mine_drop(
+    mut commands: Commands,
+    kb: Res<Input<KeyCode>>,
+    time: Res<GameTime>,
+    sprite_materials: Res<SpriteMaterials>,
+    query_player: Query<&Transform, With<Player>>,
+    mut query_rack: Query<(&Parent, &Transform, &mut Mines, &mut Timer), With<MineRack>>,
+) {
+    for (parent, rack_tf, mut mines, mut timer) in query_rack.iter_mut() {
+        // Increment timer measuring time to reload.
+        timer.tick(time.delta());
+
+        // If ready, mines left and key pressed, drop a mine behind the player.
+        let player_tf = match query_player.get(parent.0) {
+            Ok(player_tf) => player_tf,
+            Err(_) => continue,
+        };
+        if !(timer.finished() && mines.0 > 0 && kb.pressed(KeyCode::B)) {
+            continue;
+        }
+        mine_spawn(
+            &mut commands,
+            &sprite_materials,
+            parent.0,
+            player_tf.mul_transform(*rack_tf).translation,
+        );
+        // Decrease number of mines.
+        mines.0 -= 1;
+        // Player will have to wait for reload to drop another one.
+        timer.set_duration(Duration::from_secs(MINE_RELOAD));
+        timer.reset();
+    }
+}
+
+fn ship_steering(
+    time: Res<GameTime>,
+    weather: Res<Weather>,
+    mut query: Query<(&Steering, &ShipClass, &mut Speed, &mut Transform), Without<Player>>,
+) {
+    for (steering, class, mut speed, mut ship_tf) in query.iter_mut() {
+        let max_speed = class.spec().max_speed;
+        ship_tf.rotate(Quat::from_rotation_z(
+            steering.rudder * BOAT_ROTATION_SPEED * time.delta_seconds(),
+        ));
+        // Speed up or slow down toward the speed set by the throttle.
+        let target = if steering.throttle >= 0. {
+            steering.throttle * max_speed
+        } else {
+            -steering.throttle * BOAT_MAX_SPEED_BACKWARD
+        };
+        let step = BOAT_ACCELERATION * time.delta_seconds();
+        speed.0 += (target - speed.0).clamp(-step, step);
+        let translation = ship_tf.rotation.mul_vec3(Vec3::new(speed.0, 0., 0.));
+        ship_tf.translation += translation;
+        // The slower the boat, the more it drifts with the current, as that of the player.
+        let drift = (1. - speed.0.abs() / max_speed).max(0.)
+            * weather.current_at(ship_tf.translation)
+            * time.delta_seconds();
+        ship_tf.translation += drift.extend(0.);
+    }
+}
+
+fn ship_ground_collision(
+    time: Res<GameTime>,
+    mut query_ship: Query<
         (
             &mut Transform,
             &TextureAtlasSprite,
@@ -359,131 +724,177 @@ fn player_ground_collision(
             &mut Life,
             &mut CollisionReady,
         ),
-        With<Player>,
+        With<Ship>,
     >,
-    query_ground: Query<&Transform, (With<Ground>, Without<Player>)>,
+    query_ground: Query<&Transform, (With<Ground>, Without<Ship>)>,
 ) {
-    let (mut player_tf, sprite, mut speed, mut life, mut collision_ready) =
-        query_player.single_mut();
-
-    // retrieve boat dimensions
-    let boat_dimensions = sprite.custom_size.unwrap();
-    let dx = boat_dimensions[0] / 2.;
-    let dy = boat_dimensions[1] / 2.;
-    let boat_max_dim = boat_dimensions[0].max(boat_dimensions[1]);
-
-    // Compute relevants points of the boat skull
-    let front_left_pt = player_tf.translation + player_tf.rotation.mul_vec3(Vec3::new(dx, dy, 0.));
-    let front_right_pt =
-        player_tf.translation + player_tf.rotation.mul_vec3(Vec3::new(dx, -dy, 0.));
-    let middle_left_pt = player_tf.translation + player_tf.rotation.mul_vec3(Vec3::new(0., dy, 0.));
-    let middle_right_pt =
-        player_tf.translation + player_tf.rotation.mul_vec3(Vec3::new(0., -dy, 0.));
-    let rear_left_pt = player_tf.translation + player_tf.rotation.mul_vec3(Vec3::new(-dx, dy, 0.));
-    let rear_right_pt =
-        player_tf.translation + player_tf.rotation.mul_vec3(Vec3::new(-dx, -dy, 0.));
-
-    // Compute relevants segments of the boat skull
-    let front = LineSegment {
-        from: point(front_left_pt[0], front_left_pt[1]),
-        to: point(front_right_pt[0], front_right_pt[1]),
-    };
-    let rear = LineSegment {
-        from: point(front_left_pt[0], front_left_pt[1]),
-        to: point(front_right_pt[0], front_right_pt[1]),
-    };
-    let front_left = LineSegment {
-        from: point(middle_left_pt[0], middle_left_pt[1]),
-        to: point(front_left_pt[0], front_left_pt[1]),
-    };
-    let front_right = LineSegment {
-        from: point(middle_right_pt[0], middle_right_pt[1]),
-        to: point(front_right_pt[0], front_right_pt[1]),
-    };
-    let rear_left = LineSegment {
-        from: point(middle_left_pt[0], middle_left_pt[1]),
-        to: point(rear_left_pt[0], rear_left_pt[1]),
-    };
-    let rear_right = LineSegment {
-        from: point(middle_right_pt[0], middle_right_pt[1]),
-        to: point(rear_right_pt[0], rear_right_pt[1]),
-    };
-
-    let mut collision = false;
-
-    // For each ground tile, check for collision
-    for ground_tf in query_ground.iter() {
-        
-        // Quickly filter out obvious non-overlap
-        let collision_square = collide(
-            player_tf.translation,
-            Vec2::splat(boat_max_dim),
-            ground_tf.translation,
-            Vec2::new(16., 16.),
-        );
-        if collision_square.is_none() {
-            continue;
+    for (mut ship_tf, sprite, mut speed, mut life, mut collision_ready) in query_ship.iter_mut() {
+        // retrieve boat dimensions
+        let boat_dimensions = sprite.custom_size.unwrap();
+        let dx = boat_dimensions[0] / 2.;
+        let dy = boat_dimensions[1] / 2.;
+        let boat_max_dim = boat_dimensions[0].max(boat_dimensions[1]);
+
+        // Compute relevants points of the boat skull
+        let front_left_pt = ship_tf.translation + ship_tf.rotation.mul_vec3(Vec3::new(dx, dy, 0.));
+        let front_right_pt =
+            ship_tf.translation + ship_tf.rotation.mul_vec3(Vec3::new(dx, -dy, 0.));
+        let middle_left_pt = ship_tf.translation + ship_tf.rotation.mul_vec3(Vec3::new(0., dy, 0.));
+        let middle_right_pt =
+            ship_tf.translation + ship_tf.rotation.mul_vec3(Vec3::new(0., -dy, 0.));
+        let rear_left_pt = ship_tf.translation + ship_tf.rotation.mul_vec3(Vec3::new(-dx, dy, 0.));
+        let rear_right_pt =
+            ship_tf.translation + ship_tf.rotation.mul_vec3(Vec3::new(-dx, -dy, 0.));
+
+        // Compute relevants segments of the boat skull
+        let front = LineSegment {
+            from: point(front_left_pt[0], front_left_pt[1]),
+            to: point(front_right_pt[0], front_right_pt[1]),
+        };
+        let rear = LineSegment {
+            from: point(front_left_pt[0], front_left_pt[1]),
+            to: point(front_right_pt[0], front_right_pt[1]),
+        };
+        let front_left = LineSegment {
+            from: point(middle_left_pt[0], middle_left_pt[1]),
+            to: point(front_left_pt[0], front_left_pt[1]),
+        };
+        let front_right = LineSegment {
+            from: point(middle_right_pt[0], middle_right_pt[1]),
+            to: point(front_right_pt[0], front_right_pt[1]),
+        };
+        let rear_left = LineSegment {
+            from: point(middle_left_pt[0], middle_left_pt[1]),
+            to: point(rear_left_pt[0], rear_left_pt[1]),
+        };
+        let rear_right = LineSegment {
+            from: point(middle_right_pt[0], middle_right_pt[1]),
+            to: point(rear_right_pt[0], rear_right_pt[1]),
         };
 
-        // Compute segments of the ground tile
-        let mut tile_segments = Vec::new();
-        tile_segments.push(LineSegment {
-            from: vec_to_point(&(ground_tf.translation + Vec3::new(-8., 8., 0.))),
-            to: vec_to_point(&(ground_tf.translation + Vec3::new(8., 8., 0.))),
-        });
-        tile_segments.push(LineSegment {
-            from: vec_to_point(&(ground_tf.translation + Vec3::new(-8., -8., 0.))),
-            to: vec_to_point(&(ground_tf.translation + Vec3::new(8., -8., 0.))),
-        });
-        tile_segments.push(LineSegment {
-            from: vec_to_point(&(ground_tf.translation + Vec3::new(-8., -8., 0.))),
-            to: vec_to_point(&(ground_tf.translation + Vec3::new(-8., 8., 0.))),
-        });
-        tile_segments.push(LineSegment {
-            from: vec_to_point(&(ground_tf.translation + Vec3::new(8., -8., 0.))),
-            to: vec_to_point(&(ground_tf.translation + Vec3::new(8., 8., 0.))),
-        });
-
-        // Conpute collisions of the boat & tile
-        let front_collision = check_collision(&front, &tile_segments);
-        let rear_collision = check_collision(&rear, &tile_segments);
-        let front_left_collision = check_collision(&front_left, &tile_segments);
-        let front_right_collision = check_collision(&front_right, &tile_segments);
-        let rear_left_collision = check_collision(&rear_left, &tile_segments);
-        let rear_right_collision = check_collision(&rear_right, &tile_segments);
-
-        // Change boat's speed & rotation accordingly
-        if front_collision || front_left_collision || front_right_collision {
-            speed.0 = speed.0.min(0.);
+        let mut collision = false;
+
+        // For each ground tile, check for collision
+        for ground_tf in query_ground.iter() {
+            // Quickly filter out obvious non-overlap
+            let collision_square = collide(
+                ship_tf.translation,
+                Vec2::splat(boat_max_dim),
+                ground_tf.translation,
+                Vec2::new(16., 16.),
+            );
+            if collision_square.is_none() {
+                continue;
+            };
+
+            // Compute segments of the ground tile
+            let mut tile_segments = Vec::new();
+            tile_segments.push(LineSegment {
+                from: vec_to_point(&(ground_tf.translation + Vec3::new(-8., 8., 0.))),
+                to: vec_to_point(&(ground_tf.translation + Vec3::new(8., 8., 0.))),
+            });
+            tile_segments.push(LineSegment {
+                from: vec_to_point(&(ground_tf.translation + Vec3::new(-8., -8., 0.))),
+                to: vec_to_point(&(ground_tf.translation + Vec3::new(8., -8., 0.))),
+            });
+            tile_segments.push(LineSegment {
+                from: vec_to_point(&(ground_tf.translation + Vec3::new(-8., -8., 0.))),
+                to: vec_to_point(&(ground_tf.translation + Vec3::new(-8., 8., 0.))),
+            });
+            tile_segments.push(LineSegment {
+                from: vec_to_point(&(ground_tf.translation + Vec3::new(8., -8., 0.))),
+                to: vec_to_point(&(ground_tf.translation + Vec3::new(8., 8., 0.))),
+            });
+
+            // Conpute collisions of the boat & tile
+            let front_collision = check_collision(&front, &tile_segments);
+            let rear_collision = check_collision(&rear, &tile_segments);
+            let front_left_collision = check_collision(&front_left, &tile_segments);
+            let front_right_collision = check_collision(&front_right, &tile_segments);
+            let rear_left_collision = check_collision(&rear_left, &tile_segments);
+            let rear_right_collision = check_collision(&rear_right, &tile_segments);
+
+            // Change boat's speed & rotation accordingly
+            if front_collision || front_left_collision || front_right_collision {
+                speed.0 = speed.0.min(0.);
+            }
+            if rear_collision || rear_left_collision || rear_right_collision {
+                speed.0 = speed.0.max(0.);
+            }
+            let mut delta_rotate = 0.;
+            if front_left_collision || rear_right_collision {
+                delta_rotate -= BOAT_ROTATION_SPEED * time.delta_seconds();
+            }
+            if front_right_collision || rear_left_collision {
+                delta_rotate += BOAT_ROTATION_SPEED * time.delta_seconds();
+            }
+            ship_tf.rotate(Quat::from_rotation_z(delta_rotate));
+
+            collision |= front_collision
+                || rear_collision
+                || front_left_collision
+                || front_right_collision
+                || rear_left_collision
+                || rear_right_collision;
         }
-        if rear_collision || rear_left_collision || rear_right_collision {
-            speed.0 = speed.0.max(0.);
+
+        // Decrease life in case of 'new' collision.
+        if collision {
+            if collision_ready.0 {
+                life.0 -= life.0.min(10);
+                collision_ready.0 = false;
+            }
+        } else {
+            collision_ready.0 = true;
         }
-        let mut delta_rotate = 0.;
-        if front_left_collision || rear_right_collision {
-            delta_rotate -= BOAT_ROTATION_SPEED * time.delta_seconds();
+    }
+}
+
+fn ship_grounding(
+    time: Res<GameTime>,
+    tile_map: Res<TileMap>,
+    mut game_rng: ResMut<GameRng>,
+    mut query_ship: Query<(&Transform, &Draft, &ShipClass, &mut Speed, &mut Life), With<Ship>>,
+) {
+    for (ship_tf, draft, class, mut speed, mut life) in query_ship.iter_mut() {
+        if tile_map.depth(tile_of(ship_tf.translation)) >= draft.0 {
+            continue;
         }
-        if front_right_collision || rear_left_collision {
-            delta_rotate += BOAT_ROTATION_SPEED * time.delta_seconds();
+        // Touching the bottom slows the boat down and may damage its hull.
+        let throttle = speed.0.abs() / class.spec().max_speed;
+        if game_rng.rng.gen::<f32>() < GROUNDING_RATE * throttle * time.delta_seconds() {
+            life.0 -= life.0.min(GROUNDING_DAMAGE);
         }
-        player_tf.rotate(Quat::from_rotation_z(delta_rotate));
-
-        collision |= front_collision
-            || rear_collision
-            || front_left_collision
-            || front_right_collision
-            || rear_left_collision
-            || rear_right_collision;
+        speed.0 -= GROUNDING_DRAG * speed.0 * time.delta_seconds();
     }
+}
 
-    // Decrease life in case of 'new' collision.
-    if collision {
-        if collision_ready.0 {
-            life.0 -= life.0.min(10);
-            collision_ready.0 = false;
+fn ship_sinking(
+    mut commands: Commands,
+    game_mode: Res<GameMode>,
+    mut explosions: EventWriter<ExplosionEvent>,
+    mut ship_sunk: EventWriter<ShipSunk>,
+    query_ship: Query<(Entity, &Transform, &Life, &Team, Option<&Player>), With<Ship>>,
+) {
+    // In free-for-all, a boat out of life keeps floating.
+    if *game_mode == GameMode::FreeForAll {
+        return;
+    }
+    for (ship_entity, ship_tf, life, team, player) in query_ship.iter() {
+        if life.0 > 0 {
+            continue;
         }
-    } else {
-        collision_ready.0 = true;
+        commands.entity(ship_entity).despawn_recursive();
+        explosions.send(ExplosionEvent {
+            position: ship_tf.translation,
+            kind: ExplosionKind::Magazine,
+            radius: MAGAZINE_BLAST_RADIUS,
+            source: Some(ship_entity),
+        });
+        ship_sunk.send(ShipSunk {
+            team: *team,
+            player: player.is_some(),
+        });
     }
 }
 
@@ -495,12 +906,24 @@ pub struct PlayerPlugin;
 
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
-        app.add_startup_stage("game_setup_actors", SystemStage::single(player_spawn))
-            .add_system(player_movement)
-            .add_system(canon_movement)
-            .add_system(canon_fire)
-            .add_system(torpedo_fire)
-            .add_system(player_ground_collision)
-            .add_system(torpedo_sight_movement);
+        app.init_resource::<PlayerClass>()
+            .add_event::<ShipSunk>()
+            .add_startup_stage("game_setup_actors", SystemStage::single(player_spawn))
+            .add_system_set(
+                SystemSet::new()
+                    .label(GameSystem::Ships)
+                    .after(GameSystem::Weather)
+                    .with_system(player_movement)
+                    .with_system(canon_movement.after(player_movement))
+                    .with_system(canon_fire.after(canon_movement))
+                    .with_system(torpedo_tube_setting.after(canon_fire))
+                    .with_system(torpedo_fire.after(torpedo_tube_setting))
+                    .with_system(mine_drop.after(torpedo_fire))
+                    .with_system(ship_steering.after(mine_drop))
+                    .with_system(ship_ground_collision.after(ship_steering))
+                    .with_system(ship_grounding.after(ship_ground_collision))
+                    .with_system(ship_sinking.after(ship_grounding))
+                    .with_system(torpedo_sight_movement.after(ship_sinking)),
+            );
     }
 }
@@ -0,0 +1,205 @@
+use bevy::prelude::*;
+
+use crate::common::*;
+use crate::explosion::{ExplosionEvent, ExplosionKind};
+use crate::player::{Amunitions, Life, Mines, Speed, Torpedos, AMUNITIONS, LIFE, MINES, TORPEDOS};
+
+// Distance under which a boat is alongside a port.
+const PORT_RANGE: f32 = 40.;
+// Speed under which a boat is considered stopped.
+const DOCKED_MAX_SPEED: f32 = 0.1;
+const RESUPPLY_PERIOD: f32 = 1.;
+const RESUPPLY_LIFE: u32 = 5;
+const RESUPPLY_AMUNITIONS: u32 = 2;
+const RESUPPLY_TORPEDOS: u32 = 1;
+const RESUPPLY_MINES: u32 = 1;
+const BUNKER_LIFE: u32 = 60;
+
+//
+// Misc functions
+//
+
+/// Overall stock of a boat, from 0 (empty) to 1 (fully resupplied).
+pub fn stock_level(life: u32, amunitions: u32, torpedos: u32, mines: u32) -> f32 {
+    (life as f32 / LIFE as f32
+        + amunitions as f32 / AMUNITIONS as f32
+        + torpedos as f32 / TORPEDOS as f32
+        + mines as f32 / MINES as f32)
+        / 4.
+}
+
+/// Whether a boat is stopped alongside the given port.
+pub fn alongside(port: Vec3, boat: Vec3, speed: f32) -> bool {
+    speed.abs() < DOCKED_MAX_SPEED && distance(port, boat) < PORT_RANGE
+}
+
+/// Spawn a port on the given ground tile, open to all boats or only to those of a team.
+pub fn port_spawn(
+    commands: &mut Commands,
+    sprite_materials: &SpriteMaterials,
+    tile: (i32, i32),
+    team: Option<Team>,
+) -> Entity {
+    // Ports of a team are fortified.
+    let index = match team {
+        None => sprite_materials.blue_flag_index,
+        Some(_) => sprite_materials.bunker_index,
+    };
+    let mut port = commands.spawn_bundle(SpriteSheetBundle {
+        texture_atlas: sprite_materials.texture.clone(),
+        sprite: TextureAtlasSprite {
+            index,
+            color: team.map_or(Color::WHITE, |team| team.color()),
+            ..Default::default()
+        },
+        transform: Transform::from_xyz(16. * tile.0 as f32, 16. * tile.1 as f32, MOUNTAIN_Z),
+        ..Default::default()
+    });
+    port.insert(Port);
+    if let Some(team) = team {
+        port.insert(team);
+    }
+    port.id()
+}
+
+/// Spawn a bunker of the given team on a ground tile, destroyed once out of life.
+pub fn bunker_spawn(
+    commands: &mut Commands,
+    sprite_materials: &SpriteMaterials,
+    tile: (i32, i32),
+    team: Team,
+) -> Entity {
+    commands
+        .spawn_bundle(SpriteSheetBundle {
+            texture_atlas: sprite_materials.texture.clone(),
+            sprite: TextureAtlasSprite {
+                index: sprite_materials.bunker_index,
+                color: team.color(),
+                custom_size: Some(Vec2::splat(16.)),
+                ..Default::default()
+            },
+            transform: Transform::from_xyz(16. * tile.0 as f32, 16. * tile.1 as f32, MOUNTAIN_Z),
+            ..Default::default()
+        })
+        .insert(Bunker)
+        .insert(team)
+        .insert(Life(BUNKER_LIFE))
+        .id()
+}
+
+//
+// Components
+//
+
+#[derive(Component)]
+pub struct Port;
+
+/// Fortified position, hit by canonballs like a hull.
+#[derive(Component)]
+pub struct Bunker;
+
+/// A boat stopped alongside a port, resupplied at each tick of the timer.
+#[derive(Component)]
+pub struct Docked(pub Timer);
+
+//
+// Events
+//
+
+/// A bunker lost all its life and was destroyed.
+pub struct BunkerDestroyed {
+    pub team: Team,
+}
+
+//
+// Systems
+//
+
+fn port_docking(
+    mut commands: Commands,
+    query_port: Query<(&Transform, Option<&Team>), With<Port>>,
+    query_boat: Query<(Entity, &Transform, &Speed, &Team, Option<&Docked>), Without<Port>>,
+) {
+    for (boat_entity, boat_tf, speed, boat_team, docked) in query_boat.iter() {
+        // Boats are only resupplied at neutral or friendly ports.
+        let alongside = query_port.iter().any(|(port_tf, port_team)| {
+            (port_team.is_none() || port_team == Some(boat_team))
+                && alongside(port_tf.translation, boat_tf.translation, speed.0)
+        });
+        if alongside && docked.is_none() {
+            commands
+                .entity(boat_entity)
+                .insert(Docked(Timer::from_seconds(RESUPPLY_PERIOD, true)));
+        } else if !alongside && docked.is_some() {
+            commands.entity(boat_entity).remove::<Docked>();
+        }
+    }
+}
+
+fn port_resupply(
+    time: Res<GameTime>,
+    mut query_boat: Query<(&mut Docked, &mut Life, &Children)>,
+    mut query_amunitions: Query<&mut Amunitions>,
+    mut query_torpedos: Query<&mut Torpedos>,
+    mut query_mines: Query<&mut Mines>,
+) {
+    for (mut docked, mut life, children) in query_boat.iter_mut() {
+        docked.0.tick(time.delta());
+        if !docked.0.just_finished() {
+            continue;
+        }
+        // Slowly repair the boat and restock its weapons.
+        life.0 = LIFE.min(life.0 + RESUPPLY_LIFE);
+        for child in children.iter() {
+            if let Ok(mut amunitions) = query_amunitions.get_mut(*child) {
+                amunitions.0 = AMUNITIONS.min(amunitions.0 + RESUPPLY_AMUNITIONS);
+            }
+            if let Ok(mut torpedos) = query_torpedos.get_mut(*child) {
+                torpedos.0 = TORPEDOS.min(torpedos.0 + RESUPPLY_TORPEDOS);
+            }
+            if let Ok(mut mines) = query_mines.get_mut(*child) {
+                mines.0 = MINES.min(mines.0 + RESUPPLY_MINES);
+            }
+        }
+    }
+}
+
+fn bunker_destruction(
+    mut commands: Commands,
+    mut explosions: EventWriter<ExplosionEvent>,
+    mut bunker_destroyed: EventWriter<BunkerDestroyed>,
+    query_bunker: Query<(Entity, &Transform, &Life, &Team), With<Bunker>>,
+) {
+    for (bunker_entity, bunker_tf, life, team) in query_bunker.iter() {
+        if life.0 > 0 {
+            continue;
+        }
+        commands.entity(bunker_entity).despawn();
+        explosions.send(ExplosionEvent {
+            position: bunker_tf.translation,
+            kind: ExplosionKind::Magazine,
+            radius: 0.,
+            source: Some(bunker_entity),
+        });
+        bunker_destroyed.send(BunkerDestroyed { team: *team });
+    }
+}
+
+//
+// Plugin
+//
+
+pub struct PortPlugin;
+
+impl Plugin for PortPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<BunkerDestroyed>().add_system_set(
+            SystemSet::new()
+                .label(GameSystem::Ports)
+                .after(GameSystem::Mines)
+                .with_system(port_docking)
+                .with_system(port_resupply.after(port_docking))
+                .with_system(bunker_destruction.after(port_resupply)),
+        );
+    }
+}
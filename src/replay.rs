@@ -0,0 +1,572 @@
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+
+use bevy::app::AppExit;
+use bevy::ecs::schedule::{ShouldRun, SingleThreadedExecutor};
+use bevy::input::InputSystem;
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::canonball::CanonBall;
+use crate::common::*;
+use crate::mine::Mine;
+use crate::mission::{MissionPlugin, MissionSource};
+use crate::player::{Life, PlayerClass, Ship, ShipClass, Speed};
+use crate::torpedo::{DudProbability, Torpedo};
+
+// Duration, in seconds, of a tick of the game when recording or replaying.
+const TICK: f32 = 1. / 60.;
+// Ticks between two points a replay rewinds to.
+const REWIND_PERIOD: u32 = 300;
+// Ticks played per frame at each replay speed.
+const REPLAY_SPEEDS: [u32; 4] = [1, 2, 4, 8];
+const REPLAY_MAGIC: &[u8; 4] = b"NVRP";
+const REPLAY_VERSION: u8 = 5;
+// Keys controlling the boat of the player, recorded at each tick.
+const CONTROL_KEYS: [KeyCode; 18] = [
+    KeyCode::A,
+    KeyCode::D,
+    KeyCode::W,
+    KeyCode::S,
+    KeyCode::J,
+    KeyCode::L,
+    KeyCode::I,
+    KeyCode::K,
+    KeyCode::U,
+    KeyCode::O,
+    KeyCode::T,
+    KeyCode::H,
+    KeyCode::G,
+    KeyCode::Space,
+    KeyCode::Return,
+    KeyCode::B,
+    KeyCode::X,
+    KeyCode::Z,
+];
+// Offset basis and prime of the FNV-1a hash.
+const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+//
+// Misc functions
+//
+
+/// Value given on the command line after the given option, e.g. `naval --replay game.nvr`.
+fn arg_value(option: &str) -> Option<String> {
+    let args: Vec<String> = std::env::args().collect();
+    let i = args.iter().position(|arg| arg == option)?;
+    args.get(i + 1).cloned()
+}
+
+/// Control keys held down, one bit per key.
+fn controls(input: &Input<KeyCode>) -> u32 {
+    CONTROL_KEYS
+        .iter()
+        .enumerate()
+        .filter(|(_, key)| input.pressed(**key) || input.just_pressed(**key))
+        .fold(0, |bits, (i, _)| bits | 1 << i)
+}
+
+/// Hold down the given control keys, and only them.
+fn apply_controls(input: &mut Input<KeyCode>, bits: u32) {
+    input.clear();
+    for (i, key) in CONTROL_KEYS.iter().enumerate() {
+        if bits & 1 << i == 0 {
+            input.reset(*key);
+        } else if !input.pressed(*key) {
+            input.press(*key);
+        }
+    }
+}
+
+fn fnv1a(hash: u64, value: u32) -> u64 {
+    value.to_le_bytes().iter().fold(hash, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(FNV_PRIME)
+    })
+}
+
+/// Fingerprint of the state of the game, whatever the order of the entities.
+fn checksum<'a>(
+    game_rng: &GameRng,
+    ships: impl Iterator<Item = (&'a Transform, &'a Speed, &'a Life)>,
+    projectiles: impl Iterator<Item = &'a Transform>,
+) -> u64 {
+    // The next random number tells how far the game went through its randomness.
+    let mut sum = fnv1a(FNV_OFFSET, game_rng.rng.clone().gen());
+    for (ship_tf, speed, life) in ships {
+        let hash = [
+            ship_tf.translation.x,
+            ship_tf.translation.y,
+            ship_tf.rotation.z,
+            ship_tf.rotation.w,
+            speed.0,
+        ]
+        .iter()
+        .fold(FNV_OFFSET, |hash, value| fnv1a(hash, value.to_bits()));
+        sum = sum.wrapping_add(fnv1a(hash, life.0));
+    }
+    for projectile_tf in projectiles {
+        let hash = fnv1a(FNV_OFFSET, projectile_tf.translation.x.to_bits());
+        sum = sum.wrapping_add(fnv1a(hash, projectile_tf.translation.y.to_bits()));
+    }
+    sum
+}
+
+fn read_u8(reader: &mut impl Read) -> io::Result<u8> {
+    let mut bytes = [0; 1];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes[0])
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64(reader: &mut impl Read) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+/// Canonballs, torpedos and mines at sea.
+type Projectile = Or<(With<CanonBall>, With<Torpedo>, With<Mine>)>;
+
+//
+// Resources
+//
+
+/// Game recorded as the control keys of the player at each tick.
+struct Replay {
+    seed: u64,
+    mode: GameMode,
+    friendly_fire: FriendlyFire,
+    player_class: PlayerClass,
+    // Text of the mission played, if any.
+    mission: Option<MissionSource>,
+    dud_probability: DudProbability,
+    // Number of ticks played.
+    ticks: u32,
+    // Checksum of the game after the last tick.
+    checksum: u64,
+    // Ticks at which the control keys changed, with the keys held down from then on.
+    controls: Vec<(u32, u32)>,
+}
+
+impl Replay {
+    fn new(
+        seed: u64,
+        mode: GameMode,
+        friendly_fire: FriendlyFire,
+        player_class: PlayerClass,
+        mission: Option<MissionSource>,
+    ) -> Self {
+        Replay {
+            seed,
+            mode,
+            friendly_fire,
+            player_class,
+            mission,
+            dud_probability: DudProbability::default(),
+            ticks: 0,
+            checksum: 0,
+            controls: Vec::new(),
+        }
+    }
+
+    /// Control keys held down during the given tick.
+    fn controls(&self, tick: u32) -> u32 {
+        match self.controls.partition_point(|(start, _)| *start <= tick) {
+            0 => 0,
+            i => self.controls[i - 1].1,
+        }
+    }
+
+    fn record(&mut self, tick: u32, bits: u32) {
+        if self.controls.last().map(|(_, last)| *last) != Some(bits) {
+            self.controls.push((tick, bits));
+        }
+    }
+
+    fn read(path: &str) -> io::Result<Replay> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != REPLAY_MAGIC || read_u8(&mut reader)? != REPLAY_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a replay of this version of the game",
+            ));
+        }
+        let seed = read_u64(&mut reader)?;
+        let mode = match read_u8(&mut reader)? {
+            0 => GameMode::FreeForAll,
+            1 => GameMode::CaptureTheFlag,
+            3 => GameMode::TeamBattle,
+            _ => GameMode::Mission,
+        };
+        let friendly_fire = FriendlyFire(read_u8(&mut reader)? != 0);
+        let player_class = PlayerClass(match read_u8(&mut reader)? {
+            0 => ShipClass::Patrol,
+            2 => ShipClass::Cruiser,
+            _ => ShipClass::Frigate,
+        });
+        let mut mission = vec![0; read_u32(&mut reader)? as usize];
+        reader.read_exact(&mut mission)?;
+        let mission = if mission.is_empty() {
+            None
+        } else {
+            let text = String::from_utf8(mission)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+            Some(MissionSource(text))
+        };
+        let dud_probability = match read_u8(&mut reader)? {
+            0 => None,
+            _ => Some(f32::from_bits(read_u32(&mut reader)?)),
+        };
+        let ticks = read_u32(&mut reader)?;
+        let checksum = read_u64(&mut reader)?;
+        let mut controls = Vec::new();
+        for _ in 0..read_u32(&mut reader)? {
+            controls.push((read_u32(&mut reader)?, read_u32(&mut reader)?));
+        }
+        Ok(Replay {
+            seed,
+            mode,
+            friendly_fire,
+            player_class,
+            mission,
+            dud_probability: DudProbability(dud_probability),
+            ticks,
+            checksum,
+            controls,
+        })
+    }
+
+    fn write(&self, path: &str) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(REPLAY_MAGIC)?;
+        writer.write_all(&[REPLAY_VERSION])?;
+        writer.write_all(&self.seed.to_le_bytes())?;
+        let mode = match self.mode {
+            GameMode::FreeForAll => 0,
+            GameMode::CaptureTheFlag => 1,
+            GameMode::Mission => 2,
+            GameMode::TeamBattle => 3,
+        };
+        let player_class = match self.player_class.0 {
+            ShipClass::Patrol => 0,
+            ShipClass::Frigate => 1,
+            ShipClass::Cruiser => 2,
+        };
+        writer.write_all(&[mode, self.friendly_fire.0 as u8, player_class])?;
+        let mission = self
+            .mission
+            .as_ref()
+            .map_or("", |mission| mission.0.as_str());
+        writer.write_all(&(mission.len() as u32).to_le_bytes())?;
+        writer.write_all(mission.as_bytes())?;
+        match self.dud_probability.0 {
+            Some(probability) => {
+                writer.write_all(&[1])?;
+                writer.write_all(&probability.to_bits().to_le_bytes())?;
+            }
+            None => writer.write_all(&[0])?,
+        }
+        writer.write_all(&self.ticks.to_le_bytes())?;
+        writer.write_all(&self.checksum.to_le_bytes())?;
+        writer.write_all(&(self.controls.len() as u32).to_le_bytes())?;
+        for (tick, bits) in self.controls.iter() {
+            writer.write_all(&tick.to_le_bytes())?;
+            writer.write_all(&bits.to_le_bytes())?;
+        }
+        writer.flush()
+    }
+}
+
+/// Ticks played so far, and left to play this frame.
+#[derive(Default)]
+struct ReplayClock {
+    tick: u32,
+    budget: u32,
+}
+
+struct Recorder {
+    path: String,
+    replay: Replay,
+}
+
+struct Playback {
+    replay: Replay,
+    paused: bool,
+    // Index of the replay speed.
+    speed: usize,
+    // Whether the game ended as recorded, once at the end of the replay.
+    verified: Option<bool>,
+    // Tick played at once from the seed, when rewound to it.
+    start: u32,
+}
+
+//
+// Components
+//
+
+#[derive(Component)]
+struct ReplayOverlay;
+
+//
+// Systems
+//
+
+fn game_time_update(time: Res<Time>, mut game_time: ResMut<GameTime>) {
+    game_time.delta = time.delta();
+}
+
+/// Run the game systems once per tick, as long as there are ticks to play this frame.
+fn replay_tick(
+    mut clock: ResMut<ReplayClock>,
+    mut game_time: ResMut<GameTime>,
+    mut input: ResMut<Input<KeyCode>>,
+    recorder: Option<ResMut<Recorder>>,
+    playback: Option<Res<Playback>>,
+) -> ShouldRun {
+    if clock.budget == 0 {
+        return ShouldRun::No;
+    }
+    clock.budget -= 1;
+    game_time.delta = std::time::Duration::from_secs_f32(TICK);
+    // Record the keys of the player, or play them back.
+    if let Some(mut recorder) = recorder {
+        recorder.replay.record(clock.tick, controls(&input));
+    }
+    if let Some(playback) = playback {
+        apply_controls(&mut input, playback.replay.controls(clock.tick));
+    }
+    clock.tick += 1;
+    ShouldRun::YesAndCheckAgain
+}
+
+fn record_frame(mut clock: ResMut<ReplayClock>) {
+    clock.budget = 1;
+}
+
+fn record_save(
+    mut exit: EventReader<AppExit>,
+    clock: Res<ReplayClock>,
+    game_rng: Res<GameRng>,
+    dud_probability: Res<DudProbability>,
+    mut recorder: ResMut<Recorder>,
+    query_ship: Query<(&Transform, &Speed, &Life), With<Ship>>,
+    query_projectile: Query<&Transform, Projectile>,
+) {
+    if exit.iter().next().is_none() {
+        return;
+    }
+    recorder.replay.dud_probability = *dud_probability;
+    recorder.replay.ticks = clock.tick;
+    recorder.replay.checksum = checksum(&game_rng, query_ship.iter(), query_projectile.iter());
+    if let Err(err) = recorder.replay.write(&recorder.path) {
+        error!("Cannot save the replay to {}: {}", recorder.path, err);
+    }
+}
+
+fn replay_controls(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut clock: ResMut<ReplayClock>,
+    mut playback: ResMut<Playback>,
+) {
+    // Fast-forward to the tick rewound to, and wait there.
+    if clock.tick < playback.start {
+        clock.budget = playback.start - clock.tick;
+        playback.paused = true;
+        return;
+    }
+    if keyboard_input.just_pressed(KeyCode::P) {
+        playback.paused = !playback.paused;
+    }
+    if keyboard_input.just_pressed(KeyCode::F) {
+        playback.speed = (playback.speed + 1) % REPLAY_SPEEDS.len();
+    }
+    // Paused, the replay goes one tick at a time.
+    let ticks = if !playback.paused {
+        REPLAY_SPEEDS[playback.speed]
+    } else if keyboard_input.just_pressed(KeyCode::N) {
+        1
+    } else {
+        0
+    };
+    clock.budget = ticks.min(playback.replay.ticks.saturating_sub(clock.tick));
+}
+
+/// Go back a few seconds before the current tick: the game can only be played again from its
+/// seed, so the replay starts again in a new window and fast-forwards to that tick.
+fn replay_rewind(
+    keyboard_input: Res<Input<KeyCode>>,
+    clock: Res<ReplayClock>,
+    mut exit: EventWriter<AppExit>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::R) {
+        return;
+    }
+    let start = clock.tick.saturating_sub(1) / REWIND_PERIOD * REWIND_PERIOD;
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(i) = args.iter().position(|arg| arg == "--replay-from") {
+        args.drain(i..(i + 2).min(args.len()));
+    }
+    args.extend(["--replay-from".to_string(), start.to_string()]);
+    let relaunch =
+        std::env::current_exe().and_then(|exe| std::process::Command::new(exe).args(args).spawn());
+    match relaunch {
+        Ok(_) => exit.send(AppExit),
+        Err(err) => error!("Cannot rewind the replay: {}", err),
+    }
+}
+
+fn replay_verify(
+    clock: Res<ReplayClock>,
+    game_rng: Res<GameRng>,
+    mut playback: ResMut<Playback>,
+    query_ship: Query<(&Transform, &Speed, &Life), With<Ship>>,
+    query_projectile: Query<&Transform, Projectile>,
+) {
+    if playback.verified.is_some() || clock.tick < playback.replay.ticks {
+        return;
+    }
+    let checksum = checksum(&game_rng, query_ship.iter(), query_projectile.iter());
+    playback.verified = Some(checksum == playback.replay.checksum);
+    if checksum != playback.replay.checksum {
+        warn!(
+            "Replay diverged: checksum {:016x} instead of {:016x}",
+            checksum, playback.replay.checksum
+        );
+    }
+}
+
+fn replay_overlay_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    bottom: Val::Px(5.0),
+                    left: Val::Px(15.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("FiraSans-Bold.ttf"),
+                    font_size: 15.0,
+                    color: Color::BLACK,
+                },
+                Default::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(ReplayOverlay);
+}
+
+fn replay_overlay(
+    clock: Res<ReplayClock>,
+    playback: Res<Playback>,
+    mut query: Query<&mut Text, With<ReplayOverlay>>,
+) {
+    let state = if playback.paused {
+        String::from("paused (N: step)")
+    } else {
+        format!("x{}", REPLAY_SPEEDS[playback.speed])
+    };
+    let verified = match playback.verified {
+        None => "",
+        Some(true) => " - checksum OK",
+        Some(false) => " - checksum MISMATCH",
+    };
+    for mut text in query.iter_mut() {
+        text.sections[0].value = format!(
+            "Replay {:.1}/{:.1} s {} (P: pause, F: speed, R: rewind){}",
+            clock.tick as f32 * TICK,
+            playback.replay.ticks as f32 * TICK,
+            state,
+            verified
+        );
+    }
+}
+
+//
+// Plugin
+//
+
+/// Record the game with `--record <file>`, or play it back with `--replay <file>`.
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GameTime>();
+        let replay = arg_value("--replay");
+        let record = arg_value("--record");
+        if replay.is_none() && record.is_none() {
+            app.add_system_to_stage(CoreStage::PreUpdate, game_time_update);
+            return;
+        }
+        // The game systems run tick by tick, always in the same order, so that a game
+        // plays the same way from the same seed and keys, however many ticks a frame plays.
+        app.insert_resource(ReplayClock::default()).stage(
+            CoreStage::Update,
+            |stage: &mut SystemStage| {
+                stage.set_executor(Box::new(SingleThreadedExecutor));
+                stage.set_run_criteria(replay_tick)
+            },
+        );
+        if let Some(path) = replay {
+            let replay = Replay::read(&path)
+                .unwrap_or_else(|err| panic!("Cannot read the replay {}: {}", path, err));
+            // The recorded game replaces the one given on the command line, and the mission
+            // plugin, built before, sets up the recorded mission.
+            if let Some(mission) = replay.mission.clone() {
+                app.insert_resource(replay.mode)
+                    .insert_resource(mission)
+                    .add_plugin(MissionPlugin);
+            }
+            let start = arg_value("--replay-from")
+                .and_then(|tick| tick.parse().ok())
+                .unwrap_or(0)
+                .min(replay.ticks);
+            app.insert_resource(replay.mode)
+                .insert_resource(replay.friendly_fire)
+                .insert_resource(replay.player_class)
+                .insert_resource(replay.dud_probability)
+                .insert_resource(GameRng::new(replay.seed))
+                .insert_resource(Playback {
+                    replay,
+                    paused: false,
+                    speed: 0,
+                    verified: None,
+                    start,
+                })
+                .add_startup_system(replay_overlay_setup)
+                .add_system_to_stage(CoreStage::PreUpdate, replay_controls.after(InputSystem))
+                .add_system_to_stage(CoreStage::PreUpdate, replay_rewind.after(InputSystem))
+                .add_system_to_stage(CoreStage::Last, replay_verify)
+                .add_system_to_stage(CoreStage::Last, replay_overlay);
+        } else if let Some(path) = record {
+            let seed = app.world.get_resource::<GameRng>().unwrap().seed;
+            let mode = *app.world.get_resource::<GameMode>().unwrap();
+            let friendly_fire = *app.world.get_resource::<FriendlyFire>().unwrap();
+            let player_class = app
+                .world
+                .get_resource::<PlayerClass>()
+                .copied()
+                .unwrap_or_default();
+            let mission = app.world.get_resource::<MissionSource>().cloned();
+            app.insert_resource(Recorder {
+                path,
+                replay: Replay::new(seed, mode, friendly_fire, player_class, mission),
+            })
+            .add_system_to_stage(CoreStage::PreUpdate, record_frame)
+            .add_system_to_stage(CoreStage::Last, record_save);
+        }
+    }
+}
@@ -0,0 +1,727 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::str::FromStr;
+use std::time::Duration;
+
+use bevy::ecs::system::{CommandQueue, SystemState};
+use bevy::prelude::*;
+
+use crate::canonball::{canonball_spawn, Altitude, CanonBall, Energy, FiredBy, Flight};
+use crate::common::*;
+use crate::island::{
+    ground_spawn, mountain_spawn, Ground, Height, Mountain, TerrainHealth, TerrainReset, Tile,
+    TileMap,
+};
+use crate::mine::{mine_spawn, LaidBy, Mine};
+use crate::player::{
+    ship_spawn, Amunitions, CanonSight, Life, Mines, Player, Ship, ShipClass, Speed, TorpedoSight,
+    Torpedos,
+};
+use crate::port::Port;
+use crate::torpedo::{torpedo_spawn, Launcher, Torpedo, TorpedoKind, TorpedoTube, Travelled, Wire};
+
+const SAVE_FILE: &str = "naval.sav";
+const SAVE_HEADER: &str = "naval-save";
+// Version of the format written, older ones are migrated when loaded.
+const SAVE_VERSION: u32 = 2;
+// Changes to apply to the records of a file of each version to bring it to the next one.
+const MIGRATIONS: [fn(&mut Record); SAVE_VERSION as usize - 1] = [ship_class_migration];
+// Time the notice that the game cannot be saved stays on screen.
+const NOTICE_DURATION: f32 = 3.;
+
+//
+// Misc functions
+//
+
+/// Angle, from the x axis, of the given rotation around the z axis.
+fn angle_of(rotation: Quat) -> f32 {
+    let heading = rotation.mul_vec3(Vec3::X);
+    heading.y.atan2(heading.x)
+}
+
+fn torpedo_kind_name(kind: TorpedoKind) -> &'static str {
+    match kind {
+        TorpedoKind::Straight => "straight",
+        TorpedoKind::Homing => "homing",
+        TorpedoKind::WireGuided => "wire-guided",
+    }
+}
+
+fn torpedo_kind(name: &str) -> TorpedoKind {
+    match name {
+        "homing" => TorpedoKind::Homing,
+        "wire-guided" => TorpedoKind::WireGuided,
+        _ => TorpedoKind::Straight,
+    }
+}
+
+/// Boats of version 1 were all frigates.
+fn ship_class_migration(record: &mut Record) {
+    if record.kind == "ship" {
+        record
+            .fields
+            .push((String::from("class"), ShipClass::Frigate.name().to_string()));
+    }
+}
+
+/// Read the records of a saved game, migrated to the current version.
+fn read_save(path: &str) -> Result<Vec<Record>, String> {
+    let text = fs::read_to_string(path).map_err(|err| err.to_string())?;
+    let mut lines = text.lines();
+    let header = lines.next().and_then(Record::parse);
+    let version = match header {
+        Some(header) if header.kind == SAVE_HEADER => header.get("version").unwrap_or(0),
+        _ => return Err(String::from("not a saved game")),
+    };
+    if version == 0 || version > SAVE_VERSION {
+        return Err(format!("unsupported version {}", version));
+    }
+    let mut records: Vec<Record> = lines.filter_map(Record::parse).collect();
+    for migration in MIGRATIONS.iter().skip(version as usize - 1) {
+        records.iter_mut().for_each(migration);
+    }
+    Ok(records)
+}
+
+/// Line of a saved game: the kind of thing saved, followed by its `key=value` fields.
+struct Record {
+    kind: String,
+    fields: Vec<(String, String)>,
+}
+
+impl Record {
+    fn new(kind: &str) -> Self {
+        Record {
+            kind: kind.to_string(),
+            fields: Vec::new(),
+        }
+    }
+
+    fn with(mut self, key: &str, value: impl ToString) -> Self {
+        self.fields.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    /// Save a timer as its duration and the time elapsed.
+    fn with_timer(self, key: &str, timer: &Timer) -> Self {
+        self.with(key, timer.elapsed_secs())
+            .with(&format!("{}_duration", key), timer.duration().as_secs_f32())
+    }
+
+    fn parse(line: &str) -> Option<Record> {
+        let mut words = line.split_whitespace();
+        let kind = words.next()?.to_string();
+        let fields = words
+            .filter_map(|word| word.split_once('='))
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        Some(Record { kind, fields })
+    }
+
+    fn get<T: FromStr>(&self, key: &str) -> Option<T> {
+        self.fields
+            .iter()
+            .find(|(k, _)| k == key)
+            .and_then(|(_, value)| value.parse().ok())
+    }
+
+    /// Value of a field, or the given default if missing from the file.
+    fn get_or<T: FromStr>(&self, key: &str, default: T) -> T {
+        self.get(key).unwrap_or(default)
+    }
+
+    fn position(&self) -> Vec2 {
+        Vec2::new(self.get_or("x", 0.), self.get_or("y", 0.))
+    }
+
+    fn tile(&self) -> (i32, i32) {
+        (self.get_or("x", 0), self.get_or("y", 0))
+    }
+
+    fn rotation(&self, key: &str) -> Quat {
+        Quat::from_rotation_z(self.get_or(key, 0.))
+    }
+
+    fn timer(&self, key: &str) -> Timer {
+        let duration = self.get_or(&format!("{}_duration", key), 0.);
+        let mut timer = Timer::from_seconds(duration, false);
+        timer.tick(Duration::from_secs_f32(self.get_or(key, duration)));
+        timer
+    }
+}
+
+impl fmt::Display for Record {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.kind)?;
+        for (key, value) in self.fields.iter() {
+            write!(f, " {}={}", key, value)?;
+        }
+        Ok(())
+    }
+}
+
+//
+// Components
+//
+
+/// Notice shown on F5 and F9 in the modes where games are not saved.
+#[derive(Component)]
+struct SaveNotice(Timer);
+
+//
+// Systems
+//
+
+fn game_save(world: &mut World) {
+    let input = world.get_resource::<Input<KeyCode>>().unwrap();
+    if !input.just_pressed(KeyCode::F5) {
+        return;
+    }
+    let mut records = vec![Record::new(SAVE_HEADER).with("version", SAVE_VERSION)];
+
+    // Terrain: the land, as well as the shallow waters and reefs.
+    let tile_map = world.get_resource::<TileMap>().unwrap();
+    records.push(
+        Record::new("map")
+            .with("w_tiles", tile_map.w_tiles)
+            .with("h_tiles", tile_map.h_tiles),
+    );
+    let mut waters: Vec<((i32, i32), &str, f32)> = tile_map
+        .tiles
+        .iter()
+        .filter_map(|(tile, kind)| match kind {
+            Tile::Shallow => Some((*tile, "shallow", tile_map.depth(*tile))),
+            Tile::Reef => Some((*tile, "reef", tile_map.depth(*tile))),
+            _ => None,
+        })
+        .collect();
+    waters.sort_unstable_by_key(|(tile, _, _)| *tile);
+    for (tile, kind, depth) in waters {
+        records.push(
+            Record::new(kind)
+                .with("x", tile.0)
+                .with("y", tile.1)
+                .with("depth", depth),
+        );
+    }
+    let grounds: Vec<Record> = world
+        .query_filtered::<(&Transform, &TerrainHealth), With<Ground>>()
+        .iter(world)
+        .map(|(ground_tf, health)| {
+            let tile = tile_of(ground_tf.translation);
+            Record::new("ground")
+                .with("x", tile.0)
+                .with("y", tile.1)
+                .with("health", health.0)
+        })
+        .collect();
+    records.extend(grounds);
+    let mountains: Vec<Record> = world
+        .query_filtered::<(&Transform, &Height, &TerrainHealth), With<Mountain>>()
+        .iter(world)
+        .map(|(mountain_tf, height, health)| {
+            let tile = tile_of(mountain_tf.translation);
+            Record::new("mountain")
+                .with("x", tile.0)
+                .with("y", tile.1)
+                .with("height", height.0)
+                .with("health", health.0)
+        })
+        .collect();
+    records.extend(mountains);
+    let ports: Vec<Record> = world
+        .query_filtered::<&Transform, With<Port>>()
+        .iter(world)
+        .map(|port_tf| {
+            let tile = tile_of(port_tf.translation);
+            Record::new("port").with("x", tile.0).with("y", tile.1)
+        })
+        .collect();
+    records.extend(ports);
+
+    // Boats, with their weapons. Torpedos and mines refer to them by their rank in the file.
+    let ships: Vec<(
+        Entity,
+        Transform,
+        Team,
+        ShipClass,
+        f32,
+        u32,
+        bool,
+        Vec<Entity>,
+    )> = world
+        .query_filtered::<(
+            Entity,
+            &Transform,
+            &Team,
+            &ShipClass,
+            &Speed,
+            &Life,
+            Option<&Player>,
+            &Children,
+        ), With<Ship>>()
+        .iter(world)
+        .map(
+            |(entity, ship_tf, team, class, speed, life, player, children)| {
+                let children = children.iter().copied().collect();
+                (
+                    entity,
+                    *ship_tf,
+                    *team,
+                    *class,
+                    speed.0,
+                    life.0,
+                    player.is_some(),
+                    children,
+                )
+            },
+        )
+        .collect();
+    let rank: HashMap<Entity, usize> = ships
+        .iter()
+        .enumerate()
+        .map(|(i, ship)| (ship.0, i))
+        .collect();
+    for (_, ship_tf, team, class, speed, life, player, children) in ships {
+        let mut record = Record::new("ship")
+            .with("team", team.0)
+            .with("class", class.name())
+            .with("player", player)
+            .with("x", ship_tf.translation.x)
+            .with("y", ship_tf.translation.y)
+            .with("angle", angle_of(ship_tf.rotation))
+            .with("speed", speed)
+            .with("life", life);
+        for child in children {
+            let child_tf = world.get::<Transform>(child).unwrap();
+            let timer = match world.get::<Timer>(child) {
+                Some(timer) => timer,
+                None => continue,
+            };
+            if let Some(sight) = world.get::<CanonSight>(child) {
+                let amunitions = world.get::<Amunitions>(child).unwrap();
+                record = record
+                    .with("canon_x", child_tf.translation.x)
+                    .with("canon_y", child_tf.translation.y)
+                    .with("canon_angle", angle_of(child_tf.rotation))
+                    .with("canon_range", sight.0)
+                    .with("amunitions", amunitions.0)
+                    .with_timer("canon_reload", timer);
+            } else if world.get::<TorpedoSight>(child).is_some() {
+                let tube = world.get::<TorpedoTube>(child).unwrap();
+                let torpedos = world.get::<Torpedos>(child).unwrap();
+                record = record
+                    .with("torpedo_x", child_tf.translation.x)
+                    .with("torpedo_y", child_tf.translation.y)
+                    .with("torpedo_angle", angle_of(child_tf.rotation))
+                    .with("tube", torpedo_kind_name(tube.kind))
+                    .with("spread_count", tube.spread_count)
+                    .with("spread_fan", tube.spread_fan)
+                    .with("torpedos", torpedos.0)
+                    .with_timer("torpedo_reload", timer);
+            } else if let Some(mines) = world.get::<Mines>(child) {
+                record = record
+                    .with("mines", mines.0)
+                    .with_timer("mine_reload", timer);
+            }
+        }
+        records.push(record);
+    }
+
+    // Projectiles at sea.
+    let canonballs: Vec<Record> = world
+        .query_filtered::<(
+            &Transform,
+            &Energy,
+            &Flight,
+            &Altitude,
+            Option<&FiredBy>,
+        ), With<CanonBall>>()
+        .iter(world)
+        .map(|(canonball_tf, energy, flight, altitude, fired_by)| {
+            let record = Record::new("canonball")
+                .with("x", canonball_tf.translation.x)
+                .with("y", canonball_tf.translation.y)
+                .with("angle", angle_of(canonball_tf.rotation))
+                .with("energy", energy.0)
+                .with("range", flight.range)
+                .with("altitude", altitude.0);
+            // The boat which fired it, if still afloat.
+            match fired_by.and_then(|fired_by| rank.get(&fired_by.0)) {
+                Some(launcher) => record.with("launcher", launcher),
+                None => record,
+            }
+        })
+        .collect();
+    records.extend(canonballs);
+    let torpedos: Vec<Record> = world
+        .query_filtered::<(
+            &Transform,
+            &TorpedoKind,
+            &Launcher,
+            &Travelled,
+            Option<&Wire>,
+        ), With<Torpedo>>()
+        .iter(world)
+        .filter_map(|(torpedo_tf, kind, launcher, travelled, wire)| {
+            Some(
+                Record::new("torpedo")
+                    .with("x", torpedo_tf.translation.x)
+                    .with("y", torpedo_tf.translation.y)
+                    .with("angle", angle_of(torpedo_tf.rotation))
+                    .with("kind", torpedo_kind_name(*kind))
+                    .with("launcher", rank.get(&launcher.0)?)
+                    .with("travelled", travelled.0)
+                    .with("wire", wire.is_some()),
+            )
+        })
+        .collect();
+    records.extend(torpedos);
+    let mines: Vec<Record> = world
+        .query_filtered::<(&Transform, &LaidBy, &Timer), With<Mine>>()
+        .iter(world)
+        .filter_map(|(mine_tf, laid_by, timer)| {
+            Some(
+                Record::new("mine")
+                    .with("x", mine_tf.translation.x)
+                    .with("y", mine_tf.translation.y)
+                    .with("layer", rank.get(&laid_by.0)?)
+                    .with_timer("arming", timer),
+            )
+        })
+        .collect();
+    records.extend(mines);
+
+    let text: String = records
+        .iter()
+        .map(|record| format!("{}\n", record))
+        .collect();
+    if let Err(err) = fs::write(SAVE_FILE, text) {
+        error!("Cannot save the game to {}: {}", SAVE_FILE, err);
+    }
+}
+
+fn game_load(world: &mut World) {
+    let input = world.get_resource::<Input<KeyCode>>().unwrap();
+    if !input.just_pressed(KeyCode::F9) {
+        return;
+    }
+    let records = match read_save(SAVE_FILE) {
+        Ok(records) => records,
+        Err(err) => {
+            error!("Cannot load the game from {}: {}", SAVE_FILE, err);
+            return;
+        }
+    };
+
+    // Replace the terrain.
+    let mut tile_map = TileMap {
+        w_tiles: 0,
+        h_tiles: 0,
+        tiles: HashMap::new(),
+        depths: HashMap::new(),
+    };
+    for record in records.iter() {
+        match record.kind.as_str() {
+            "map" => {
+                tile_map.w_tiles = record.get_or("w_tiles", 0);
+                tile_map.h_tiles = record.get_or("h_tiles", 0);
+            }
+            "ground" => {
+                tile_map.tiles.entry(record.tile()).or_insert(Tile::Ground);
+            }
+            "mountain" => {
+                tile_map.tiles.insert(record.tile(), Tile::Mountain);
+            }
+            "shallow" | "reef" => {
+                let tile = if record.kind == "reef" {
+                    Tile::Reef
+                } else {
+                    Tile::Shallow
+                };
+                tile_map.tiles.insert(record.tile(), tile);
+                tile_map
+                    .depths
+                    .insert(record.tile(), record.get_or("depth", 0.));
+            }
+            _ => {}
+        }
+    }
+    let previous: Vec<Entity> = world
+        .query_filtered::<Entity, Or<(
+            With<Ship>,
+            With<Ground>,
+            With<Mountain>,
+            With<Port>,
+            With<CanonBall>,
+            With<Torpedo>,
+            With<Mine>,
+        )>>()
+        .iter(world)
+        .collect();
+    let mut queue = CommandQueue::default();
+    let mut commands = Commands::new(&mut queue, world);
+    let sprite_materials = world.get_resource::<SpriteMaterials>().unwrap();
+    for entity in previous {
+        commands.entity(entity).despawn_recursive();
+    }
+    let mut ships = Vec::new();
+    for record in records.iter() {
+        match record.kind.as_str() {
+            "ground" => {
+                ground_spawn(
+                    &mut commands,
+                    sprite_materials,
+                    record.tile(),
+                    |tile| tile_map.is_land(tile),
+                    record.get_or("health", 1),
+                );
+            }
+            "mountain" => {
+                mountain_spawn(
+                    &mut commands,
+                    sprite_materials,
+                    record.tile(),
+                    record.get_or("height", 0.),
+                    record.get_or("health", 1),
+                );
+            }
+            "port" => {
+                crate::port::port_spawn(&mut commands, sprite_materials, record.tile(), None);
+            }
+            "ship" => {
+                let ship = ship_spawn(
+                    &mut commands,
+                    sprite_materials,
+                    record.position().extend(BOAT_Z),
+                    record.get_or("angle", 0.),
+                    Team(record.get_or("team", 0)),
+                    ShipClass::from_name(&record.get_or("class", String::new()))
+                        .unwrap_or_default(),
+                );
+                commands
+                    .entity(ship)
+                    .insert(Speed(record.get_or("speed", 0.)))
+                    .insert(Life(record.get_or("life", 0)));
+                if record.get_or("player", false) {
+                    commands.entity(ship).insert(Player);
+                }
+                ships.push((ship, record));
+            }
+            _ => {}
+        }
+    }
+    // Torpedos and mines of boats no longer in the file are lost, canonballs fly on.
+    let ship_at = |rank: Option<usize>| rank.and_then(|rank| ships.get(rank)).map(|ship| ship.0);
+    for record in records.iter() {
+        match record.kind.as_str() {
+            "canonball" => {
+                let canonball = canonball_spawn(
+                    &mut commands,
+                    sprite_materials,
+                    record.position().extend(PROJECTILE_Z),
+                    record.rotation("angle"),
+                    record.get_or("range", 0.),
+                );
+                commands
+                    .entity(canonball)
+                    .insert(Energy(record.get_or("energy", 0.)))
+                    .insert(Altitude(record.get_or("altitude", 0.)));
+                if let Some(launcher) = ship_at(record.get("launcher")) {
+                    commands.entity(canonball).insert(FiredBy(launcher));
+                }
+            }
+            "torpedo" => {
+                if let Some(launcher) = ship_at(record.get("launcher")) {
+                    let torpedo = torpedo_spawn(
+                        &mut commands,
+                        sprite_materials,
+                        torpedo_kind(&record.get_or("kind", String::new())),
+                        launcher,
+                        record.position().extend(TORPEDO_Z),
+                        record.rotation("angle"),
+                    );
+                    commands
+                        .entity(torpedo)
+                        .insert(Travelled(record.get_or("travelled", 0.)));
+                    if !record.get_or("wire", false) {
+                        commands.entity(torpedo).remove::<Wire>();
+                    }
+                }
+            }
+            "mine" => {
+                if let Some(layer) = ship_at(record.get("layer")) {
+                    let mine = mine_spawn(
+                        &mut commands,
+                        sprite_materials,
+                        layer,
+                        record.position().extend(TORPEDO_Z),
+                    );
+                    commands.entity(mine).insert(record.timer("arming"));
+                }
+            }
+            _ => {}
+        }
+    }
+    queue.apply(world);
+
+    // Set the weapons of the boats, now spawned.
+    for (ship, record) in ships {
+        let children: Vec<Entity> = world
+            .get::<Children>(ship)
+            .unwrap()
+            .iter()
+            .copied()
+            .collect();
+        for child in children {
+            let mut child = world.entity_mut(child);
+            if child.contains::<CanonSight>() {
+                let sight_tf = Transform {
+                    translation: Vec3::new(
+                        record.get_or("canon_x", 0.),
+                        record.get_or("canon_y", 0.),
+                        WEAPON_Z,
+                    ),
+                    rotation: record.rotation("canon_angle"),
+                    ..Default::default()
+                };
+                child
+                    .insert(sight_tf)
+                    .insert(CanonSight(record.get_or("canon_range", 0.)))
+                    .insert(Amunitions(record.get_or("amunitions", 0)))
+                    .insert(record.timer("canon_reload"));
+            } else if child.contains::<TorpedoSight>() {
+                let sight_tf = Transform {
+                    translation: Vec3::new(
+                        record.get_or("torpedo_x", 0.),
+                        record.get_or("torpedo_y", 0.),
+                        WEAPON_Z,
+                    ),
+                    rotation: record.rotation("torpedo_angle"),
+                    ..Default::default()
+                };
+                let default_tube = TorpedoTube::default();
+                let tube = TorpedoTube {
+                    kind: torpedo_kind(&record.get_or("tube", String::new())),
+                    spread_count: record.get_or("spread_count", default_tube.spread_count),
+                    spread_fan: record.get_or("spread_fan", default_tube.spread_fan),
+                };
+                child
+                    .insert(sight_tf)
+                    .insert(tube)
+                    .insert(Torpedos(record.get_or("torpedos", 0)))
+                    .insert(record.timer("torpedo_reload"));
+            } else if child.contains::<Mines>() {
+                child
+                    .insert(Mines(record.get_or("mines", 0)))
+                    .insert(record.timer("mine_reload"));
+            }
+        }
+    }
+    world.insert_resource(tile_map);
+    let mut state = SystemState::<EventWriter<TerrainReset>>::new(world);
+    state.get_mut(world).send(TerrainReset);
+}
+
+fn save_notice_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let font = asset_server.load("FiraSans-Bold.ttf");
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(15.0),
+                    right: Val::Px(15.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font,
+                    font_size: 20.0,
+                    color: Color::BLACK,
+                },
+                Default::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(SaveNotice(Timer::from_seconds(NOTICE_DURATION, false)));
+}
+
+/// Tell the player, on F5 or F9, that games of this mode are not saved.
+fn save_unavailable(
+    input: Res<Input<KeyCode>>,
+    time: Res<Time>,
+    mut query: Query<(&mut Text, &mut SaveNotice)>,
+) {
+    let pressed = input.just_pressed(KeyCode::F5) || input.just_pressed(KeyCode::F9);
+    if pressed {
+        warn!("Only free-for-all games can be saved and loaded");
+    }
+    for (mut text, mut notice) in query.iter_mut() {
+        if pressed {
+            text.sections[0].value = String::from("Only free-for-all games can be saved");
+            notice.0.reset();
+        } else if notice.0.tick(time.delta()).just_finished() {
+            text.sections[0].value.clear();
+        }
+    }
+}
+
+//
+// Plugin
+//
+
+/// Save the game with F5 and load it back with F9.
+pub struct SavePlugin;
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        // Capture-the-flag, team and mission games, with their bases, flags and objectives,
+        // are not saved.
+        if app.world.get_resource::<GameMode>() != Some(&GameMode::FreeForAll) {
+            app.add_startup_system(save_notice_setup)
+                .add_system(save_unavailable);
+            return;
+        }
+        app.add_system(game_save.exclusive_system())
+            .add_system(game_load.exclusive_system());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn version_1_ships_become_frigates() {
+        let path = std::env::temp_dir().join("naval-save-v1-test.sav");
+        let text = "naval-save version=1\n\
+                    map w_tiles=10 h_tiles=10\n\
+                    ship team=1 player=false x=12 y=-4 angle=0 speed=1 life=80\n\
+                    mine x=3 y=4 layer=0 arming=0 arming_duration=2\n";
+        fs::write(&path, text).unwrap();
+        let records = read_save(path.to_str().unwrap()).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(records.len(), 3);
+        let ship = &records[1];
+        assert_eq!(ship.kind, "ship");
+        assert_eq!(ship.get::<String>("class").as_deref(), Some("frigate"));
+        assert_eq!(ship.get::<u32>("life"), Some(80));
+        assert_eq!(records[2].get::<String>("class"), None);
+    }
+
+    #[test]
+    fn unknown_versions_are_rejected() {
+        let path = std::env::temp_dir().join("naval-save-v99-test.sav");
+        fs::write(&path, format!("naval-save version={}\n", SAVE_VERSION + 1)).unwrap();
+        let result = read_save(path.to_str().unwrap());
+        fs::remove_file(&path).unwrap();
+
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,562 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::sync::{Arc, Mutex};
+
+use bevy::prelude::*;
+use rhai::module_resolvers::DummyModuleResolver;
+use rhai::{Array, Dynamic, Engine, FuncArgs, ImmutableString, Map, Scope, AST, FLOAT, INT};
+
+use crate::common::*;
+use crate::explosion::ExplosionEvent;
+use crate::island::TileMap;
+use crate::player::{
+    canon_fire_at, mine_drop_at, ship_spawn, torpedo_fire_at, Amunitions, CanonSight, Life,
+    MineRack, Mines, Player, Ship, ShipClass, Speed, Steering, TorpedoSight, Torpedos,
+};
+use crate::torpedo::TorpedoTube;
+
+// Limits of the sandbox, for a broken script not to hang or exhaust the game.
+const SCRIPT_MAX_OPERATIONS: u64 = 100_000;
+const SCRIPT_MAX_CALL_LEVELS: usize = 32;
+const SCRIPT_MAX_STRING_SIZE: usize = 4096;
+const SCRIPT_MAX_ARRAY_SIZE: usize = 1024;
+const SCRIPT_MAX_MAP_SIZE: usize = 256;
+// Messages of the scripts shown on screen.
+const SCRIPT_MESSAGES: usize = 3;
+
+//
+// Misc functions
+//
+
+fn ship_id(entity: Entity) -> INT {
+    entity.to_bits() as INT
+}
+
+fn ship_entity(id: INT) -> Entity {
+    Entity::from_bits(id as u64)
+}
+
+/// Engine with the API of the game, and nothing giving access to the machine.
+fn script_engine(host: &Arc<Mutex<Host>>) -> Engine {
+    let mut engine = Engine::new();
+    engine.set_module_resolver(DummyModuleResolver::new());
+    engine.disable_symbol("eval");
+    engine.set_max_operations(SCRIPT_MAX_OPERATIONS);
+    engine.set_max_call_levels(SCRIPT_MAX_CALL_LEVELS);
+    engine.set_max_string_size(SCRIPT_MAX_STRING_SIZE);
+    engine.set_max_array_size(SCRIPT_MAX_ARRAY_SIZE);
+    engine.set_max_map_size(SCRIPT_MAX_MAP_SIZE);
+    engine.on_print(|text| info!("script: {}", text));
+
+    // What the scripts can see.
+    let h = host.clone();
+    engine.register_fn("ships", move || h.lock().unwrap().ships.clone());
+    let h = host.clone();
+    engine.register_fn("is_land", move |x: FLOAT, y: FLOAT| {
+        let tile = tile_of(Vec3::new(x as f32, y as f32, 0.));
+        matches!(&h.lock().unwrap().tile_map, Some(tile_map) if tile_map.is_land(tile))
+    });
+    let h = host.clone();
+    engine.register_fn("depth", move |x: FLOAT, y: FLOAT| {
+        let tile = tile_of(Vec3::new(x as f32, y as f32, 0.));
+        match &h.lock().unwrap().tile_map {
+            Some(tile_map) => tile_map.depth(tile) as FLOAT,
+            None => 0.,
+        }
+    });
+    let h = host.clone();
+    engine.register_fn("get_state", move |key: ImmutableString| {
+        let host = h.lock().unwrap();
+        host.state
+            .get(key.as_str())
+            .cloned()
+            .unwrap_or(Dynamic::UNIT)
+    });
+
+    // What the scripts can do, once they return.
+    let h = host.clone();
+    engine.register_fn("set_state", move |key: ImmutableString, value: Dynamic| {
+        h.lock().unwrap().state.insert(key.as_str().into(), value);
+    });
+    let h = host.clone();
+    engine.register_fn("message", move |text: ImmutableString| {
+        h.lock().unwrap().messages.push(text.to_string());
+    });
+    let h = host.clone();
+    engine.register_fn(
+        "spawn_ship",
+        move |team: INT, x: FLOAT, y: FLOAT, angle: FLOAT| {
+            h.lock().unwrap().requests.push(Request::Spawn {
+                team: Team(team.max(0) as usize),
+                position: Vec3::new(x as f32, y as f32, BOAT_Z),
+                angle: angle as f32,
+                class: ShipClass::default(),
+            });
+        },
+    );
+    let h = host.clone();
+    engine.register_fn(
+        "spawn_ship",
+        move |team: INT, x: FLOAT, y: FLOAT, angle: FLOAT, class: &str| {
+            h.lock().unwrap().requests.push(Request::Spawn {
+                team: Team(team.max(0) as usize),
+                position: Vec3::new(x as f32, y as f32, BOAT_Z),
+                angle: angle as f32,
+                class: ShipClass::from_name(class).unwrap_or_default(),
+            });
+        },
+    );
+    let h = host.clone();
+    engine.register_fn(
+        "set_controls",
+        move |id: INT, throttle: FLOAT, rudder: FLOAT| {
+            h.lock().unwrap().requests.push(Request::Controls {
+                ship: ship_entity(id),
+                throttle: (throttle as f32).clamp(-1., 1.),
+                rudder: (rudder as f32).clamp(-1., 1.),
+            });
+        },
+    );
+    let h = host.clone();
+    engine.register_fn("fire_canon", move |id: INT, x: FLOAT, y: FLOAT| {
+        h.lock().unwrap().requests.push(Request::Fire {
+            ship: ship_entity(id),
+            target: Vec3::new(x as f32, y as f32, 0.),
+        });
+    });
+    let h = host.clone();
+    engine.register_fn("fire_torpedo", move |id: INT, x: FLOAT, y: FLOAT| {
+        h.lock().unwrap().requests.push(Request::Torpedo {
+            ship: ship_entity(id),
+            target: Vec3::new(x as f32, y as f32, 0.),
+        });
+    });
+    let h = host.clone();
+    engine.register_fn("drop_mine", move |id: INT| {
+        h.lock().unwrap().requests.push(Request::Mine {
+            ship: ship_entity(id),
+        });
+    });
+    engine
+}
+
+/// Call a hook of a script, if it defines it, stopping the script on error.
+fn call_hook(
+    engine: &Engine,
+    script: &mut Script,
+    errors: &mut Vec<String>,
+    hook: &str,
+    args: impl FuncArgs,
+) {
+    if !script.running || !script.hooks.contains(hook) {
+        return;
+    }
+    let result = engine.call_fn::<Dynamic>(&mut Scope::new(), &script.ast, hook, args);
+    if let Err(err) = result {
+        let error = format!("{}: {} in {}, script stopped", script.path, err, hook);
+        error!("{}", error);
+        errors.push(error);
+        script.running = false;
+    }
+}
+
+//
+// Components
+//
+
+#[derive(Component)]
+struct ScriptPanel;
+
+//
+// Resources
+//
+
+/// What a script asked the game to do.
+enum Request {
+    Spawn {
+        team: Team,
+        position: Vec3,
+        angle: f32,
+        class: ShipClass,
+    },
+    Controls {
+        ship: Entity,
+        throttle: f32,
+        rudder: f32,
+    },
+    Fire {
+        ship: Entity,
+        target: Vec3,
+    },
+    // Salvo from the torpedo tube, as set up on the boat.
+    Torpedo {
+        ship: Entity,
+        target: Vec3,
+    },
+    Mine {
+        ship: Entity,
+    },
+}
+
+/// Game as seen by the scripts, and what they asked for.
+#[derive(Default)]
+struct Host {
+    ships: Array,
+    tile_map: Option<TileMap>,
+    // Values kept by the scripts from one call to the next.
+    state: Map,
+    requests: Vec<Request>,
+    messages: Vec<String>,
+}
+
+struct Script {
+    path: String,
+    ast: AST,
+    hooks: HashSet<String>,
+    running: bool,
+}
+
+/// Scripts given on the command line, e.g. `naval --script scripts/patrol.rhai`.
+struct Scripts {
+    engine: Engine,
+    host: Arc<Mutex<Host>>,
+    scripts: Vec<Script>,
+    started: bool,
+    // Life of each boat at the previous tick, to tell the damage.
+    lives: HashMap<Entity, u32>,
+    errors: Vec<String>,
+}
+
+impl Scripts {
+    fn load(paths: &[String]) -> Self {
+        let host = Arc::new(Mutex::new(Host::default()));
+        let engine = script_engine(&host);
+        let mut scripts = Vec::new();
+        let mut errors = Vec::new();
+        for path in paths {
+            let ast = fs::read_to_string(path)
+                .map_err(|err| err.to_string())
+                .and_then(|text| engine.compile(&text).map_err(|err| err.to_string()));
+            match ast {
+                Ok(ast) => scripts.push(Script {
+                    path: path.clone(),
+                    hooks: ast.iter_functions().map(|f| f.name.to_string()).collect(),
+                    // Scripts are only made of hooks, called by the game.
+                    ast: ast.clone_functions_only(),
+                    running: true,
+                }),
+                Err(err) => errors.push(format!("{}: {}", path, err)),
+            }
+        }
+        Scripts {
+            engine,
+            host,
+            scripts,
+            started: false,
+            lives: HashMap::new(),
+            errors,
+        }
+    }
+}
+
+//
+// Systems
+//
+
+fn script_run(
+    time: Res<GameTime>,
+    tile_map: Res<TileMap>,
+    mut scripts: ResMut<Scripts>,
+    mut explosions: EventReader<ExplosionEvent>,
+    query_ship: Query<
+        (
+            Entity,
+            &Transform,
+            &Team,
+            &Speed,
+            &Life,
+            Option<&Player>,
+            &Children,
+        ),
+        With<Ship>,
+    >,
+    query_canon: Query<&Amunitions>,
+    query_torpedos: Query<&Torpedos>,
+    query_mines: Query<&Mines>,
+) {
+    let scripts = &mut *scripts;
+    // Show the scripts the boats as they are now.
+    let mut damages = Vec::new();
+    let mut ships = Array::new();
+    for (entity, ship_tf, team, speed, life, player, children) in query_ship.iter() {
+        let heading = ship_tf.rotation.mul_vec3(Vec3::X);
+        let mut ship = Map::new();
+        ship.insert("id".into(), Dynamic::from(ship_id(entity)));
+        ship.insert("team".into(), Dynamic::from(team.0 as INT));
+        ship.insert("player".into(), Dynamic::from(player.is_some()));
+        ship.insert("x".into(), Dynamic::from(ship_tf.translation.x as FLOAT));
+        ship.insert("y".into(), Dynamic::from(ship_tf.translation.y as FLOAT));
+        ship.insert(
+            "angle".into(),
+            Dynamic::from(heading.y.atan2(heading.x) as FLOAT),
+        );
+        ship.insert("speed".into(), Dynamic::from(speed.0 as FLOAT));
+        ship.insert("life".into(), Dynamic::from(life.0 as INT));
+        for child in children.iter() {
+            if let Ok(amunitions) = query_canon.get(*child) {
+                ship.insert("amunitions".into(), Dynamic::from(amunitions.0 as INT));
+            }
+            if let Ok(torpedos) = query_torpedos.get(*child) {
+                ship.insert("torpedos".into(), Dynamic::from(torpedos.0 as INT));
+            }
+            if let Ok(mines) = query_mines.get(*child) {
+                ship.insert("mines".into(), Dynamic::from(mines.0 as INT));
+            }
+        }
+        ships.push(Dynamic::from(ship));
+        if let Some(previous) = scripts.lives.insert(entity, life.0) {
+            if previous > life.0 {
+                damages.push((ship_id(entity), (previous - life.0) as INT));
+            }
+        }
+    }
+    // Forget the boats sunk.
+    scripts
+        .lives
+        .retain(|entity, _| query_ship.get(*entity).is_ok());
+    {
+        let mut host = scripts.host.lock().unwrap();
+        host.ships = ships;
+        if tile_map.is_changed() || host.tile_map.is_none() {
+            host.tile_map = Some(tile_map.clone());
+        }
+    }
+    let explosions: Vec<(FLOAT, FLOAT, String)> = explosions
+        .iter()
+        .map(|explosion| {
+            (
+                explosion.position.x as FLOAT,
+                explosion.position.y as FLOAT,
+                format!("{:?}", explosion.kind),
+            )
+        })
+        .collect();
+
+    // Call the hooks of each script.
+    let start = !scripts.started;
+    scripts.started = true;
+    for script in scripts.scripts.iter_mut() {
+        let (engine, errors) = (&scripts.engine, &mut scripts.errors);
+        if start {
+            call_hook(engine, script, errors, "on_start", ());
+        }
+        call_hook(
+            engine,
+            script,
+            errors,
+            "on_tick",
+            (time.delta_seconds() as FLOAT,),
+        );
+        for (id, damage) in damages.iter() {
+            call_hook(engine, script, errors, "on_damage", (*id, *damage));
+        }
+        for (x, y, kind) in explosions.iter() {
+            call_hook(
+                engine,
+                script,
+                errors,
+                "on_explosion",
+                (*x, *y, kind.clone()),
+            );
+        }
+    }
+}
+
+fn script_requests(
+    mut commands: Commands,
+    sprite_materials: Res<SpriteMaterials>,
+    scripts: Res<Scripts>,
+    query_ship: Query<&Transform, (With<Ship>, Without<Player>)>,
+    mut query_steering: Query<&mut Steering>,
+    mut query_sight: Query<(&Parent, &mut Amunitions, &mut Timer), With<CanonSight>>,
+    mut query_tube: Query<
+        (&Parent, &TorpedoTube, &mut Torpedos, &mut Timer),
+        (With<TorpedoSight>, Without<CanonSight>),
+    >,
+    mut query_rack: Query<
+        (&Parent, &Transform, &mut Mines, &mut Timer),
+        (With<MineRack>, Without<CanonSight>, Without<TorpedoSight>),
+    >,
+) {
+    let requests: Vec<Request> = scripts.host.lock().unwrap().requests.drain(..).collect();
+    for request in requests {
+        match request {
+            Request::Spawn {
+                team,
+                position,
+                angle,
+                class,
+            } => {
+                ship_spawn(
+                    &mut commands,
+                    &sprite_materials,
+                    position,
+                    angle,
+                    team,
+                    class,
+                );
+            }
+            // Scripts only steer the boats not played.
+            Request::Controls {
+                ship,
+                throttle,
+                rudder,
+            } => {
+                if query_ship.get(ship).is_err() {
+                    continue;
+                }
+                match query_steering.get_mut(ship) {
+                    Ok(mut steering) => {
+                        steering.throttle = throttle;
+                        steering.rudder = rudder;
+                    }
+                    Err(_) => {
+                        commands.entity(ship).insert(Steering { throttle, rudder });
+                    }
+                }
+            }
+            Request::Fire { ship, target } => {
+                let ship_tf = match query_ship.get(ship) {
+                    Ok(ship_tf) => ship_tf,
+                    Err(_) => continue,
+                };
+                for (parent, mut amunitions, mut timer) in query_sight.iter_mut() {
+                    if parent.0 == ship {
+                        canon_fire_at(
+                            &mut commands,
+                            &sprite_materials,
+                            ship,
+                            ship_tf,
+                            target,
+                            &mut amunitions,
+                            &mut timer,
+                        );
+                    }
+                }
+            }
+            Request::Torpedo { ship, target } => {
+                let ship_tf = match query_ship.get(ship) {
+                    Ok(ship_tf) => ship_tf,
+                    Err(_) => continue,
+                };
+                for (parent, tube, mut torpedos, mut timer) in query_tube.iter_mut() {
+                    if parent.0 == ship {
+                        torpedo_fire_at(
+                            &mut commands,
+                            &sprite_materials,
+                            ship,
+                            ship_tf,
+                            target,
+                            tube,
+                            &mut torpedos,
+                            &mut timer,
+                        );
+                    }
+                }
+            }
+            Request::Mine { ship } => {
+                let ship_tf = match query_ship.get(ship) {
+                    Ok(ship_tf) => ship_tf,
+                    Err(_) => continue,
+                };
+                for (parent, rack_tf, mut mines, mut timer) in query_rack.iter_mut() {
+                    if parent.0 == ship {
+                        mine_drop_at(
+                            &mut commands,
+                            &sprite_materials,
+                            ship,
+                            ship_tf,
+                            rack_tf,
+                            &mut mines,
+                            &mut timer,
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn script_panel_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let font = asset_server.load("FiraSans-Bold.ttf");
+    let section = |color| TextSection {
+        value: String::new(),
+        style: TextStyle {
+            font: font.clone(),
+            font_size: 15.0,
+            color,
+        },
+    };
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    bottom: Val::Px(25.0),
+                    left: Val::Px(15.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text {
+                sections: vec![section(Color::BLACK), section(Color::rgb(0.8, 0.1, 0.1))],
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .insert(ScriptPanel);
+}
+
+fn script_panel(scripts: Res<Scripts>, mut query: Query<&mut Text, With<ScriptPanel>>) {
+    // Last messages of the scripts, then their errors.
+    let messages = {
+        let mut host = scripts.host.lock().unwrap();
+        let skip = host.messages.len().saturating_sub(SCRIPT_MESSAGES);
+        host.messages.drain(..skip);
+        host.messages.join("\n")
+    };
+    for mut text in query.iter_mut() {
+        text.sections[0].value = if scripts.errors.is_empty() {
+            messages.clone()
+        } else {
+            format!("{}\n", messages)
+        };
+        text.sections[1].value = scripts.errors.join("\n");
+    }
+}
+
+//
+// Plugin
+//
+
+/// Run the scripts given with `naval --script <file>`, in a sandbox.
+pub struct ScriptPlugin;
+
+impl Plugin for ScriptPlugin {
+    fn build(&self, app: &mut App) {
+        let args: Vec<String> = std::env::args().collect();
+        let paths: Vec<String> = args
+            .windows(2)
+            .filter(|pair| pair[0] == "--script")
+            .map(|pair| pair[1].clone())
+            .collect();
+        if paths.is_empty() {
+            return;
+        }
+        app.insert_resource(Scripts::load(&paths))
+            .add_startup_system(script_panel_setup)
+            .add_system(script_run)
+            .add_system(script_requests.after(script_run))
+            .add_system(script_panel.after(script_requests));
+    }
+}
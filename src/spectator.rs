@@ -0,0 +1,214 @@
+use bevy::input::InputSystem;
+use bevy::prelude::*;
+
+use crate::camera::CameraRig;
+use crate::common::*;
+use crate::dashboard::{compass, reload_progress};
+use crate::player::{Amunitions, CanonSight, Life, Mines, Player, Ship, TorpedoSight, Torpedos};
+
+// Speed of the free camera, in pixels per second at the normal zoom.
+const CAMERA_PAN_SPEED: f32 = 400.;
+
+//
+// Misc functions
+//
+
+/// State of a weapon, from the progress of its reload timer.
+fn weapon_state(timer: &Timer) -> String {
+    let progress = reload_progress(timer);
+    if progress < 1. {
+        format!("{:.0}%", 100. * progress)
+    } else {
+        String::from("ready")
+    }
+}
+
+//
+// Components
+//
+
+#[derive(Component)]
+struct SpectatorOverlay;
+
+//
+// Resources
+//
+
+/// Whether the game is watched rather than played, and which boat the camera follows.
+pub struct Spectator {
+    // Watching only, as asked on the command line: the player is given no boat.
+    pub read_only: bool,
+    pub active: bool,
+    following: Option<Entity>,
+}
+
+impl Spectator {
+    pub fn new(read_only: bool) -> Self {
+        Spectator {
+            read_only,
+            active: false,
+            following: None,
+        }
+    }
+}
+
+//
+// Systems
+//
+
+fn spectator_switch(mut spectator: ResMut<Spectator>, query_player: Query<&Player>) {
+    // Spectate while the boat of the player is sunk.
+    let active = spectator.read_only || query_player.iter().next().is_none();
+    if active != spectator.active {
+        spectator.active = active;
+        spectator.following = None;
+    }
+}
+
+fn spectator_camera(
+    time: Res<Time>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut spectator: ResMut<Spectator>,
+    mut rig: ResMut<CameraRig>,
+    query_ship: Query<(Entity, &Transform), With<Ship>>,
+) {
+    if !spectator.active {
+        return;
+    }
+    // Tab follows the next boat, the arrows pan freely.
+    if keyboard_input.just_pressed(KeyCode::Tab) {
+        let mut ships: Vec<Entity> = query_ship.iter().map(|(entity, _)| entity).collect();
+        ships.sort();
+        let current = spectator
+            .following
+            .and_then(|following| ships.iter().position(|ship| *ship == following));
+        spectator.following = match current {
+            Some(i) => ships.get((i + 1) % ships.len()).copied(),
+            None => ships.first().copied(),
+        };
+    }
+    let mut pan = Vec2::ZERO;
+    if keyboard_input.pressed(KeyCode::Left) {
+        pan.x -= 1.;
+    }
+    if keyboard_input.pressed(KeyCode::Right) {
+        pan.x += 1.;
+    }
+    if keyboard_input.pressed(KeyCode::Down) {
+        pan.y -= 1.;
+    }
+    if keyboard_input.pressed(KeyCode::Up) {
+        pan.y += 1.;
+    }
+    if pan != Vec2::ZERO {
+        spectator.following = None;
+        let step = CAMERA_PAN_SPEED * rig.zoom * time.delta_seconds();
+        rig.centre += step * pan;
+    }
+    if let Some(following) = spectator.following {
+        match query_ship.get(following) {
+            Ok((_, ship_tf)) => rig.centre = ship_tf.translation.truncate(),
+            // The boat followed sank.
+            Err(_) => spectator.following = None,
+        }
+    }
+}
+
+fn spectator_overlay_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    bottom: Val::Px(5.0),
+                    right: Val::Px(15.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("FiraSans-Bold.ttf"),
+                    font_size: 15.0,
+                    color: Color::BLACK,
+                },
+                Default::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(SpectatorOverlay);
+}
+
+fn spectator_overlay(
+    spectator: Res<Spectator>,
+    mut query_overlay: Query<&mut Text, With<SpectatorOverlay>>,
+    query_ship: Query<(Entity, &Transform, &Team, &Life, &Children), With<Ship>>,
+    query_canon: Query<(&Amunitions, &Timer), With<CanonSight>>,
+    query_torpedo: Query<(&Torpedos, &Timer), With<TorpedoSight>>,
+    query_mines: Query<&Mines>,
+) {
+    let mut lines = Vec::new();
+    if spectator.active {
+        lines.push(String::from(
+            "Spectating (Tab: follow a boat, arrows: pan, PgUp/PgDn: zoom)",
+        ));
+        let mut ships: Vec<_> = query_ship.iter().collect();
+        ships.sort_by_key(|(entity, ..)| *entity);
+        // One line per boat, with its life, heading and weapons.
+        for (entity, ship_tf, team, life, children) in ships {
+            let heading = ship_tf.rotation.mul_vec3(Vec3::X);
+            let mut line = format!(
+                "{}{} - life {} - heading {:03.0}°",
+                if spectator.following == Some(entity) {
+                    "> "
+                } else {
+                    ""
+                },
+                team.name(),
+                life.0,
+                compass(heading.y.atan2(heading.x))
+            );
+            for child in children.iter() {
+                if let Ok((amunitions, timer)) = query_canon.get(*child) {
+                    line += &format!(" - canon {} {}", amunitions.0, weapon_state(timer));
+                }
+                if let Ok((torpedos, timer)) = query_torpedo.get(*child) {
+                    line += &format!(" - torpedos {} {}", torpedos.0, weapon_state(timer));
+                }
+                if let Ok(mines) = query_mines.get(*child) {
+                    line += &format!(" - mines {}", mines.0);
+                }
+            }
+            lines.push(line);
+        }
+    }
+    for mut text in query_overlay.iter_mut() {
+        text.sections[0].value = lines.join("\n");
+    }
+}
+
+//
+// Plugin
+//
+
+/// Watch the game with a free camera, once sunk or from the start with `naval --spectate`
+/// or `naval --join <address>`.
+pub struct SpectatorPlugin;
+
+impl Plugin for SpectatorPlugin {
+    fn build(&self, app: &mut App) {
+        // Out of the game systems, to look around even when a replay is paused.
+        // Spectators of a game hosted elsewhere have no boat either.
+        app.insert_resource(Spectator::new(
+            std::env::args().any(|arg| arg == "--spectate" || arg == "--join"),
+        ))
+        .add_startup_system(spectator_overlay_setup)
+        .add_system_to_stage(CoreStage::PreUpdate, spectator_switch)
+        .add_system_to_stage(
+            CoreStage::PreUpdate,
+            spectator_camera.after(InputSystem).after(spectator_switch),
+        )
+        .add_system_to_stage(CoreStage::Last, spectator_overlay);
+    }
+}
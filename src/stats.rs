@@ -0,0 +1,408 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use serde::Serialize;
+use serde_json::json;
+
+use crate::canonball::{CanonBall, FiredBy};
+use crate::common::*;
+use crate::explosion::{HitEvent, Weapon};
+use crate::island::TileMap;
+use crate::mine::{LaidBy, Mine};
+use crate::player::{Draft, Life, Player, Ship};
+use crate::torpedo::{Launcher, Torpedo};
+
+// Distance under which a torpedo passing by threatens a boat.
+const TORPEDO_THREAT_RANGE: f32 = 48.;
+
+//
+// Misc functions
+//
+
+fn summary_string(stats: &Stats) -> String {
+    let mut lines = vec![format!("Match summary, {:.0} s", stats.seconds)];
+    for ship in stats.ships.iter() {
+        lines.push(format!(
+            "{}{}: canon {}/{}, torpedos {}/{}, mines {}/{}, damage {} dealt {} taken, {} kills, \
+             {:.0} px sailed, {:.0} s aground, {} torpedos evaded",
+            ship.name,
+            if ship.player { " (you)" } else { "" },
+            ship.canon.hit,
+            ship.canon.fired,
+            ship.torpedo.hit,
+            ship.torpedo.fired,
+            ship.mine.hit,
+            ship.mine.fired,
+            ship.damage_dealt,
+            ship.damage_taken,
+            ship.kills,
+            ship.distance,
+            ship.aground,
+            ship.torpedos_evaded
+        ));
+    }
+    lines.join("\n")
+}
+
+/// Write the statistics of the game as JSON, with the totals of each team.
+fn stats_write(path: &str, stats: &Stats, seed: u64, mode: GameMode) -> io::Result<()> {
+    let mut teams: Vec<ShipStats> = Vec::new();
+    for ship in stats.ships.iter() {
+        while teams.len() <= ship.team {
+            teams.push(ShipStats {
+                name: Team(teams.len()).name().to_string(),
+                team: teams.len(),
+                ..Default::default()
+            });
+        }
+        teams[ship.team].add(ship);
+    }
+    let report = json!({
+        "seed": seed,
+        "mode": format!("{:?}", mode),
+        "seconds": stats.seconds,
+        "ships": stats.ships,
+        "teams": teams,
+    });
+    serde_json::to_writer_pretty(File::create(path)?, &report)?;
+    Ok(())
+}
+
+//
+// Components
+//
+
+#[derive(Component)]
+struct SummaryPanel;
+
+//
+// Resources
+//
+
+#[derive(Clone, Default, Serialize)]
+pub struct WeaponStats {
+    pub fired: u32,
+    // Hulls hit.
+    pub hit: u32,
+}
+
+impl WeaponStats {
+    fn add(&mut self, other: &WeaponStats) {
+        self.fired += other.fired;
+        self.hit += other.hit;
+    }
+}
+
+#[derive(Clone, Default, Serialize)]
+pub struct ShipStats {
+    pub name: String,
+    pub team: usize,
+    pub player: bool,
+    pub canon: WeaponStats,
+    pub torpedo: WeaponStats,
+    pub mine: WeaponStats,
+    pub damage_dealt: u32,
+    pub damage_taken: u32,
+    // Boats and bunkers brought down to no life.
+    pub kills: u32,
+    // Pixels sailed, and seconds spent touching the bottom.
+    pub distance: f32,
+    pub aground: f32,
+    // Torpedos which came close and went by.
+    pub torpedos_evaded: u32,
+    // Position and life at the previous update.
+    #[serde(skip)]
+    position: Vec3,
+    #[serde(skip)]
+    life: u32,
+}
+
+impl ShipStats {
+    fn weapon(&mut self, weapon: Weapon) -> &mut WeaponStats {
+        match weapon {
+            Weapon::Canon => &mut self.canon,
+            Weapon::Torpedo => &mut self.torpedo,
+            Weapon::Mine => &mut self.mine,
+        }
+    }
+
+    fn add(&mut self, other: &ShipStats) {
+        self.canon.add(&other.canon);
+        self.torpedo.add(&other.torpedo);
+        self.mine.add(&other.mine);
+        self.damage_dealt += other.damage_dealt;
+        self.damage_taken += other.damage_taken;
+        self.kills += other.kills;
+        self.distance += other.distance;
+        self.aground += other.aground;
+        self.torpedos_evaded += other.torpedos_evaded;
+    }
+}
+
+/// What each boat of the game did, a respawned boat counting as a new one.
+#[derive(Clone, Default)]
+pub struct Stats {
+    pub ships: Vec<ShipStats>,
+    pub seconds: f32,
+    index: HashMap<Entity, usize>,
+    // Boats each torpedo in the water came close to.
+    threats: HashMap<Entity, Vec<Entity>>,
+}
+
+impl Stats {
+    fn ship(&mut self, entity: Entity) -> Option<&mut ShipStats> {
+        let i = *self.index.get(&entity)?;
+        self.ships.get_mut(i)
+    }
+}
+
+/// File the statistics are written to, with `--stats <file>`.
+struct StatsExport {
+    path: String,
+    written: bool,
+}
+
+//
+// Systems
+//
+
+fn stats_ships(
+    mut stats: ResMut<Stats>,
+    query_ship: Query<(Entity, &Transform, &Team, &Life, Option<&Player>), Added<Ship>>,
+) {
+    for (ship_entity, ship_tf, team, life, player) in query_ship.iter() {
+        let number = stats
+            .ships
+            .iter()
+            .filter(|ship| ship.team == team.0)
+            .count()
+            + 1;
+        let i = stats.ships.len();
+        stats.ships.push(ShipStats {
+            name: format!("{} {}", team.name(), number),
+            team: team.0,
+            player: player.is_some(),
+            position: ship_tf.translation,
+            life: life.0,
+            ..Default::default()
+        });
+        stats.index.insert(ship_entity, i);
+    }
+}
+
+fn stats_shots(
+    mut stats: ResMut<Stats>,
+    query_canonball: Query<&FiredBy, Added<CanonBall>>,
+    query_torpedo: Query<&Launcher, Added<Torpedo>>,
+    query_mine: Query<&LaidBy, Added<Mine>>,
+) {
+    let shots = query_canonball
+        .iter()
+        .map(|fired_by| (fired_by.0, Weapon::Canon))
+        .chain(
+            query_torpedo
+                .iter()
+                .map(|launcher| (launcher.0, Weapon::Torpedo)),
+        )
+        .chain(query_mine.iter().map(|laid_by| (laid_by.0, Weapon::Mine)));
+    for (source, weapon) in shots {
+        if let Some(ship) = stats.ship(source) {
+            ship.weapon(weapon).fired += 1;
+        }
+    }
+}
+
+fn stats_hits(
+    mut stats: ResMut<Stats>,
+    mut hits: EventReader<HitEvent>,
+    query_torpedo: Query<(Entity, &Transform, &Launcher), With<Torpedo>>,
+    query_ship: Query<(Entity, &Transform, &Team), With<Ship>>,
+    query_team: Query<&Team>,
+) {
+    let mut torpedoed = Vec::new();
+    for hit in hits.iter() {
+        // A boat caught by its own mine is no hit of its weapons.
+        if hit.source == Some(hit.target) {
+            continue;
+        }
+        // A torpedoed boat evaded none of the torpedos around it.
+        if hit.weapon == Weapon::Torpedo {
+            torpedoed.push(hit.target);
+            for threats in stats.threats.values_mut() {
+                threats.retain(|ship_entity| *ship_entity != hit.target);
+            }
+        }
+        if let Some(ship) = hit.source.and_then(|source| stats.ship(source)) {
+            ship.weapon(hit.weapon).hit += 1;
+            ship.damage_dealt += hit.damage;
+            ship.kills += hit.fatal as u32;
+        }
+    }
+
+    // Boats threatened by a torpedo evade it if it goes away without hitting them.
+    for (torpedo_entity, torpedo_tf, launcher) in query_torpedo.iter() {
+        let launcher_team = query_team.get(launcher.0).ok();
+        for (ship_entity, ship_tf, team) in query_ship.iter() {
+            let threatened = ship_entity != launcher.0
+                && !torpedoed.contains(&ship_entity)
+                && launcher_team != Some(team)
+                && distance(ship_tf.translation, torpedo_tf.translation) < TORPEDO_THREAT_RANGE;
+            if threatened {
+                let threats = stats.threats.entry(torpedo_entity).or_default();
+                if !threats.contains(&ship_entity) {
+                    threats.push(ship_entity);
+                }
+            }
+        }
+    }
+    let gone: Vec<Entity> = stats
+        .threats
+        .keys()
+        .filter(|torpedo_entity| query_torpedo.get(**torpedo_entity).is_err())
+        .copied()
+        .collect();
+    for torpedo_entity in gone {
+        let threats = stats.threats.remove(&torpedo_entity).unwrap_or_default();
+        for ship_entity in threats {
+            if let Some(ship) = stats.ship(ship_entity) {
+                ship.torpedos_evaded += 1;
+            }
+        }
+    }
+}
+
+fn stats_sailing(
+    time: Res<GameTime>,
+    tile_map: Res<TileMap>,
+    mut stats: ResMut<Stats>,
+    query_ship: Query<(Entity, &Transform, &Draft, &Life), With<Ship>>,
+) {
+    stats.seconds += time.delta_seconds();
+    for (ship_entity, ship_tf, draft, life) in query_ship.iter() {
+        let ship = match stats.ship(ship_entity) {
+            Some(ship) => ship,
+            None => continue,
+        };
+        ship.distance += distance(ship_tf.translation, ship.position);
+        ship.position = ship_tf.translation;
+        if tile_map.depth(tile_of(ship_tf.translation)) < draft.0 {
+            ship.aground += time.delta_seconds();
+        }
+        // Whatever the cause, weapons or the bottom; repairs do not count.
+        ship.damage_taken += ship.life.saturating_sub(life.0);
+        ship.life = life.0;
+    }
+}
+
+fn summary_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                display: Display::None,
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(80.0),
+                    left: Val::Px(40.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("FiraSans-Bold.ttf"),
+                    font_size: 15.0,
+                    color: Color::BLACK,
+                },
+                Default::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(SummaryPanel);
+}
+
+fn summary_panel(
+    keyboard_input: Res<Input<KeyCode>>,
+    game_state: Res<State<GameState>>,
+    stats: Res<Stats>,
+    mut query: Query<(&mut Style, &mut Text), With<SummaryPanel>>,
+) {
+    // Shown at the end of the game, and at any time with F1.
+    let over = !matches!(
+        game_state.current(),
+        GameState::Playing | GameState::Watching
+    );
+    for (mut style, mut text) in query.iter_mut() {
+        if game_state.is_changed() && over {
+            style.display = Display::Flex;
+        }
+        if keyboard_input.just_pressed(KeyCode::F1) {
+            style.display = match style.display {
+                Display::Flex => Display::None,
+                Display::None => Display::Flex,
+            };
+        }
+        if style.display == Display::Flex {
+            text.sections[0].value = summary_string(&stats);
+        }
+    }
+}
+
+fn stats_export(
+    mut export: ResMut<StatsExport>,
+    stats: Res<Stats>,
+    game_rng: Res<GameRng>,
+    game_mode: Res<GameMode>,
+    game_state: Res<State<GameState>>,
+    mut app_exit: EventReader<AppExit>,
+) {
+    // Once the game is over, or when leaving before.
+    let over = !matches!(
+        game_state.current(),
+        GameState::Playing | GameState::Watching
+    );
+    let exit = app_exit.iter().count() > 0;
+    if export.written || !(over || exit) {
+        return;
+    }
+    export.written = true;
+    if let Err(err) = stats_write(&export.path, &stats, game_rng.seed, *game_mode) {
+        error!("Cannot write the statistics to {}: {}", export.path, err);
+    }
+}
+
+//
+// Plugin
+//
+
+/// Statistics of the boats, in a summary at the end of the game and in JSON with
+/// `--stats <file>`.
+pub struct StatsPlugin;
+
+impl Plugin for StatsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Stats>()
+            .add_startup_system(summary_setup)
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(stats_ships)
+                    .with_system(stats_shots)
+                    .with_system(stats_hits)
+                    .with_system(stats_sailing),
+            )
+            .add_system(summary_panel);
+        let args: Vec<String> = std::env::args().collect();
+        if let Some(i) = args.iter().position(|arg| arg == "--stats") {
+            if let Some(path) = args.get(i + 1) {
+                app.insert_resource(StatsExport {
+                    path: path.clone(),
+                    written: false,
+                })
+                .add_system_to_stage(CoreStage::Last, stats_export);
+            }
+        }
+    }
+}
@@ -0,0 +1,297 @@
+use bevy::prelude::*;
+
+use crate::common::*;
+use crate::ctf::Base;
+use crate::island::TileMap;
+use crate::player::{
+    canon_fire_at, ship_spawn, Amunitions, CanonSight, Draft, Life, Player, PlayerClass, Ship,
+    ShipClass, ShipSunk, Steering, CANON_MAX_DISTANCE,
+};
+use crate::port::{bunker_spawn, port_spawn, BunkerDestroyed};
+use crate::spectator::Spectator;
+
+// Classes of the boats of a fleet, the first one being that of the player in the Blue fleet.
+const FLEET: [ShipClass; 3] = [ShipClass::Frigate, ShipClass::Cruiser, ShipClass::Patrol];
+const BUNKERS_PER_BASE: usize = 2;
+// Distance between the boats of a fleet as they leave their base.
+const FLEET_SPACING: f32 = 32.;
+// Distance under which a crew slows down and opens fire.
+const CREW_FIRE_RANGE: f32 = 0.8 * CANON_MAX_DISTANCE;
+// Distance ahead of its boat at which a crew looks out for shallow water.
+const CREW_LOOKOUT: f32 = 32.;
+const CREW_SLOW_THROTTLE: f32 = 0.4;
+
+//
+// Misc functions
+//
+
+/// Spawn the base of a team on the given ground tile, with bunkers on some of the given tiles.
+pub fn fort_spawn(
+    commands: &mut Commands,
+    sprite_materials: &SpriteMaterials,
+    tile: (i32, i32),
+    dock: Vec3,
+    team: Team,
+    bunker_tiles: &[(i32, i32)],
+) {
+    let base = port_spawn(commands, sprite_materials, tile, Some(team));
+    commands.entity(base).insert(Base { dock });
+    for bunker_tile in bunker_tiles.iter().take(BUNKERS_PER_BASE) {
+        bunker_spawn(commands, sprite_materials, *bunker_tile, team);
+    }
+}
+
+fn scoreboard_string(scores: &TeamScores, over: bool) -> String {
+    let mut scoreboard = format!(
+        "{} {} - {} {}",
+        Team(0).name(),
+        scores.points[0],
+        scores.points[1],
+        Team(1).name()
+    );
+    if over {
+        scoreboard += &match scores.winner {
+            Some(team) => format!("\n{} wins!", team.name()),
+            None => String::from("\nDraw!"),
+        };
+    }
+    scoreboard
+}
+
+//
+// Components
+//
+
+/// Boat sailed by its crew, toward the closest enemy.
+#[derive(Component)]
+pub struct Crew;
+
+#[derive(Component)]
+struct TeamScoreBoard;
+
+//
+// Resources
+//
+
+/// Enemy boats sunk and bunkers destroyed by each team, and the team left afloat.
+#[derive(Default)]
+pub struct TeamScores {
+    pub points: [u32; NUM_TEAMS],
+    pub winner: Option<Team>,
+}
+
+//
+// Systems
+//
+
+fn fleet_spawn(
+    mut commands: Commands,
+    sprite_materials: Res<SpriteMaterials>,
+    spectator: Res<Spectator>,
+    player_class: Res<PlayerClass>,
+    query_base: Query<(&Base, &Team)>,
+) {
+    for (base, team) in query_base.iter() {
+        // Fleets leave their base side by side, heading toward the center of the sea.
+        let angle = if base.dock.x < 0. {
+            0.
+        } else {
+            std::f32::consts::PI
+        };
+        for (i, class) in FLEET.iter().enumerate() {
+            let offset = FLEET_SPACING * (i as f32 - (FLEET.len() - 1) as f32 / 2.);
+            let position = base.dock + Vec3::new(0., offset, 0.);
+            // The player commands the first boat of the first team, the crews the others.
+            let player = *team == Team(0) && i == 0 && !spectator.read_only;
+            let class = if player { player_class.0 } else { *class };
+            let ship = ship_spawn(
+                &mut commands,
+                &sprite_materials,
+                position,
+                angle,
+                *team,
+                class,
+            );
+            if player {
+                commands.entity(ship).insert(Player);
+            } else {
+                commands.entity(ship).insert(Crew).insert(Steering {
+                    throttle: 0.,
+                    rudder: 0.,
+                });
+            }
+        }
+    }
+}
+
+pub fn crew_orders(
+    mut commands: Commands,
+    sprite_materials: Res<SpriteMaterials>,
+    tile_map: Res<TileMap>,
+    mut query_crew: Query<(Entity, &Transform, &Team, &Draft, &mut Steering), With<Crew>>,
+    query_ship: Query<(&Transform, &Team, &Life), With<Ship>>,
+    mut query_sight: Query<(&Parent, &mut Amunitions, &mut Timer), With<CanonSight>>,
+) {
+    for (ship, ship_tf, team, draft, mut steering) in query_crew.iter_mut() {
+        let position = ship_tf.translation;
+        let enemy = query_ship
+            .iter()
+            .filter(|(_, enemy_team, life)| *enemy_team != team && life.0 > 0)
+            .map(|(enemy_tf, ..)| enemy_tf.translation)
+            .min_by(|a, b| {
+                distance(*a, position)
+                    .partial_cmp(&distance(*b, position))
+                    .unwrap()
+            });
+        let target = match enemy {
+            Some(target) => target,
+            None => {
+                steering.throttle = 0.;
+                steering.rudder = 0.;
+                continue;
+            }
+        };
+
+        // Head for the enemy, slowing down once within range.
+        let heading = ship_tf.rotation.mul_vec3(Vec3::X);
+        let (ahead, to_target) = (heading.truncate(), (target - position).truncate());
+        let turn = ahead.perp_dot(to_target).atan2(ahead.dot(to_target));
+        steering.rudder = (2. * turn).clamp(-1., 1.);
+        let in_range = distance(target, position) < CREW_FIRE_RANGE;
+        steering.throttle = if in_range { CREW_SLOW_THROTTLE } else { 1. };
+        // Steer clear of shallow water.
+        let lookout = position + CREW_LOOKOUT * heading;
+        if tile_map.depth(tile_of(lookout)) < draft.0 {
+            steering.throttle = CREW_SLOW_THROTTLE;
+            steering.rudder = 1.;
+        }
+
+        if !in_range {
+            continue;
+        }
+        for (parent, mut amunitions, mut timer) in query_sight.iter_mut() {
+            if parent.0 == ship {
+                canon_fire_at(
+                    &mut commands,
+                    &sprite_materials,
+                    ship,
+                    ship_tf,
+                    target,
+                    &mut amunitions,
+                    &mut timer,
+                );
+            }
+        }
+    }
+}
+
+fn team_scoring(
+    mut scores: ResMut<TeamScores>,
+    mut ship_sunk: EventReader<ShipSunk>,
+    mut bunker_destroyed: EventReader<BunkerDestroyed>,
+) {
+    // Each loss of a team scores for the others.
+    let losses = ship_sunk
+        .iter()
+        .map(|sunk| sunk.team)
+        .chain(bunker_destroyed.iter().map(|destroyed| destroyed.team));
+    for team in losses {
+        for other in 0..NUM_TEAMS {
+            if other != team.0 {
+                scores.points[other] += 1;
+            }
+        }
+    }
+}
+
+fn team_victory(
+    mut scores: ResMut<TeamScores>,
+    mut game_state: ResMut<State<GameState>>,
+    query_ship: Query<(&Team, &Life), With<Ship>>,
+) {
+    // The battle is over once a single team, or none, has boats afloat.
+    let mut afloat = [false; NUM_TEAMS];
+    for (team, life) in query_ship.iter() {
+        if life.0 > 0 && team.0 < NUM_TEAMS {
+            afloat[team.0] = true;
+        }
+    }
+    let mut teams = (0..NUM_TEAMS).filter(|team| afloat[*team]);
+    if let (first, None) = (teams.next(), teams.next()) {
+        scores.winner = first.map(Team);
+        if let Err(err) = game_state.set(GameState::Over) {
+            warn!("Cannot end the battle: {:?}", err);
+        }
+    }
+}
+
+fn scoreboard_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                align_self: AlignSelf::FlexEnd,
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(5.0),
+                    right: Val::Px(15.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                scoreboard_string(&TeamScores::default(), false),
+                TextStyle {
+                    font: asset_server.load("FiraSans-Bold.ttf"),
+                    font_size: 20.0,
+                    color: Color::BLACK,
+                },
+                Default::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(TeamScoreBoard);
+}
+
+fn update_scoreboard(
+    scores: Res<TeamScores>,
+    game_state: Res<State<GameState>>,
+    mut query: Query<&mut Text, With<TeamScoreBoard>>,
+) {
+    let over = *game_state.current() == GameState::Over;
+    for mut text in query.iter_mut() {
+        text.sections[0].value = scoreboard_string(&scores, over);
+    }
+}
+
+//
+// Plugin
+//
+
+/// Battle between the fleets of each team, with `naval --mode teams`.
+pub struct TeamPlugin;
+
+impl Plugin for TeamPlugin {
+    fn build(&self, app: &mut App) {
+        if app.world.get_resource::<GameMode>() != Some(&GameMode::TeamBattle) {
+            return;
+        }
+        app.init_resource::<TeamScores>()
+            // Once the bases are on the coast.
+            .add_startup_stage_after(
+                "game_setup_scene",
+                "game_setup_fleets",
+                SystemStage::single(fleet_spawn),
+            )
+            .add_startup_system(scoreboard_setup)
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    // Orders given before the boats steer and fire.
+                    .after(GameSystem::Weather)
+                    .before(GameSystem::Ships)
+                    .with_system(crew_orders)
+                    .with_system(team_victory.after(crew_orders)),
+            )
+            .add_system(team_scoring)
+            .add_system(update_scoreboard);
+    }
+}
@@ -1,12 +1,112 @@
 use std::collections::HashMap;
 
 use bevy::{prelude::*, sprite::collide_aabb::collide};
+use rand::Rng;
 
-use crate::common::WinSize;
-use crate::explosion::ExplosionToSpawn;
-use crate::island::Ground;
+use crate::common::*;
+use crate::explosion::{ExplosionEvent, ExplosionKind, HitEvent, Weapon};
+use crate::island::{Ground, TileMap};
+use crate::player::Life;
+use crate::weather::Weather;
 
-const TORPEDO_SPEED: f32 = 50.;
+const TORPEDO_MIN_SPEED: f32 = 20.;
+const TORPEDO_DAMAGE: u32 = 30;
+// Depth, in metres, at which torpedos run.
+const TORPEDO_DEPTH: f32 = 1.5;
+// Radius of the terrain blasted by a torpedo running aground.
+const TORPEDO_BLAST_RADIUS: f32 = 16.;
+
+pub const SPREAD_COUNT: u32 = 3;
+pub const SPREAD_MAX_COUNT: u32 = 6;
+pub const SPREAD_FAN: f32 = 0.2;
+pub const SPREAD_MAX_FAN: f32 = 1.;
+
+const SEEKER_RANGE: f32 = 150.;
+const SEEKER_HALF_ANGLE: f32 = std::f32::consts::PI / 6.;
+const WIRE_LENGTH: f32 = 300.;
+
+//
+// Misc functions
+//
+
+/// Characteristics of a kind of torpedo.
+pub struct TorpedoSpec {
+    pub speed: f32,
+    // Distance to travel before the torpedo can explode.
+    pub arming_distance: f32,
+    // Distance travelled before the torpedo runs out of fuel and sinks.
+    pub run_length: f32,
+    // Probability for the torpedo not to explode on impact.
+    pub dud_probability: f32,
+    // Maximum rotation speed when steered.
+    pub turn_rate: f32,
+}
+
+/// Check if a torpedo explodes when hitting something, that is if it is armed and not a dud.
+fn detonates(
+    game_rng: &mut GameRng,
+    travelled: &Travelled,
+    arming_distance: &ArmingDistance,
+    dud_chance: &DudChance,
+) -> bool {
+    travelled.0 >= arming_distance.0 && game_rng.rng.gen_range(0. ..1.) >= dud_chance.0
+}
+
+/// Explosion of a torpedo fired by the given launcher, or its splash if it failed to go off.
+fn blast(position: Vec3, launcher: &Launcher, detonated: bool) -> ExplosionEvent {
+    let (kind, radius) = if detonated {
+        (ExplosionKind::Torpedo, TORPEDO_BLAST_RADIUS)
+    } else {
+        (ExplosionKind::Splash, 0.)
+    };
+    ExplosionEvent {
+        position,
+        kind,
+        radius,
+        source: Some(launcher.0),
+    }
+}
+
+/// Rotate a torpedo toward the given direction, no faster than the given angle.
+fn steer(torpedo_tf: &mut Transform, direction: Vec2, max_angle: f32) {
+    let heading = torpedo_tf.rotation.mul_vec3(Vec3::X).truncate();
+    let angle = heading.angle_between(direction);
+    torpedo_tf.rotate(Quat::from_rotation_z(angle.clamp(-max_angle, max_angle)));
+}
+
+/// Spawn a torpedo launched by the given boat.
+pub fn torpedo_spawn(
+    commands: &mut Commands,
+    sprite_materials: &SpriteMaterials,
+    kind: TorpedoKind,
+    launcher: Entity,
+    position: Vec3,
+    rotation: Quat,
+) -> Entity {
+    let mut torpedo = commands.spawn_bundle(SpriteSheetBundle {
+        texture_atlas: sprite_materials.texture.clone(),
+        sprite: TextureAtlasSprite::new(sprite_materials.torpedo_index),
+        transform: Transform {
+            translation: Vec3::new(position.x, position.y, TORPEDO_Z),
+            rotation,
+            ..Default::default()
+        },
+        ..Default::default()
+    });
+    let spec = kind.spec();
+    torpedo
+        .insert(Torpedo)
+        .insert(kind)
+        .insert(Launcher(launcher))
+        .insert(Travelled(0.))
+        .insert(ArmingDistance(spec.arming_distance))
+        .insert(MaxRun(spec.run_length))
+        .insert(DudChance(spec.dud_probability));
+    if kind == TorpedoKind::WireGuided {
+        torpedo.insert(Wire);
+    }
+    torpedo.id()
+}
 
 //
 // Components
@@ -15,23 +115,165 @@ const TORPEDO_SPEED: f32 = 50.;
 #[derive(Component)]
 pub struct Torpedo;
 
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TorpedoKind {
+    // Runs straight ahead, launched in spreads.
+    Straight,
+    // Turns toward the nearest hull within its seeker cone.
+    Homing,
+    // Steered from the launcher until the wire is cut.
+    WireGuided,
+}
+
+impl TorpedoKind {
+    pub fn spec(&self) -> TorpedoSpec {
+        match self {
+            TorpedoKind::Straight => TorpedoSpec {
+                speed: 50.,
+                arming_distance: 30.,
+                run_length: 400.,
+                dud_probability: 0.05,
+                turn_rate: 0.,
+            },
+            TorpedoKind::Homing => TorpedoSpec {
+                speed: 40.,
+                arming_distance: 60.,
+                run_length: 500.,
+                dud_probability: 0.1,
+                turn_rate: std::f32::consts::PI / 4.,
+            },
+            TorpedoKind::WireGuided => TorpedoSpec {
+                speed: 45.,
+                arming_distance: 40.,
+                run_length: 600.,
+                dud_probability: 0.1,
+                turn_rate: std::f32::consts::PI / 3.,
+            },
+        }
+    }
+
+    /// Next kind, to cycle through the kinds of torpedo.
+    pub fn next(&self) -> TorpedoKind {
+        match self {
+            TorpedoKind::Straight => TorpedoKind::Homing,
+            TorpedoKind::Homing => TorpedoKind::WireGuided,
+            TorpedoKind::WireGuided => TorpedoKind::Straight,
+        }
+    }
+}
+
+/// Setting of the torpedo tube of a boat.
+#[derive(Component, Clone, Copy)]
+pub struct TorpedoTube {
+    pub kind: TorpedoKind,
+    // Number of torpedos launched in a straight-running spread.
+    pub spread_count: u32,
+    // Angle between the first and last torpedos of a spread.
+    pub spread_fan: f32,
+}
+
+impl Default for TorpedoTube {
+    fn default() -> Self {
+        TorpedoTube {
+            kind: TorpedoKind::Straight,
+            spread_count: SPREAD_COUNT,
+            spread_fan: SPREAD_FAN,
+        }
+    }
+}
+
+impl TorpedoTube {
+    /// Angles, relative to the torpedo sight, of the torpedos launched in a salvo.
+    pub fn salvo(&self) -> Vec<f32> {
+        if self.kind != TorpedoKind::Straight || self.spread_count <= 1 {
+            return vec![0.];
+        }
+        let step = self.spread_fan / (self.spread_count - 1) as f32;
+        (0..self.spread_count)
+            .map(|i| -0.5 * self.spread_fan + i as f32 * step)
+            .collect()
+    }
+}
+
+/// Boat that launched the torpedo.
+#[derive(Component)]
+pub struct Launcher(pub Entity);
+
+/// Distance travelled since launch.
+#[derive(Component)]
+pub struct Travelled(pub f32);
+
+/// Distance to travel before the torpedo can explode.
+#[derive(Component)]
+pub struct ArmingDistance(pub f32);
+
+/// Distance after which the torpedo runs out of fuel and sinks.
+#[derive(Component)]
+pub struct MaxRun(pub f32);
+
+/// Probability for the torpedo not to explode on impact.
+#[derive(Component)]
+pub struct DudChance(pub f32);
+
+/// A wire-guided torpedo still connected to its launcher.
+#[derive(Component)]
+pub struct Wire;
+
+//
+// Resources
+//
+
+/// Probability for every torpedo not to explode on impact, instead of that of its kind, with
+/// `naval --dud-chance <probability>`.
+#[derive(Clone, Copy, Default)]
+pub struct DudProbability(pub Option<f32>);
+
 //
 // Systems
 //
 
+fn torpedo_dud_setting(
+    dud_probability: Res<DudProbability>,
+    mut query: Query<&mut DudChance, Added<Torpedo>>,
+) {
+    if let Some(probability) = dud_probability.0 {
+        for mut dud_chance in query.iter_mut() {
+            dud_chance.0 = probability;
+        }
+    }
+}
+
 fn torpedo_movement(
     mut commands: Commands,
-    time: Res<Time>,
+    time: Res<GameTime>,
     win_size: Res<WinSize>,
-    mut query: Query<(Entity, &mut Transform), With<Torpedo>>,
+    weather: Res<Weather>,
+    mut explosions: EventWriter<ExplosionEvent>,
+    mut query: Query<
+        (
+            Entity,
+            &mut Transform,
+            &TorpedoKind,
+            &mut Travelled,
+            &MaxRun,
+            &Launcher,
+        ),
+        With<Torpedo>,
+    >,
 ) {
-    for (torpedo_entity, mut torpedo_tf) in query.iter_mut() {
+    for (torpedo_entity, mut torpedo_tf, kind, mut travelled, max_run, launcher) in query.iter_mut()
+    {
+        // Torpedos are slowed down when travelling against the current.
+        let heading = torpedo_tf.rotation.mul_vec3(Vec3::X).truncate();
+        let current = weather.current_at(torpedo_tf.translation);
+        let speed = (kind.spec().speed + heading.dot(current)).max(TORPEDO_MIN_SPEED);
         // Compute new torpedo position.
         let translation =
             torpedo_tf
                 .rotation
-                .mul_vec3(Vec3::new(TORPEDO_SPEED * time.delta_seconds(), 0., 0.));
+                .mul_vec3(Vec3::new(speed * time.delta_seconds(), 0., 0.));
         torpedo_tf.translation += translation;
+        travelled.0 += speed * time.delta_seconds();
         // Remove torpedo if off screen.
         if (torpedo_tf.translation.y < -0.5 * win_size.h)
             || (0.5 * win_size.h < torpedo_tf.translation.y)
@@ -39,18 +281,112 @@ fn torpedo_movement(
             || (0.5 * win_size.w < torpedo_tf.translation.x)
         {
             commands.entity(torpedo_entity).despawn();
+        } else if travelled.0 > max_run.0 {
+            // Out of fuel: the torpedo sinks.
+            commands.entity(torpedo_entity).despawn();
+            explosions.send(blast(torpedo_tf.translation, launcher, false));
+        }
+    }
+}
+
+fn torpedo_homing(
+    time: Res<GameTime>,
+    mut query_torpedo: Query<(&mut Transform, &TorpedoKind, &Launcher), With<Torpedo>>,
+    query_hull: Query<(Entity, &Transform), (With<Life>, Without<Torpedo>)>,
+) {
+    for (mut torpedo_tf, kind, launcher) in query_torpedo.iter_mut() {
+        if *kind != TorpedoKind::Homing {
+            continue;
+        }
+        // Look for the nearest hull within the seeker cone.
+        let heading = torpedo_tf.rotation.mul_vec3(Vec3::X).truncate();
+        let mut target: Option<Vec2> = None;
+        let mut nearest = SEEKER_RANGE;
+        for (hull_entity, hull_tf) in query_hull.iter() {
+            if hull_entity == launcher.0 {
+                continue;
+            }
+            let direction = (hull_tf.translation - torpedo_tf.translation).truncate();
+            if direction.length() <= nearest
+                && heading.angle_between(direction).abs() <= SEEKER_HALF_ANGLE
+            {
+                nearest = direction.length();
+                target = Some(direction);
+            }
+        }
+        // Turn toward it.
+        if let Some(direction) = target {
+            steer(
+                &mut torpedo_tf,
+                direction,
+                kind.spec().turn_rate * time.delta_seconds(),
+            );
+        }
+    }
+}
+
+fn torpedo_wire_guidance(
+    mut commands: Commands,
+    keyboard_input: Res<Input<KeyCode>>,
+    time: Res<GameTime>,
+    mut query_torpedo: Query<(Entity, &mut Transform, &TorpedoKind, &Launcher), With<Wire>>,
+    query_launcher: Query<&Transform, Without<Wire>>,
+) {
+    for (torpedo_entity, mut torpedo_tf, kind, launcher) in query_torpedo.iter_mut() {
+        // The wire is cut if the torpedo goes too far from its launcher.
+        let connected = match query_launcher.get(launcher.0) {
+            Ok(launcher_tf) => {
+                launcher_tf.translation.distance(torpedo_tf.translation) <= WIRE_LENGTH
+            }
+            Err(_) => false,
+        };
+        if !connected {
+            commands.entity(torpedo_entity).remove::<Wire>();
+            continue;
         }
+        // Steer the torpedo with the torpedo sight keys.
+        let delta_angle = if keyboard_input.pressed(KeyCode::U) {
+            kind.spec().turn_rate * time.delta_seconds()
+        } else if keyboard_input.pressed(KeyCode::O) {
+            -kind.spec().turn_rate * time.delta_seconds()
+        } else {
+            0.
+        };
+        torpedo_tf.rotate(Quat::from_rotation_z(delta_angle));
     }
 }
 
 fn torpedo_ground_collision(
     mut commands: Commands,
-    mut query_torpedo: Query<(Entity, &Transform), With<Torpedo>>,
+    mut game_rng: ResMut<GameRng>,
+    mut explosions: EventWriter<ExplosionEvent>,
+    mut query_torpedo: Query<
+        (
+            Entity,
+            &Transform,
+            &Travelled,
+            &ArmingDistance,
+            &DudChance,
+            &Launcher,
+        ),
+        With<Torpedo>,
+    >,
+    tile_map: Res<TileMap>,
     query_moutain: Query<&Transform, With<Ground>>,
 ) {
     let mut despawned = HashMap::new();
     // for each torpedo & ground.
-    for (torpedo_entity, torpedo_tf) in query_torpedo.iter_mut() {
+    for (torpedo_entity, torpedo_tf, travelled, arming_distance, dud_chance, launcher) in
+        query_torpedo.iter_mut()
+    {
+        // Torpedos run aground in shallow waters and on reefs.
+        if tile_map.depth(tile_of(torpedo_tf.translation)) < TORPEDO_DEPTH {
+            despawned.insert(torpedo_entity, true);
+            commands.entity(torpedo_entity).despawn();
+            let detonated = detonates(&mut game_rng, travelled, arming_distance, dud_chance);
+            explosions.send(blast(torpedo_tf.translation, launcher, detonated));
+            continue;
+        }
         for ground_tf in query_moutain.iter() {
             // Check for collision.
             let collision = collide(
@@ -59,15 +395,76 @@ fn torpedo_ground_collision(
                 ground_tf.translation,
                 Vec2::splat(16.),
             );
-            // If collision, replace torpedo by an explosion.
-            if collision.is_some() {
-                if !despawned.contains_key(&torpedo_entity) {
-                    despawned.insert(torpedo_entity, true);
+            // If collision, the torpedo runs aground and explodes if it can, or splashes.
+            if collision.is_some() && !despawned.contains_key(&torpedo_entity) {
+                despawned.insert(torpedo_entity, true);
+                commands.entity(torpedo_entity).despawn();
+                let detonated = detonates(&mut game_rng, travelled, arming_distance, dud_chance);
+                explosions.send(blast(torpedo_tf.translation, launcher, detonated));
+            }
+        }
+    }
+}
+
+fn torpedo_hull_collision(
+    mut commands: Commands,
+    friendly_fire: Res<FriendlyFire>,
+    mut game_rng: ResMut<GameRng>,
+    mut explosions: EventWriter<ExplosionEvent>,
+    mut hits: EventWriter<HitEvent>,
+    query_torpedo: Query<
+        (
+            Entity,
+            &Transform,
+            &Travelled,
+            &ArmingDistance,
+            &DudChance,
+            &Launcher,
+        ),
+        With<Torpedo>,
+    >,
+    mut query_hull: Query<
+        (
+            Entity,
+            &Transform,
+            &TextureAtlasSprite,
+            &mut Life,
+            Option<&Team>,
+        ),
+        Without<Torpedo>,
+    >,
+    query_team: Query<&Team>,
+) {
+    for (torpedo_entity, torpedo_tf, travelled, arming_distance, dud_chance, launcher) in
+        query_torpedo.iter()
+    {
+        // Torpedos pass harmlessly under hulls until armed.
+        if travelled.0 < arming_distance.0 {
+            continue;
+        }
+        let source = query_team.get(launcher.0).ok();
+        for (hull_entity, hull_tf, sprite, mut life, team) in query_hull.iter_mut() {
+            if let Some(size) = sprite.custom_size {
+                if hull_contains(hull_tf, size, torpedo_tf.translation) {
                     commands.entity(torpedo_entity).despawn();
+                    // A dud, or a hull the torpedo spares, only gets a splash.
+                    let detonated =
+                        detonates(&mut game_rng, travelled, arming_distance, dud_chance)
+                            && harms(&friendly_fire, source, team);
+                    if detonated {
+                        let before = life.0;
+                        life.0 -= life.0.min(TORPEDO_DAMAGE);
+                        hits.send(HitEvent::new(
+                            Some(launcher.0),
+                            hull_entity,
+                            Weapon::Torpedo,
+                            before,
+                            life.0,
+                        ));
+                    }
+                    explosions.send(blast(torpedo_tf.translation, launcher, detonated));
+                    break;
                 }
-                commands
-                    .spawn()
-                    .insert(ExplosionToSpawn(torpedo_tf.translation.clone()));
             }
         }
     }
@@ -81,7 +478,142 @@ pub struct TorpedoPlugin;
 
 impl Plugin for TorpedoPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system(torpedo_movement)
-            .add_system(torpedo_ground_collision);
+        // Unless a replay already set the probability of the game recorded.
+        if app.world.get_resource::<DudProbability>().is_none() {
+            let args: Vec<String> = std::env::args().collect();
+            let dud_probability = args
+                .iter()
+                .position(|arg| arg == "--dud-chance")
+                .and_then(|i| args.get(i + 1))
+                .and_then(|probability| probability.parse::<f32>().ok())
+                .map(|probability| probability.clamp(0., 1.));
+            app.insert_resource(DudProbability(dud_probability));
+        }
+        app.add_system_set(
+            SystemSet::new()
+                .label(GameSystem::Torpedos)
+                .after(GameSystem::CanonBalls)
+                // Before the torpedos launched at the previous tick can hit anything.
+                .with_system(torpedo_dud_setting)
+                .with_system(torpedo_movement.after(torpedo_dud_setting))
+                .with_system(torpedo_homing.after(torpedo_movement))
+                .with_system(torpedo_wire_guidance.after(torpedo_homing))
+                .with_system(torpedo_ground_collision.after(torpedo_wire_guidance))
+                .with_system(torpedo_hull_collision.after(torpedo_ground_collision)),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    const HULL_LIFE: u32 = 100;
+
+    /// Kinds of the explosions of the update.
+    #[derive(Default)]
+    struct Explosions(Vec<ExplosionKind>);
+
+    fn explosions_record(
+        mut explosions: ResMut<Explosions>,
+        mut events: EventReader<ExplosionEvent>,
+    ) {
+        explosions.0 = events.iter().map(|explosion| explosion.kind).collect();
+    }
+
+    /// Game with only the torpedo systems, updated by ticks of a tenth of a second.
+    fn test_app(seed: u64) -> App {
+        let mut app = App::new();
+        app.insert_resource(GameRng::new(seed))
+            .insert_resource(GameTime {
+                delta: Duration::from_millis(100),
+            })
+            .insert_resource(WinSize { w: 1000., h: 700. })
+            .init_resource::<Weather>()
+            .init_resource::<FriendlyFire>()
+            .add_event::<ExplosionEvent>()
+            .add_event::<HitEvent>()
+            .init_resource::<Explosions>()
+            .add_system(torpedo_movement)
+            .add_system(torpedo_hull_collision)
+            .add_system_to_stage(CoreStage::PostUpdate, explosions_record);
+        app
+    }
+
+    fn hull_spawn(app: &mut App) -> Entity {
+        let mut sprite = TextureAtlasSprite::new(0);
+        sprite.custom_size = Some(Vec2::new(40., 8.));
+        app.world
+            .spawn()
+            .insert(Transform::from_xyz(0., 0., BOAT_Z))
+            .insert(sprite)
+            .insert(Life(HULL_LIFE))
+            .id()
+    }
+
+    /// Torpedo at the given position, which already travelled the given distance.
+    fn torpedo_test_spawn(app: &mut App, position: Vec3, travelled: f32, dud: f32) -> Entity {
+        let launcher = app.world.spawn().id();
+        let spec = TorpedoKind::Straight.spec();
+        app.world
+            .spawn()
+            .insert(Transform::from_translation(position))
+            .insert(Torpedo)
+            .insert(TorpedoKind::Straight)
+            .insert(Launcher(launcher))
+            .insert(Travelled(travelled))
+            .insert(ArmingDistance(spec.arming_distance))
+            .insert(MaxRun(spec.run_length))
+            .insert(DudChance(dud))
+            .id()
+    }
+
+    fn explosions(app: &App) -> Vec<ExplosionKind> {
+        app.world.get_resource::<Explosions>().unwrap().0.clone()
+    }
+
+    fn life(app: &App, hull: Entity) -> u32 {
+        app.world.get::<Life>(hull).unwrap().0
+    }
+
+    #[test]
+    fn unarmed_torpedo_does_not_explode() {
+        let mut app = test_app(1);
+        let hull = hull_spawn(&mut app);
+        let torpedo = torpedo_test_spawn(&mut app, Vec3::ZERO, 0., 0.);
+        app.update();
+        assert!(app.world.get_entity(torpedo).is_some());
+        assert_eq!(life(&app, hull), HULL_LIFE);
+        assert!(explosions(&app).is_empty());
+    }
+
+    #[test]
+    fn torpedo_sinks_past_max_run() {
+        let mut app = test_app(1);
+        let run_length = TorpedoKind::Straight.spec().run_length;
+        let torpedo = torpedo_test_spawn(&mut app, Vec3::new(200., 200., 0.), run_length, 0.);
+        app.update();
+        assert!(app.world.get_entity(torpedo).is_none());
+        assert_eq!(explosions(&app), vec![ExplosionKind::Splash]);
+    }
+
+    #[test]
+    fn dud_chance_decides_detonation() {
+        let armed = TorpedoKind::Straight.spec().arming_distance;
+        let mut app = test_app(0);
+        let hull = hull_spawn(&mut app);
+        torpedo_test_spawn(&mut app, Vec3::ZERO, armed, 1.);
+        app.update();
+        assert_eq!(life(&app, hull), HULL_LIFE);
+        assert_eq!(explosions(&app), vec![ExplosionKind::Splash]);
+
+        let mut app = test_app(0);
+        let hull = hull_spawn(&mut app);
+        torpedo_test_spawn(&mut app, Vec3::ZERO, armed, 0.);
+        app.update();
+        assert_eq!(life(&app, hull), HULL_LIFE - TORPEDO_DAMAGE);
+        assert_eq!(explosions(&app), vec![ExplosionKind::Torpedo]);
     }
 }
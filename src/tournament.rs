@@ -0,0 +1,632 @@
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::env::{Action, BotObservation, Contact, Env, EnvConfig, ShipState, TICK};
+use crate::player::CANON_MAX_DISTANCE;
+
+// Bots playing when none is given.
+const DEFAULT_BOTS: [&str; 3] = ["hunter", "circler", "turret"];
+// Games played by each pair of bots when not given.
+const DEFAULT_GAMES: u32 = 10;
+const ELO_START: f32 = 1500.;
+const ELO_K: f32 = 32.;
+// Difference of rating beyond which a win is an upset, worth a replay.
+const UPSET_ELO: f32 = 100.;
+// Water shallower than this is steered away from, the boats drawing 2 metres.
+const MIN_DEPTH: f32 = 2.5;
+// Distance the hunter closes to before firing.
+const HUNTER_RANGE: f32 = 0.6 * CANON_MAX_DISTANCE;
+
+//
+// Misc functions
+//
+
+/// Value following an option on the command line.
+fn arg_value(args: &[String], option: &str) -> Option<String> {
+    let i = args.iter().position(|arg| arg == option)?;
+    args.get(i + 1).cloned()
+}
+
+/// Bot from its name on the command line: one of the built-in bots, or `exec:<command>`.
+fn bot_new(spec: &str) -> io::Result<Box<dyn Bot>> {
+    match spec {
+        "hunter" => Ok(Box::new(Hunter)),
+        "circler" => Ok(Box::new(Circler)),
+        "turret" => Ok(Box::new(Turret)),
+        _ => match spec.strip_prefix("exec:") {
+            Some(command) => Ok(Box::new(ProcessBot::new(command)?)),
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("unknown bot {}", spec),
+            )),
+        },
+    }
+}
+
+/// Closest boat still afloat.
+fn nearest(observation: &BotObservation) -> Option<&Contact> {
+    observation
+        .ships
+        .iter()
+        .filter(|contact| contact.life > 0)
+        .min_by(|a, b| {
+            (a.x * a.x + a.y * a.y)
+                .partial_cmp(&(b.x * b.x + b.y * b.y))
+                .unwrap()
+        })
+}
+
+/// Rudder turning the boat toward the given direction.
+fn rudder_toward(ship: &ShipState, x: f32, y: f32) -> f32 {
+    let mut turn = y.atan2(x) - ship.angle;
+    while turn > std::f32::consts::PI {
+        turn -= 2. * std::f32::consts::PI;
+    }
+    while turn < -std::f32::consts::PI {
+        turn += 2. * std::f32::consts::PI;
+    }
+    (2. * turn).clamp(-1., 1.)
+}
+
+/// Check if shallow water or land lies on the next tiles ahead of the boat.
+fn shoal_ahead(observation: &BotObservation) -> bool {
+    let side = (observation.tiles.len() as f32).sqrt() as i32;
+    let view = side / 2;
+    let (sin, cos) = observation.ship.angle.sin_cos();
+    (1..=view.min(2)).any(|k| {
+        let x = view + (cos * k as f32).round() as i32;
+        let y = view + (sin * k as f32).round() as i32;
+        matches!(observation.tiles.get((y * side + x) as usize), Some(depth) if *depth < MIN_DEPTH)
+    })
+}
+
+/// Fire at the given boat if it is within range of a loaded canon.
+fn fire_at(observation: &BotObservation, contact: Option<&Contact>) -> Option<(f32, f32)> {
+    let ship = &observation.ship;
+    let contact = contact?;
+    let in_range =
+        contact.x * contact.x + contact.y * contact.y <= CANON_MAX_DISTANCE * CANON_MAX_DISTANCE;
+    if in_range && ship.reload <= 0. && ship.amunitions > 0 {
+        Some((contact.x, contact.y))
+    } else {
+        None
+    }
+}
+
+/// Expected score of a player against another, from their ratings.
+fn elo_expected(rating: f32, other: f32) -> f32 {
+    1. / (1. + 10f32.powf((other - rating) / 400.))
+}
+
+/// Play a game between two bots, recording its steps for a replay.
+fn game_play(
+    env: &mut Env,
+    bots: [&mut Box<dyn Bot>; 2],
+    seed: u64,
+) -> (GameResult, Vec<ReplayStep>) {
+    let mut observation = env.reset(seed);
+    let start: Vec<u32> = observation.bots.iter().map(|bot| bot.ship.life).collect();
+    let mut sunk_at: Vec<Option<u32>> = vec![None; 2];
+    let mut steps = Vec::new();
+    let [bot0, bot1] = bots;
+    loop {
+        let actions = vec![
+            bot0.act(observation.step, &observation.bots[0]),
+            bot1.act(observation.step, &observation.bots[1]),
+        ];
+        let (next, _, done) = env.step(&actions);
+        steps.push(ReplayStep {
+            ships: observation
+                .bots
+                .iter()
+                .map(|bot| bot.ship.clone())
+                .collect(),
+            actions,
+        });
+        observation = next;
+        for (i, bot) in observation.bots.iter().enumerate() {
+            if bot.ship.life == 0 && sunk_at[i].is_none() {
+                sunk_at[i] = Some(observation.step);
+            }
+        }
+        if done {
+            break;
+        }
+    }
+
+    let ships: Vec<&ShipState> = observation.bots.iter().map(|bot| &bot.ship).collect();
+    let afloat: Vec<usize> = (0..2).filter(|i| ships[*i].life > 0).collect();
+    // A game lasting until the end of time is a draw.
+    let winner = match afloat.as_slice() {
+        [i] => Some(*i),
+        _ => None,
+    };
+    let seconds = |step: u32| step as f32 * env.config().ticks_per_step as f32 * TICK;
+    let result = GameResult {
+        seed,
+        bots: [0, 1],
+        winner,
+        seconds: seconds(env.steps()),
+        sides: [0, 1].map(|i| SideResult {
+            damage_dealt: ships[i].damage_dealt,
+            damage_taken: start[i].saturating_sub(ships[i].life),
+            shots: ships[i].shots,
+            hits: ships[i].hits,
+            survival: seconds(sunk_at[i].unwrap_or_else(|| env.steps())),
+        }),
+    };
+    (result, steps)
+}
+
+fn replay_write(dir: &str, name: &str, replay: &GameReplay) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    let file = File::create(Path::new(dir).join(name))?;
+    serde_json::to_writer(file, replay)?;
+    Ok(())
+}
+
+fn replay_read(path: &str) -> io::Result<GameReplay> {
+    let file = File::open(path)?;
+    Ok(serde_json::from_reader(BufReader::new(file))?)
+}
+
+/// Play a kept game again from its seed and actions, and tell the first step at which the
+/// boats differ from the recorded ones, if any.
+fn replay_check(replay: &GameReplay) -> Option<usize> {
+    let mut env = Env::new(replay.config.clone());
+    let mut observation = env.reset(replay.seed);
+    for (i, step) in replay.steps.iter().enumerate() {
+        let ships: Vec<&ShipState> = observation.bots.iter().map(|bot| &bot.ship).collect();
+        if serde_json::to_value(&ships).ok() != serde_json::to_value(&step.ships).ok() {
+            return Some(i);
+        }
+        observation = env.step(&step.actions).0;
+    }
+    None
+}
+
+fn report_write(path: &str, report: &Report) -> io::Result<()> {
+    let mut file = File::create(path)?;
+    if path.ends_with(".json") {
+        serde_json::to_writer_pretty(&mut file, report)?;
+        return writeln!(file);
+    }
+    writeln!(
+        file,
+        "bot,elo,games,wins,losses,draws,damage_dealt,damage_taken,shots,hits,survival"
+    )?;
+    for bot in report.bots.iter() {
+        writeln!(
+            file,
+            "{},{:.0},{},{},{},{},{},{},{},{},{:.1}",
+            bot.name,
+            bot.elo,
+            bot.games,
+            bot.wins,
+            bot.losses,
+            bot.draws,
+            bot.damage_dealt,
+            bot.damage_taken,
+            bot.shots,
+            bot.hits,
+            bot.survival
+        )?;
+    }
+    Ok(())
+}
+
+fn report_print(report: &Report) {
+    println!(
+        "{:<24} {:>5} {:>5} {:>5} {:>5} {:>5} {:>7} {:>7} {:>6} {:>5} {:>8}",
+        "bot",
+        "elo",
+        "games",
+        "wins",
+        "loss",
+        "draw",
+        "dealt",
+        "taken",
+        "shots",
+        "hits",
+        "survival"
+    );
+    for bot in report.bots.iter() {
+        println!(
+            "{:<24} {:>5.0} {:>5} {:>5} {:>5} {:>5} {:>7} {:>7} {:>6} {:>5} {:>7.1}s",
+            bot.name,
+            bot.elo,
+            bot.games,
+            bot.wins,
+            bot.losses,
+            bot.draws,
+            bot.damage_dealt,
+            bot.damage_taken,
+            bot.shots,
+            bot.hits,
+            bot.survival
+        );
+    }
+}
+
+/// Pit bots against each other over many seeded seas, with `naval --tournament`.
+pub fn run() {
+    let args: Vec<String> = std::env::args().collect();
+    let mut specs: Vec<String> = args
+        .iter()
+        .zip(args.iter().skip(1))
+        .filter(|(option, _)| *option == "--bot")
+        .map(|(_, spec)| spec.clone())
+        .collect();
+    if specs.is_empty() {
+        specs = DEFAULT_BOTS.iter().map(|spec| spec.to_string()).collect();
+    }
+    let games: u32 = arg_value(&args, "--games")
+        .and_then(|games| games.parse().ok())
+        .unwrap_or(DEFAULT_GAMES);
+    let seed: u64 = arg_value(&args, "--seed")
+        .and_then(|seed| seed.parse().ok())
+        .unwrap_or(0);
+    let report_path = arg_value(&args, "--report");
+    let replays_dir = arg_value(&args, "--replays");
+
+    // A game kept by an earlier tournament, played again.
+    if let Some(path) = arg_value(&args, "--replay") {
+        match replay_read(&path).map(|replay| (replay_check(&replay), replay)) {
+            Ok((None, replay)) => println!(
+                "{}: {} steps played again as recorded, won by {}",
+                path,
+                replay.steps.len(),
+                replay.winner.as_deref().unwrap_or("nobody")
+            ),
+            Ok((Some(step), _)) => println!("{}: the game differs from step {}", path, step),
+            Err(err) => eprintln!("Cannot read replay {}: {}", path, err),
+        }
+        return;
+    }
+
+    let mut bots = Vec::new();
+    for spec in specs.iter() {
+        match bot_new(spec) {
+            Ok(bot) => bots.push(bot),
+            Err(err) => {
+                eprintln!("{}", err);
+                return;
+            }
+        }
+    }
+    if bots.len() < 2 {
+        eprintln!("A tournament needs at least two bots");
+        return;
+    }
+
+    let mut stats: Vec<BotStats> = specs
+        .iter()
+        .map(|spec| BotStats {
+            name: spec.clone(),
+            elo: ELO_START,
+            ..Default::default()
+        })
+        .collect();
+    let mut results = Vec::new();
+    // Shortest game won by a bot, replayed at the end.
+    let mut shortest: Option<(f32, GameReplay)> = None;
+
+    let mut env = Env::new(EnvConfig::default());
+    for g in 0..games {
+        for a in 0..bots.len() {
+            for b in a + 1..bots.len() {
+                // Each bot takes each side in turn, on the same seas.
+                let sides = if g % 2 == 0 { [a, b] } else { [b, a] };
+                let game_seed = seed + g as u64;
+                let (first, second) = bots.split_at_mut(sides[0].max(sides[1]));
+                let (bot0, bot1) = if sides[0] < sides[1] {
+                    (&mut first[sides[0]], &mut second[0])
+                } else {
+                    (&mut second[0], &mut first[sides[1]])
+                };
+                let (mut result, steps) = game_play(&mut env, [bot0, bot1], game_seed);
+                result.bots = sides;
+
+                // Ratings before the game, to spot the upsets.
+                let elos = sides.map(|i| stats[i].elo);
+                for (side, i) in sides.iter().enumerate() {
+                    let score = match result.winner {
+                        Some(winner) if winner == side => 1.,
+                        Some(_) => 0.,
+                        None => 0.5,
+                    };
+                    let bot = &mut stats[*i];
+                    bot.elo += ELO_K * (score - elo_expected(elos[side], elos[1 - side]));
+                    bot.games += 1;
+                    match result.winner {
+                        Some(winner) if winner == side => bot.wins += 1,
+                        Some(_) => bot.losses += 1,
+                        None => bot.draws += 1,
+                    }
+                    let side_result = &result.sides[side];
+                    bot.damage_dealt += side_result.damage_dealt;
+                    bot.damage_taken += side_result.damage_taken;
+                    bot.shots += side_result.shots;
+                    bot.hits += side_result.hits;
+                    bot.survival += side_result.survival;
+                }
+
+                if let Some(dir) = &replays_dir {
+                    let replay = GameReplay {
+                        seed: game_seed,
+                        config: env.config().clone(),
+                        bots: sides.map(|i| specs[i].clone()),
+                        winner: result.winner.map(|side| specs[sides[side]].clone()),
+                        steps,
+                    };
+                    let upset = matches!(result.winner, Some(winner)
+                        if elos[winner] + UPSET_ELO < elos[1 - winner]);
+                    if upset {
+                        let name = format!("upset-{}.json", results.len());
+                        if let Err(err) = replay_write(dir, &name, &replay) {
+                            eprintln!("Cannot write replay {}: {}", name, err);
+                        }
+                    }
+                    let shorter = match &shortest {
+                        Some((seconds, _)) => result.seconds < *seconds,
+                        None => true,
+                    };
+                    if result.winner.is_some() && shorter {
+                        shortest = Some((result.seconds, replay));
+                    }
+                }
+                results.push(result);
+            }
+        }
+    }
+    if let (Some(dir), Some((_, replay))) = (&replays_dir, &shortest) {
+        if let Err(err) = replay_write(dir, "shortest.json", replay) {
+            eprintln!("Cannot write replay shortest.json: {}", err);
+        }
+    }
+
+    for bot in stats.iter_mut() {
+        // Mean time afloat per game.
+        bot.survival /= bot.games.max(1) as f32;
+    }
+    let report = Report {
+        bots: stats,
+        games: results
+            .iter()
+            .map(|result| GameReport {
+                seed: result.seed,
+                bots: result.bots.map(|i| specs[i].clone()),
+                winner: result.winner.map(|side| specs[result.bots[side]].clone()),
+                seconds: result.seconds,
+                sides: result.sides.clone(),
+            })
+            .collect(),
+    };
+    report_print(&report);
+    if let Some(path) = report_path {
+        if let Err(err) = report_write(&path, &report) {
+            eprintln!("Cannot write report {}: {}", path, err);
+        }
+    }
+}
+
+//
+// Bots
+//
+
+/// Player of a boat, given what it sees at each step.
+pub trait Bot {
+    fn act(&mut self, step: u32, observation: &BotObservation) -> Action;
+}
+
+/// Sails to the closest boat and fires once close enough.
+struct Hunter;
+
+impl Bot for Hunter {
+    fn act(&mut self, _step: u32, observation: &BotObservation) -> Action {
+        let contact = nearest(observation);
+        let mut action = Action {
+            throttle: 1.,
+            ..Default::default()
+        };
+        if let Some(contact) = contact {
+            action.rudder = rudder_toward(&observation.ship, contact.x, contact.y);
+            if contact.x * contact.x + contact.y * contact.y < HUNTER_RANGE * HUNTER_RANGE {
+                action.throttle = 0.3;
+            }
+        }
+        if shoal_ahead(observation) {
+            action.throttle = 0.3;
+            action.rudder = 1.;
+        }
+        action.fire = fire_at(observation, contact);
+        action
+    }
+}
+
+/// Sails in circles, firing at whatever comes within range.
+struct Circler;
+
+impl Bot for Circler {
+    fn act(&mut self, _step: u32, observation: &BotObservation) -> Action {
+        Action {
+            throttle: if shoal_ahead(observation) { 0.3 } else { 1. },
+            rudder: 1.,
+            fire: fire_at(observation, nearest(observation)),
+        }
+    }
+}
+
+/// Stays still, firing at whatever comes within range.
+struct Turret;
+
+impl Bot for Turret {
+    fn act(&mut self, _step: u32, observation: &BotObservation) -> Action {
+        Action {
+            fire: fire_at(observation, nearest(observation)),
+            ..Default::default()
+        }
+    }
+}
+
+/// Bot run as another program, reading each observation as a JSON line on its stdin and
+/// answering with an action on its stdout.
+struct ProcessBot {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl ProcessBot {
+    fn new(command: &str) -> io::Result<Self> {
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let stdin = child.stdin.take().unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        Ok(ProcessBot {
+            child,
+            stdin,
+            stdout,
+        })
+    }
+}
+
+impl Bot for ProcessBot {
+    fn act(&mut self, step: u32, observation: &BotObservation) -> Action {
+        let request = json!({ "step": step, "observation": observation });
+        if writeln!(self.stdin, "{}", request)
+            .and_then(|_| self.stdin.flush())
+            .is_err()
+        {
+            return Action::default();
+        }
+        // A bot which does not answer properly leaves its boat adrift.
+        let mut line = String::new();
+        match self.stdout.read_line(&mut line) {
+            Ok(n) if n > 0 => serde_json::from_str(&line).unwrap_or_default(),
+            _ => Action::default(),
+        }
+    }
+}
+
+impl Drop for ProcessBot {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+//
+// Resources
+//
+
+#[derive(Clone, Debug, Default, Serialize)]
+struct SideResult {
+    // Life taken from the other boat by the weapons of the bot, and life lost.
+    damage_dealt: u32,
+    damage_taken: u32,
+    shots: u32,
+    hits: u32,
+    // Seconds afloat.
+    survival: f32,
+}
+
+struct GameResult {
+    seed: u64,
+    // Index of the bot on each side.
+    bots: [usize; 2],
+    // Side left afloat, none for a draw.
+    winner: Option<usize>,
+    seconds: f32,
+    sides: [SideResult; 2],
+}
+
+#[derive(Default, Serialize)]
+struct BotStats {
+    name: String,
+    elo: f32,
+    games: u32,
+    wins: u32,
+    losses: u32,
+    draws: u32,
+    damage_dealt: u32,
+    damage_taken: u32,
+    shots: u32,
+    hits: u32,
+    // Mean seconds afloat per game.
+    survival: f32,
+}
+
+#[derive(Serialize)]
+struct GameReport {
+    seed: u64,
+    bots: [String; 2],
+    winner: Option<String>,
+    seconds: f32,
+    sides: [SideResult; 2],
+}
+
+#[derive(Serialize)]
+struct Report {
+    bots: Vec<BotStats>,
+    games: Vec<GameReport>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ReplayStep {
+    // Boats at the start of the step, and what the bots did with them.
+    ships: Vec<ShipState>,
+    actions: Vec<Action>,
+}
+
+/// Game kept for being notable: its seed and actions play it again with `Env`, as done by
+/// `naval --tournament --replay <file>`.
+#[derive(Serialize, Deserialize)]
+struct GameReplay {
+    seed: u64,
+    config: EnvConfig,
+    bots: [String; 2],
+    winner: Option<String>,
+    steps: Vec<ReplayStep>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kept_games_play_again() {
+        let config = EnvConfig {
+            max_steps: 300,
+            ..Default::default()
+        };
+        let mut env = Env::new(config.clone());
+        let mut hunter: Box<dyn Bot> = Box::new(Hunter);
+        let mut turret: Box<dyn Bot> = Box::new(Turret);
+        let (result, steps) = game_play(&mut env, [&mut hunter, &mut turret], 3);
+        // The damage dealt by a bot is part of the damage taken by the other.
+        for side in 0..2 {
+            assert!(result.sides[side].damage_dealt <= result.sides[1 - side].damage_taken);
+        }
+
+        let replay = GameReplay {
+            seed: 3,
+            config,
+            bots: [String::from("hunter"), String::from("turret")],
+            winner: None,
+            steps,
+        };
+        let text = serde_json::to_string(&replay).unwrap();
+        let replay: GameReplay = serde_json::from_str(&text).unwrap();
+        assert_eq!(replay_check(&replay), None);
+    }
+}
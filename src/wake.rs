@@ -0,0 +1,226 @@
+use bevy::prelude::*;
+use rand::{thread_rng, Rng};
+
+use crate::common::*;
+use crate::explosion::{ExplosionEvent, ExplosionKind};
+use crate::fog::Fog;
+use crate::player::{Ship, ShipClass, Speed, BOAT_MAX_SPEED_FORWARD};
+use crate::torpedo::Torpedo;
+
+// Number of particles shared by all the wakes.
+const POOL_SIZE: usize = 512;
+// Particles emitted per second by a boat at full speed.
+const FOAM_RATE: f32 = 30.;
+const BOW_WAVE_RATE: f32 = 20.;
+const BUBBLE_RATE: f32 = 15.;
+const RIPPLE_COUNT: usize = 12;
+// Speed, in pixels per second, at which particles spread.
+const BOW_WAVE_SPREAD: f32 = 20.;
+const RIPPLE_SPREAD: f32 = 40.;
+
+//
+// Misc functions
+//
+
+/// Number of particles to emit this frame for the given rate per second.
+fn emission_count(rate: f32, delta: f32) -> u32 {
+    let count = rate * delta;
+    count.floor() as u32 + (thread_rng().gen::<f32>() < count.fract()) as u32
+}
+
+//
+// Components
+//
+
+#[derive(Clone, Copy)]
+enum ParticleKind {
+    Foam,
+    Bubble,
+    Ripple,
+}
+
+impl ParticleKind {
+    /// Lifetime, initial size, final size and color of the particles.
+    fn spec(&self) -> (f32, f32, f32, Color) {
+        match self {
+            ParticleKind::Foam => (1.5, 4., 12., Color::rgba(1., 1., 1., 0.6)),
+            ParticleKind::Bubble => (1., 3., 5., Color::rgba(0.8, 0.95, 1., 0.7)),
+            ParticleKind::Ripple => (0.8, 4., 2., Color::rgba(0.9, 0.97, 1., 0.8)),
+        }
+    }
+}
+
+/// Particle of foam, taken from the pool while its timer runs.
+#[derive(Component)]
+struct Particle {
+    kind: ParticleKind,
+    velocity: Vec2,
+    timer: Timer,
+}
+
+//
+// Resources
+//
+
+/// Particles not currently in use.
+struct WakePool(Vec<Entity>);
+
+impl WakePool {
+    /// Take a particle from the pool, if any left, and start it at the given position.
+    fn emit(
+        &mut self,
+        query: &mut Query<(&mut Particle, &mut Transform, &mut Visibility)>,
+        kind: ParticleKind,
+        position: Vec3,
+        velocity: Vec2,
+    ) {
+        if let Some(entity) = self.0.pop() {
+            if let Ok((mut particle, mut transform, mut visibility)) = query.get_mut(entity) {
+                particle.kind = kind;
+                particle.velocity = velocity;
+                particle.timer = Timer::from_seconds(kind.spec().0, false);
+                transform.translation = Vec3::new(position.x, position.y, WAKE_Z);
+                visibility.is_visible = true;
+            }
+        }
+    }
+}
+
+//
+// Systems
+//
+
+fn wake_setup(mut commands: Commands, mut images: ResMut<Assets<Image>>) {
+    // Particles are plain white squares, tinted and scaled while they live.
+    let texture = images.add(blank_image());
+    // Particles are spawned once and hidden until emitted.
+    let mut pool = Vec::with_capacity(POOL_SIZE);
+    for _ in 0..POOL_SIZE {
+        let entity = commands
+            .spawn_bundle(SpriteBundle {
+                texture: texture.clone(),
+                visibility: Visibility { is_visible: false },
+                ..Default::default()
+            })
+            .insert(Particle {
+                kind: ParticleKind::Foam,
+                velocity: Vec2::ZERO,
+                timer: Timer::from_seconds(0., false),
+            })
+            .id();
+        pool.push(entity);
+    }
+    commands.insert_resource(WakePool(pool));
+}
+
+fn wake_emission(
+    time: Res<GameTime>,
+    fog: Res<Fog>,
+    mut pool: ResMut<WakePool>,
+    query_ship: Query<(&Transform, &Speed, &ShipClass), (With<Ship>, Without<Particle>)>,
+    query_torpedo: Query<&Transform, (With<Torpedo>, Without<Particle>)>,
+    mut explosions: EventReader<ExplosionEvent>,
+    mut query_particle: Query<(&mut Particle, &mut Transform, &mut Visibility)>,
+) {
+    let delta = time.delta_seconds();
+    let mut rng = thread_rng();
+    // Foam behind the hulls and waves spreading from their bows, as fast as they go.
+    for (ship_tf, speed, class) in query_ship.iter() {
+        if !fog.is_visible(ship_tf.translation) {
+            continue;
+        }
+        let throttle = speed.0.abs() / BOAT_MAX_SPEED_FORWARD;
+        let heading = ship_tf.rotation.mul_vec3(Vec3::X);
+        let side = ship_tf.rotation.mul_vec3(Vec3::Y).truncate();
+        let length = class.spec().size.x;
+        for _ in 0..emission_count(throttle * FOAM_RATE, delta) {
+            let offset = rng.gen_range(-3. ..3.) * side.extend(0.);
+            let stern = ship_tf.translation - 0.5 * length * heading + offset;
+            pool.emit(&mut query_particle, ParticleKind::Foam, stern, Vec2::ZERO);
+        }
+        for _ in 0..emission_count(throttle * BOW_WAVE_RATE, delta) {
+            let bow = ship_tf.translation + 0.4 * length * heading;
+            for sign in [-1., 1.] {
+                let velocity = sign * BOW_WAVE_SPREAD * side;
+                pool.emit(&mut query_particle, ParticleKind::Foam, bow, velocity);
+            }
+        }
+    }
+    // Bubbles give away torpedos.
+    for torpedo_tf in query_torpedo.iter() {
+        if !fog.is_visible(torpedo_tf.translation) {
+            continue;
+        }
+        for _ in 0..emission_count(BUBBLE_RATE, delta) {
+            let position = torpedo_tf.translation + rng.gen_range(-2. ..2.) * Vec3::ONE;
+            pool.emit(
+                &mut query_particle,
+                ParticleKind::Bubble,
+                position,
+                Vec2::ZERO,
+            );
+        }
+    }
+    // Rings of ripples where canonballs splash.
+    for explosion in explosions.iter() {
+        if explosion.kind != ExplosionKind::Splash || !fog.is_visible(explosion.position) {
+            continue;
+        }
+        for i in 0..RIPPLE_COUNT {
+            let angle = 2. * std::f32::consts::PI * i as f32 / RIPPLE_COUNT as f32;
+            let velocity = RIPPLE_SPREAD * Vec2::new(angle.cos(), angle.sin());
+            pool.emit(
+                &mut query_particle,
+                ParticleKind::Ripple,
+                explosion.position,
+                velocity,
+            );
+        }
+    }
+}
+
+fn wake_update(
+    time: Res<GameTime>,
+    mut pool: ResMut<WakePool>,
+    mut query: Query<(
+        Entity,
+        &mut Particle,
+        &mut Transform,
+        &mut Sprite,
+        &mut Visibility,
+    )>,
+) {
+    for (entity, mut particle, mut transform, mut sprite, mut visibility) in query.iter_mut() {
+        if !visibility.is_visible {
+            continue;
+        }
+        particle.timer.tick(time.delta());
+        if particle.timer.finished() {
+            // Give the particle back to the pool.
+            visibility.is_visible = false;
+            pool.0.push(entity);
+            continue;
+        }
+        // Particles spread, grow or shrink and fade away.
+        let (_, start_size, end_size, color) = particle.kind.spec();
+        let t = particle.timer.percent();
+        transform.translation += (particle.velocity * time.delta_seconds()).extend(0.);
+        sprite.custom_size = Some(Vec2::splat(start_size + t * (end_size - start_size)));
+        sprite.color = color;
+        sprite.color.set_a(color.a() * (1. - t));
+    }
+}
+
+//
+// Plugin
+//
+
+pub struct WakePlugin;
+
+impl Plugin for WakePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(wake_setup)
+            .add_system(wake_emission)
+            .add_system(wake_update);
+    }
+}
@@ -0,0 +1,197 @@
+use bevy::prelude::*;
+
+use crate::common::*;
+use crate::fog::Fog;
+use crate::island::{TerrainChanged, TerrainReset, Tile, TileMap, DEEP_WATER_DEPTH};
+use crate::weather::Weather;
+
+const DEEP_COLOR: (f32, f32, f32) = (0., 0.4118, 0.5804);
+const SHALLOW_COLOR: (f32, f32, f32) = (0.1, 0.62, 0.66);
+const REEF_COLOR: (f32, f32, f32) = (0.35, 0.5, 0.45);
+// Waves travel along the wind, with a wavelength of a few tiles.
+const WAVE_NUMBER: f32 = 2. * std::f32::consts::PI / 96.;
+const WAVE_SPEED: f32 = 1.5;
+const WAVE_BRIGHTNESS: f32 = 0.04;
+const SURF_MAX_ALPHA: f32 = 0.5;
+const UNEXPLORED_DIMMING: f32 = 0.5;
+
+//
+// Misc functions
+//
+
+/// Height of the waves, from -1 to 1, at the given position.
+fn wave(weather: &Weather, position: Vec3, time: f32) -> f32 {
+    let direction = Vec2::new(weather.wind_angle.cos(), weather.wind_angle.sin());
+    (WAVE_NUMBER * direction.dot(position.truncate()) - WAVE_SPEED * time).sin()
+}
+
+/// Spawn the water of the given tile, with surf if along the coast.
+fn water_tile_spawn(
+    commands: &mut Commands,
+    texture: &Handle<Image>,
+    tile_map: &TileMap,
+    tile: (i32, i32),
+) {
+    // The shallower the water, the lighter, with reefs showing through.
+    let shallowness = 1. - tile_map.depth(tile) / DEEP_WATER_DEPTH;
+    let lerp = |deep: f32, shallow: f32| deep + shallowness * (shallow - deep);
+    let color = if tile_map.get(tile) == Some(Tile::Reef) {
+        REEF_COLOR
+    } else {
+        (
+            lerp(DEEP_COLOR.0, SHALLOW_COLOR.0),
+            lerp(DEEP_COLOR.1, SHALLOW_COLOR.1),
+            lerp(DEEP_COLOR.2, SHALLOW_COLOR.2),
+        )
+    };
+    let translation = Vec3::new(16. * tile.0 as f32, 16. * tile.1 as f32, WATER_Z);
+    commands
+        .spawn_bundle(SpriteBundle {
+            texture: texture.clone(),
+            sprite: Sprite {
+                custom_size: Some(Vec2::splat(16.)),
+                ..Default::default()
+            },
+            transform: Transform::from_translation(translation),
+            ..Default::default()
+        })
+        .insert(Water(color.0, color.1, color.2));
+    // Surf breaks along the coast.
+    let coast = [(1, 0), (-1, 0), (0, 1), (0, -1)]
+        .iter()
+        .any(|(dx, dy)| tile_map.is_land((tile.0 + dx, tile.1 + dy)));
+    if coast {
+        commands
+            .spawn_bundle(SpriteBundle {
+                texture: texture.clone(),
+                sprite: Sprite {
+                    custom_size: Some(Vec2::splat(16.)),
+                    ..Default::default()
+                },
+                transform: Transform::from_xyz(translation.x, translation.y, SURF_Z),
+                ..Default::default()
+            })
+            .insert(Surf);
+    }
+}
+
+/// Spawn the water of all the tiles of the sea.
+fn water_tiles_spawn(commands: &mut Commands, texture: &Handle<Image>, tile_map: &TileMap) {
+    for x in -tile_map.w_tiles..=tile_map.w_tiles {
+        for y in -tile_map.h_tiles..=tile_map.h_tiles {
+            if !tile_map.is_land((x, y)) {
+                water_tile_spawn(commands, texture, tile_map, (x, y));
+            }
+        }
+    }
+}
+
+//
+// Components
+//
+
+/// Water tile, of the given color in calm weather.
+#[derive(Component)]
+struct Water(f32, f32, f32);
+
+/// Foam breaking on the coast.
+#[derive(Component)]
+struct Surf;
+
+//
+// Resources
+//
+
+struct WaterTexture(Handle<Image>);
+
+//
+// Systems
+//
+
+fn water_spawn(mut commands: Commands, tile_map: Res<TileMap>, mut images: ResMut<Assets<Image>>) {
+    let texture = images.add(blank_image());
+    water_tiles_spawn(&mut commands, &texture, &tile_map);
+    commands.insert_resource(WaterTexture(texture));
+}
+
+fn water_terrain_reset(
+    mut commands: Commands,
+    tile_map: Res<TileMap>,
+    texture: Res<WaterTexture>,
+    mut terrain_reset: EventReader<TerrainReset>,
+    query: Query<Entity, Or<(With<Water>, With<Surf>)>>,
+) {
+    if terrain_reset.iter().count() == 0 {
+        return;
+    }
+    for entity in query.iter() {
+        commands.entity(entity).despawn();
+    }
+    water_tiles_spawn(&mut commands, &texture.0, &tile_map);
+}
+
+fn water_terrain_change(
+    mut commands: Commands,
+    tile_map: Res<TileMap>,
+    texture: Res<WaterTexture>,
+    mut terrain_changed: EventReader<TerrainChanged>,
+) {
+    // Blasted coasts are flooded.
+    for TerrainChanged(tile) in terrain_changed.iter() {
+        if !tile_map.is_land(*tile) {
+            water_tile_spawn(&mut commands, &texture.0, &tile_map, *tile);
+        }
+    }
+}
+
+fn water_animation(
+    time: Res<Time>,
+    weather: Res<Weather>,
+    fog: Res<Fog>,
+    mut query: Query<(&Transform, &Water, &mut Sprite)>,
+) {
+    let t = time.seconds_since_startup() as f32;
+    for (water_tf, water, mut sprite) in query.iter_mut() {
+        let brightness = 1. + WAVE_BRIGHTNESS * wave(&weather, water_tf.translation, t);
+        let dimming = if fog.explored.contains(&tile_of(water_tf.translation)) {
+            1.
+        } else {
+            UNEXPLORED_DIMMING
+        };
+        let factor = dimming * brightness;
+        sprite.color = Color::rgb(factor * water.0, factor * water.1, factor * water.2);
+    }
+}
+
+fn surf_animation(
+    time: Res<Time>,
+    weather: Res<Weather>,
+    mut query: Query<(&Transform, &mut Sprite), With<Surf>>,
+) {
+    // Foam rises as each wave reaches the coast.
+    let t = time.seconds_since_startup() as f32;
+    for (surf_tf, mut sprite) in query.iter_mut() {
+        let crest = wave(&weather, surf_tf.translation, t).max(0.);
+        sprite.color = Color::rgba(1., 1., 1., SURF_MAX_ALPHA * crest);
+    }
+}
+
+//
+// Plugin
+//
+
+pub struct WaterPlugin;
+
+impl Plugin for WaterPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_stage_after(
+            "game_setup_scene",
+            "game_setup_water",
+            SystemStage::single(water_spawn),
+        )
+        .add_system(water_terrain_change)
+        .add_system(water_terrain_reset)
+        .add_system(water_animation)
+        .add_system(surf_animation);
+    }
+}
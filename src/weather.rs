@@ -0,0 +1,113 @@
+use std::f32::consts::PI;
+
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::common::{GameRng, GameSystem, GameTime};
+
+const WIND_MAX_STRENGTH: f32 = 20.;
+const CURRENT_MAX_STRENGTH: f32 = 10.;
+// Period between two changes of the weather.
+const WEATHER_PERIOD: f32 = 10.;
+// Maximum change of direction of the wind & current at each period.
+const WEATHER_MAX_VEER: f32 = PI / 6.;
+// Rate at which the weather moves toward its next state.
+const WEATHER_INERTIA: f32 = 0.2;
+// Distance over which the current varies across the map.
+const CURRENT_WAVELENGTH: f32 = 400.;
+
+//
+// Resources
+//
+
+#[derive(Clone)]
+pub struct Weather {
+    pub wind_angle: f32,
+    pub wind_strength: f32,
+    pub current_angle: f32,
+    pub current_strength: f32,
+    // State the weather is moving toward.
+    next_wind_angle: f32,
+    next_wind_strength: f32,
+    next_current_angle: f32,
+    next_current_strength: f32,
+    timer: Timer,
+}
+
+impl Weather {
+    /// Wind blowing over the whole map.
+    pub fn wind(&self) -> Vec2 {
+        self.wind_strength * Vec2::new(self.wind_angle.cos(), self.wind_angle.sin())
+    }
+
+    /// Sea current at the given position: the main stream swirls and its strength varies
+    /// across the map.
+    pub fn current_at(&self, position: Vec3) -> Vec2 {
+        let angle = self.current_angle + 0.5 * (2. * PI * position.y / CURRENT_WAVELENGTH).sin();
+        let strength = self.current_strength
+            * (0.75 + 0.25 * (2. * PI * position.x / CURRENT_WAVELENGTH).sin());
+        strength * Vec2::new(angle.cos(), angle.sin())
+    }
+}
+
+impl FromWorld for Weather {
+    fn from_world(world: &mut World) -> Self {
+        let rng = &mut world.get_resource_mut::<GameRng>().unwrap().rng;
+        let wind_angle = rng.gen_range(-PI..PI);
+        let wind_strength = rng.gen_range(0. ..WIND_MAX_STRENGTH);
+        let current_angle = rng.gen_range(-PI..PI);
+        let current_strength = rng.gen_range(0. ..CURRENT_MAX_STRENGTH);
+        Weather {
+            wind_angle,
+            wind_strength,
+            current_angle,
+            current_strength,
+            next_wind_angle: wind_angle,
+            next_wind_strength: wind_strength,
+            next_current_angle: current_angle,
+            next_current_strength: current_strength,
+            timer: Timer::from_seconds(WEATHER_PERIOD, true),
+        }
+    }
+}
+
+//
+// Systems
+//
+
+fn weather_update(
+    time: Res<GameTime>,
+    mut game_rng: ResMut<GameRng>,
+    mut weather: ResMut<Weather>,
+) {
+    // Periodically pick the next state of the weather.
+    weather.timer.tick(time.delta());
+    if weather.timer.just_finished() {
+        let rng = &mut game_rng.rng;
+        weather.next_wind_angle =
+            weather.wind_angle + rng.gen_range(-WEATHER_MAX_VEER..WEATHER_MAX_VEER);
+        weather.next_wind_strength = rng.gen_range(0. ..WIND_MAX_STRENGTH);
+        weather.next_current_angle =
+            weather.current_angle + rng.gen_range(-WEATHER_MAX_VEER..WEATHER_MAX_VEER);
+        weather.next_current_strength = rng.gen_range(0. ..CURRENT_MAX_STRENGTH);
+    }
+    // Smoothly move toward it.
+    let ratio = (WEATHER_INERTIA * time.delta_seconds()).min(1.);
+    weather.wind_angle += ratio * (weather.next_wind_angle - weather.wind_angle);
+    weather.wind_strength += ratio * (weather.next_wind_strength - weather.wind_strength);
+    weather.current_angle += ratio * (weather.next_current_angle - weather.current_angle);
+    weather.current_strength += ratio * (weather.next_current_strength - weather.current_strength);
+}
+
+//
+// Plugin
+//
+
+pub struct WeatherPlugin;
+
+impl Plugin for WeatherPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Weather>()
+            .add_system(weather_update.label(GameSystem::Weather));
+    }
+}